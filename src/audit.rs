@@ -0,0 +1,99 @@
+use crate::json;
+
+// how one conflict of a written file was decided
+pub struct Entry {
+  pub file: String,
+  // first and last line of the conflict markers in the file as it was read, 1-based
+  pub lines: Option<(usize, usize)>,
+  // local, incoming, both, nothing, edited or unresolved
+  pub resolution: &'static str,
+  pub edited: bool,
+}
+
+// one JSON object per line and conflict, appended so every write of a merge stays in the log
+pub fn append(path: &str, entries: &[Entry]) -> std::io::Result<()> {
+  let time = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_secs());
+  let mut text = String::new();
+
+  for entry in entries {
+    text.push_str(&line(entry, time));
+    text.push('\n');
+  }
+
+  let mut file = std::fs::OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)?;
+  std::io::Write::write_all(&mut file, text.as_bytes())
+}
+
+fn line(entry: &Entry, time: u64) -> String {
+  let number = |n: usize| json::Value::Number(n.to_string());
+  let value = json::object(vec![
+    (String::from("time"), json::string(&timestamp(time))),
+    (String::from("file"), json::string(&entry.file)),
+    (
+      String::from("lines"),
+      entry.lines.map_or(json::Value::Null, |(start, end)| {
+        json::array(vec![number(start), number(end)])
+      }),
+    ),
+    (String::from("resolution"), json::string(entry.resolution)),
+    (String::from("edited"), json::Value::Bool(entry.edited)),
+  ]);
+
+  json::print(&value, "").concat()
+}
+
+// seconds since the epoch as an ISO 8601 UTC time
+fn timestamp(time: u64) -> String {
+  let (days, seconds) = (time / 86400, time % 86400);
+
+  // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+  let z = days as i64 + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+    year,
+    month,
+    day,
+    seconds / 3600,
+    seconds % 3600 / 60,
+    seconds % 60
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn line() {
+    let entry = crate::audit::Entry {
+      file: String::from("src/\"a\".rs"),
+      lines: Some((3, 9)),
+      resolution: "edited",
+      edited: true,
+    };
+
+    assert_eq!(
+      crate::audit::line(&entry, 0),
+      "{\"time\": \"1970-01-01T00:00:00Z\",\"file\": \"src/\\\"a\\\".rs\",\"lines\": [3,9],\
+       \"resolution\": \"edited\",\"edited\": true}"
+    );
+  }
+
+  #[test]
+  fn timestamp() {
+    assert_eq!(crate::audit::timestamp(951782400), "2000-02-29T00:00:00Z");
+    assert_eq!(crate::audit::timestamp(1791981296), "2026-10-14T12:34:56Z");
+  }
+}
@@ -0,0 +1,228 @@
+use clap::{CommandFactory, Parser, Subcommand, ValueHint};
+use clap_complete::Shell;
+
+// the whole command line, files are opened in the editor unless a subcommand is given
+#[derive(Debug, Parser)]
+#[command(
+  name = "mersge",
+  version,
+  about = "resolve merge conflicts",
+  args_conflicts_with_subcommands = true,
+  disable_help_subcommand = true
+)]
+pub struct Cli {
+  #[command(subcommand)]
+  pub command: Option<Command>,
+  #[arg(
+    long,
+    group = "strategy",
+    help = "resolve every conflict with the local side and write the files without the editor"
+  )]
+  pub ours: bool,
+  #[arg(long, group = "strategy", help = "the same with the incoming side")]
+  pub theirs: bool,
+  #[arg(
+    long,
+    group = "strategy",
+    help = "the same keeping both sides, local first"
+  )]
+  pub union: bool,
+  #[arg(
+    short,
+    long,
+    value_name = "path",
+    value_hint = ValueHint::FilePath,
+    help = "write the result to <path> and leave the file as it is"
+  )]
+  pub output: Option<String>,
+  #[arg(
+    long,
+    help = "print the result of --ours, --theirs or --union instead of writing it"
+  )]
+  pub stdout: bool,
+  #[arg(
+    long,
+    value_name = "against",
+    value_parser = ["input", "base"],
+    help = "print the resolution as a unified diff against the conflicted input or the base"
+  )]
+  pub patch: Option<String>,
+  #[arg(
+    long,
+    value_name = "path",
+    value_hint = ValueHint::FilePath,
+    help = "run the resolution commands in <path> (- for stdin) instead of opening the editor"
+  )]
+  pub script: Option<String>,
+  #[arg(
+    long,
+    help = "print the parsed files, buffers and conflicts as JSON instead of opening them"
+  )]
+  pub dump_json: bool,
+  #[arg(
+    long,
+    help = "open the conflicted files of `git status --porcelain` read from stdin"
+  )]
+  pub from_git_status: bool,
+  #[arg(
+    long,
+    value_name = "dir",
+    value_hint = ValueHint::DirPath,
+    help = "open every file under <dir> with conflict markers, .gitignore is respected"
+  )]
+  pub scan: Vec<String>,
+  #[arg(
+    long,
+    value_name = "name",
+    help = "read and write the files in <name>, e.g. utf-8, latin1, shift_jis or utf-16le"
+  )]
+  pub encoding: Option<String>,
+  #[arg(
+    long,
+    help = "copy each file to <file>.orig before it is first written"
+  )]
+  pub backup: bool,
+  #[arg(long, help = "run `git add` on files written with no conflicts left")]
+  pub stage: bool,
+  #[arg(long, help = "open files that have no conflicts too")]
+  pub open_clean: bool,
+  #[arg(long, help = "open files with malformed conflict markers")]
+  pub force: bool,
+  #[arg(long, help = "merge .json, .yaml and .toml files line by line")]
+  pub lines: bool,
+  #[arg(
+    long,
+    help = "merge the versions in the git index again instead of reading the markers"
+  )]
+  pub index: bool,
+  #[arg(
+    long,
+    value_name = "side",
+    value_parser = ["ours", "theirs", "context"],
+    help = "resolve conflicts that differ only in whitespace with ours, theirs or context"
+  )]
+  pub whitespace: Option<String>,
+  #[arg(
+    long,
+    value_name = "path",
+    value_hint = ValueHint::FilePath,
+    help = "resolve conflicts the way <path>, an already merged copy of the file, does"
+  )]
+  pub reference: Option<String>,
+  #[arg(
+    value_name = "file",
+    value_hint = ValueHint::FilePath,
+    help = "conflicted files, - reads one from stdin"
+  )]
+  pub files: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum Command {
+  #[command(about = "list conflict markers left in files, exits with 1 when there are any")]
+  Check {
+    #[arg(long, help = "print the number of conflicts per file and a total")]
+    count: bool,
+    #[arg(
+      value_name = "path",
+      value_hint = ValueHint::FilePath,
+      help = "files and directories, stdin without any"
+    )]
+    paths: Vec<String>,
+  },
+  #[command(about = "print every conflict of the files, as JSON with --json")]
+  List {
+    #[arg(long, help = "print an array with an object per file")]
+    json: bool,
+    #[arg(
+      value_name = "file",
+      value_hint = ValueHint::FilePath,
+      required = true
+    )]
+    files: Vec<String>,
+  },
+  #[command(about = "print a completion script for bash, zsh, fish, powershell or elvish")]
+  Completions {
+    #[arg(value_name = "shell")]
+    shell: Shell,
+  },
+}
+
+// completion script for `shell` made from the definition of the command line
+pub fn completions(shell: Shell) -> String {
+  let mut script = vec![];
+  clap_complete::generate(shell, &mut Cli::command(), "mersge", &mut script);
+  String::from_utf8_lossy(&script).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use clap::{CommandFactory, Parser};
+
+  #[test]
+  fn parse() {
+    let cli = crate::cli::Cli::try_parse_from([
+      "mersge",
+      "--ours",
+      "-o",
+      "out",
+      "a",
+      "--whitespace=context",
+      "-",
+      "--",
+      "--b",
+    ])
+    .unwrap();
+
+    assert!(cli.ours);
+    assert_eq!(cli.output.as_deref(), Some("out"));
+    assert_eq!(cli.whitespace.as_deref(), Some("context"));
+    assert_eq!(cli.files, vec!["a", "-", "--b"]);
+
+    let cli = crate::cli::Cli::try_parse_from(["mersge", "check", "--count", "src"]).unwrap();
+    assert_eq!(
+      cli.command,
+      Some(crate::cli::Command::Check {
+        count: true,
+        paths: vec![String::from("src")]
+      })
+    );
+
+    let parse = |args: &[&str]| crate::cli::Cli::try_parse_from(args);
+    assert!(parse(&["mersge", "--nope"]).is_err());
+    assert!(parse(&["mersge", "--output"]).is_err());
+    assert!(parse(&["mersge", "--stage=yes"]).is_err());
+    assert!(parse(&["mersge", "--ours", "--theirs", "a"]).is_err());
+    assert!(parse(&["mersge", "--patch", "head", "a"]).is_err());
+  }
+
+  #[test]
+  fn help() {
+    crate::cli::Cli::command().debug_assert();
+    let help = crate::cli::Cli::command().render_help().to_string();
+
+    assert!(help.contains("  -o, --output <path>"));
+    assert!(help.contains("\n      --stage "));
+    assert!(help.contains("\n  check "));
+  }
+
+  #[test]
+  fn completions() {
+    use clap_complete::Shell;
+
+    for shell in [
+      Shell::Bash,
+      Shell::Zsh,
+      Shell::Fish,
+      Shell::PowerShell,
+      Shell::Elvish,
+    ] {
+      let script = crate::cli::completions(shell);
+      assert!(script.contains("stage"), "{}", shell);
+      assert!(script.contains("check"), "{}", shell);
+    }
+
+    assert!(crate::cli::completions(Shell::Bash).contains("ours theirs context"));
+    assert!(crate::cli::Cli::try_parse_from(["mersge", "completions", "tcsh"]).is_err());
+  }
+}
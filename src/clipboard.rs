@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::io::Write;
+
+thread_local! {
+  // kept open for the whole run, on X11 the copied text lives only as long as its owner
+  static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+  // what tests paste, there is no clipboard where they run
+  #[cfg(test)]
+  pub static PASTED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn copy(text: &str) -> Result<(), String> {
+  match with_clipboard(|clipboard| clipboard.set_text(text)) {
+    Ok(()) => Ok(()),
+    // no clipboard to talk to, ask the terminal to do it (works over ssh too)
+    Err(_) => osc52(text),
+  }
+}
+
+pub fn paste() -> Result<String, String> {
+  #[cfg(test)]
+  if let Some(text) = PASTED.with(|pasted| pasted.borrow().clone()) {
+    return Ok(text);
+  }
+
+  with_clipboard(|clipboard| clipboard.get_text())
+}
+
+fn with_clipboard<T>(
+  f: impl FnOnce(&mut arboard::Clipboard) -> Result<T, arboard::Error>,
+) -> Result<T, String> {
+  CLIPBOARD.with(|clipboard| {
+    let mut clipboard = clipboard.borrow_mut();
+
+    if clipboard.is_none() {
+      *clipboard = Some(arboard::Clipboard::new().map_err(|err| err.to_string())?);
+    }
+
+    f(clipboard.as_mut().unwrap()).map_err(|err| err.to_string())
+  })
+}
+
+fn osc52(text: &str) -> Result<(), String> {
+  let mut stdout = std::io::stdout();
+
+  write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))
+    .and_then(|_| stdout.flush())
+    .map_err(|err| err.to_string())
+}
+
+fn base64(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+  let mut encoded = String::new();
+
+  for chunk in bytes.chunks(3) {
+    let b = [
+      chunk[0],
+      *chunk.get(1).unwrap_or(&0),
+      *chunk.get(2).unwrap_or(&0),
+    ];
+    let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+    for i in 0..4 {
+      if i <= chunk.len() {
+        encoded.push(ALPHABET[(n >> (18 - i * 6)) as usize & 63] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+
+  encoded
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn base64() {
+    assert_eq!(crate::clipboard::base64(b""), "");
+    assert_eq!(crate::clipboard::base64(b"f"), "Zg==");
+    assert_eq!(crate::clipboard::base64(b"fo"), "Zm8=");
+    assert_eq!(crate::clipboard::base64(b"foo"), "Zm9v");
+    assert_eq!(crate::clipboard::base64(b"foobar\n"), "Zm9vYmFyCg==");
+  }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+// how many panes are shown, `Auto` picks two on narrow terminals
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Layout {
+  #[default]
+  Auto,
+  Three,
+  Two,
+}
+
+// which side wins a conflict whose sides differ only in whitespace, `Context` takes the one
+// indented like the lines around it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Whitespace {
+  Ours,
+  Theirs,
+  Context,
+}
+
+pub struct Config {
+  pub guide_column: Option<usize>,
+  pub git_backend: String,
+  pub merge_tool: Option<String>,
+  pub diff_tool: Option<String>,
+  pub permalink: Option<String>,
+  // file every write appends how each conflict was resolved to
+  pub audit_log: Option<String>,
+  pub autowrite: bool,
+  pub autostage: bool,
+  pub fold_resolved: bool,
+  pub context_lines: usize,
+  pub layout: Layout,
+  pub pane_ratios: [u16; 3],
+  pub wrap: bool,
+  pub gutter: bool,
+  pub tab_width: usize,
+  pub backup: bool,
+  pub whitespace: Option<Whitespace>,
+  pub ignore_whitespace: bool,
+  pub structured_merge: bool,
+  // file pattern and the command merging such files, longest pattern first
+  pub drivers: Vec<(String, String)>,
+}
+
+impl Default for Config {
+  fn default() -> Config {
+    Config {
+      guide_column: None,
+      git_backend: String::from("subprocess"),
+      merge_tool: None,
+      diff_tool: None,
+      permalink: None,
+      audit_log: None,
+      autowrite: false,
+      autostage: false,
+      fold_resolved: false,
+      context_lines: 3,
+      layout: Layout::Auto,
+      pane_ratios: [30, 40, 30],
+      wrap: false,
+      gutter: false,
+      tab_width: 4,
+      backup: false,
+      whitespace: None,
+      ignore_whitespace: false,
+      structured_merge: true,
+      drivers: vec![],
+    }
+  }
+}
+
+impl Config {
+  pub fn load() -> Result<Config, String> {
+    let path = match path() {
+      Some(path) => path,
+      None => return Ok(Config::default()),
+    };
+
+    match std::fs::read_to_string(&path) {
+      Ok(content) => Config::parse(&content).map_err(|err| format!("{}: {}", path.display(), err)),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+      Err(err) => Err(format!("{}: {}", path.display(), err)),
+    }
+  }
+
+  pub fn parse(content: &str) -> Result<Config, String> {
+    let values = parse_values(content)?;
+    let mut config = Config::default();
+
+    if let Some(value) = values.get("guide_column") {
+      config.guide_column = Some(parse_number("guide_column", value)?);
+    }
+
+    if let Some(value) = values.get("git_backend") {
+      config.git_backend = value.clone();
+    }
+
+    config.merge_tool = values.get("merge_tool").cloned();
+    config.diff_tool = values.get("diff_tool").cloned();
+    config.permalink = values.get("permalink").cloned();
+    config.audit_log = values.get("audit_log").cloned();
+
+    if let Some(value) = values.get("autowrite") {
+      config.autowrite = parse_bool("autowrite", value)?;
+    }
+
+    if let Some(value) = values.get("autostage") {
+      config.autostage = parse_bool("autostage", value)?;
+    }
+
+    if let Some(value) = values.get("fold_resolved") {
+      config.fold_resolved = parse_bool("fold_resolved", value)?;
+    }
+
+    if let Some(value) = values.get("context_lines") {
+      config.context_lines = parse_number("context_lines", value)?;
+    }
+
+    if let Some(value) = values.get("layout") {
+      config.layout = match value.as_str() {
+        "auto" => Layout::Auto,
+        "three" => Layout::Three,
+        "two" => Layout::Two,
+        _ => {
+          return Err(format!(
+            "`layout` must be auto, three or two, got `{}`",
+            value
+          ))
+        }
+      };
+    }
+
+    if let Some(value) = values.get("pane_ratios") {
+      config.pane_ratios = parse_ratios(value)?;
+    }
+
+    if let Some(value) = values.get("wrap") {
+      config.wrap = parse_bool("wrap", value)?;
+    }
+
+    if let Some(value) = values.get("gutter") {
+      config.gutter = parse_bool("gutter", value)?;
+    }
+
+    if let Some(value) = values.get("tab_width") {
+      config.tab_width = parse_number("tab_width", value)?;
+    }
+
+    if let Some(value) = values.get("backup") {
+      config.backup = parse_bool("backup", value)?;
+    }
+
+    if let Some(value) = values.get("whitespace") {
+      config.whitespace = match value.as_str() {
+        "off" => None,
+        value => Some(parse_whitespace(value)?),
+      };
+    }
+
+    if let Some(value) = values.get("ignore_whitespace") {
+      config.ignore_whitespace = parse_bool("ignore_whitespace", value)?;
+    }
+
+    if let Some(value) = values.get("structured_merge") {
+      config.structured_merge = parse_bool("structured_merge", value)?;
+    }
+
+    config.drivers = values
+      .iter()
+      .filter_map(|(key, command)| {
+        let pattern = key.strip_prefix("drivers.")?.trim_matches('"');
+        Some((String::from(pattern), command.clone()))
+      })
+      .collect();
+    config
+      .drivers
+      .sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(&b.0)));
+
+    Ok(config)
+  }
+
+  // merge driver command for a file, patterns without a `/` match the file name only
+  pub fn driver(&self, file_name: &str) -> Option<&str> {
+    let name = std::path::Path::new(file_name)
+      .file_name()
+      .and_then(|name| name.to_str())
+      .unwrap_or(file_name);
+
+    self
+      .drivers
+      .iter()
+      .find(|(pattern, _)| match pattern.contains('/') {
+        true => glob(pattern, file_name),
+        false => glob(pattern, name),
+      })
+      .map(|(_, command)| command.as_str())
+  }
+}
+
+// `*` matches any run of characters, everything else itself
+fn glob(pattern: &str, text: &str) -> bool {
+  match pattern.split_once('*') {
+    None => pattern == text,
+    Some((prefix, rest)) => {
+      text.starts_with(prefix)
+        && (prefix.len()..=text.len())
+          .filter(|i| text.is_char_boundary(*i))
+          .any(|i| glob(rest, &text[i..]))
+    }
+  }
+}
+
+pub fn parse_whitespace(value: &str) -> Result<Whitespace, String> {
+  match value {
+    "ours" => Ok(Whitespace::Ours),
+    "theirs" => Ok(Whitespace::Theirs),
+    "context" => Ok(Whitespace::Context),
+    _ => Err(format!(
+      "`whitespace` must be ours, theirs, context or off, got `{}`",
+      value
+    )),
+  }
+}
+
+// set a top level `key = value` in the config file, the rest of the file is kept as it is
+pub fn save(key: &str, value: &str) -> Result<(), String> {
+  let path = path().ok_or_else(|| String::from("no config location"))?;
+  let content = match std::fs::read_to_string(&path) {
+    Ok(content) => content,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+    Err(err) => return Err(format!("{}: {}", path.display(), err)),
+  };
+
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+  }
+
+  crate::write_atomic(&path, set_value(&content, key, value).as_bytes())
+    .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn set_value(content: &str, key: &str, value: &str) -> String {
+  let mut lines: Vec<String> = content.lines().map(String::from).collect();
+  let line = format!("{} = {}", key, value);
+
+  // only lines before the first section header are top level
+  let top = lines
+    .iter()
+    .position(|line| line.trim().starts_with('['))
+    .unwrap_or(lines.len());
+
+  let existing = lines[..top].iter().position(|line| {
+    line
+      .split_once('=')
+      .map(|(name, _)| name.trim() == key)
+      .unwrap_or(false)
+  });
+
+  match existing {
+    Some(i) => lines[i] = line,
+    None => lines.insert(top, line),
+  }
+
+  let mut content = lines.join("\n");
+  content.push('\n');
+  content
+}
+
+fn path() -> Option<std::path::PathBuf> {
+  if let Some(path) = std::env::var_os("MERSGE_CONFIG") {
+    return Some(path.into());
+  }
+
+  if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+    return Some(std::path::Path::new(&dir).join("mersge").join("config"));
+  }
+
+  std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config/mersge/config"))
+}
+
+// parse `key = value` lines, keys under a `[section]` header are prefixed with `section.`
+fn parse_values(content: &str) -> Result<HashMap<String, String>, String> {
+  let mut values = HashMap::new();
+  let mut section = String::new();
+
+  for (i, line) in content.lines().enumerate() {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    if line.starts_with('[') && line.ends_with(']') {
+      section = String::from(line[1..line.len() - 1].trim());
+      continue;
+    }
+
+    let (key, value) = match line.split_once('=') {
+      Some((key, value)) => (key.trim(), value.trim()),
+      None => return Err(format!("line {}: expected `key = value`", i + 1)),
+    };
+
+    let key = if section.is_empty() {
+      String::from(key)
+    } else {
+      format!("{}.{}", section, key)
+    };
+
+    values.insert(key, String::from(value));
+  }
+
+  Ok(values)
+}
+
+fn parse_number(key: &str, value: &str) -> Result<usize, String> {
+  value
+    .parse()
+    .map_err(|_| format!("`{}` must be a number, got `{}`", key, value))
+}
+
+fn parse_ratios(value: &str) -> Result<[u16; 3], String> {
+  let error = || {
+    format!(
+      "`pane_ratios` must be three numbers adding up to 100, got `{}`",
+      value
+    )
+  };
+  let numbers: Vec<u16> = value
+    .split_whitespace()
+    .map(|number| number.parse().map_err(|_| error()))
+    .collect::<Result<_, _>>()?;
+
+  match numbers[..] {
+    [a, b, c] if a + b + c == 100 => Ok([a, b, c]),
+    _ => Err(error()),
+  }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+  match value {
+    "true" | "yes" | "on" => Ok(true),
+    "false" | "no" | "off" => Ok(false),
+    _ => Err(format!("`{}` must be true or false, got `{}`", key, value)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn parse() {
+    let config = crate::config::Config::parse("# comment\n\nguide_column = 100\n").unwrap();
+    assert_eq!(config.guide_column, Some(100));
+
+    let config = crate::config::Config::parse("").unwrap();
+    assert_eq!(config.guide_column, None);
+    assert_eq!(config.git_backend, "subprocess");
+
+    assert!(crate::config::Config::parse("guide_column = wide").is_err());
+    assert!(crate::config::Config::parse("guide_column").is_err());
+
+    let config = crate::config::Config::parse("autowrite = yes\nautostage = false").unwrap();
+    assert!(config.autowrite);
+    assert!(!config.autostage);
+    assert!(crate::config::Config::parse("autowrite = maybe").is_err());
+
+    let config = crate::config::Config::parse("pane_ratios = 20 60 20").unwrap();
+    assert_eq!(config.pane_ratios, [20, 60, 20]);
+    assert!(crate::config::Config::parse("pane_ratios = 20 60").is_err());
+    assert!(crate::config::Config::parse("pane_ratios = 50 60 20").is_err());
+
+    let config = crate::config::Config::parse("gutter = true").unwrap();
+    assert!(config.gutter);
+
+    let config = crate::config::Config::parse("layout = two").unwrap();
+    assert_eq!(config.layout, crate::config::Layout::Two);
+    assert!(crate::config::Config::parse("layout = four").is_err());
+
+    let config = crate::config::Config::parse("whitespace = context").unwrap();
+    assert_eq!(config.whitespace, Some(crate::config::Whitespace::Context));
+    assert_eq!(
+      crate::config::Config::parse("whitespace = off")
+        .unwrap()
+        .whitespace,
+      None
+    );
+  }
+
+  #[test]
+  fn driver() {
+    let config =
+      crate::config::Config::parse("[drivers]\n\"*.ipynb\" = nbdime merge\ndocs/*.md = md-merge\n")
+        .unwrap();
+
+    assert_eq!(config.driver("notes/a.ipynb"), Some("nbdime merge"));
+    assert_eq!(config.driver("docs/a.md"), Some("md-merge"));
+    assert_eq!(config.driver("a.md"), None);
+    assert!(crate::config::glob("a*b*c", "abxbc"));
+    assert!(!crate::config::glob("a*b", "abc"));
+  }
+
+  #[test]
+  fn set_value() {
+    assert_eq!(crate::config::set_value("", "a", "1"), "a = 1\n");
+    assert_eq!(
+      crate::config::set_value("# x\na = 1\nb = 2\n", "a", "3"),
+      "# x\na = 3\nb = 2\n"
+    );
+    assert_eq!(
+      crate::config::set_value("b = 2\n[section]\na = 1\n", "a", "3"),
+      "b = 2\na = 3\n[section]\na = 1\n"
+    );
+  }
+
+  #[test]
+  fn parse_values() {
+    let values = crate::config::parse_values("a = 1\n[section]\nb = x = y\n").unwrap();
+    assert_eq!(values["a"], "1");
+    assert_eq!(values["section.b"], "x = y");
+  }
+}
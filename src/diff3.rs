@@ -0,0 +1,137 @@
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+// stretch of a three-way merge of lines
+#[derive(Debug, PartialEq)]
+pub enum Chunk<'a> {
+  // lines both sides agree on, or that only one side changed
+  Clean(Vec<&'a str>),
+  Conflict {
+    base: Vec<&'a str>,
+    ours: Vec<&'a str>,
+    theirs: Vec<&'a str>,
+  },
+}
+
+// merge the lines the way diff3 does: the base lines both sides kept split the files into
+// stretches, one changed by a single side takes that side and one changed by both conflicts
+pub fn merge<'a>(base: &[&'a str], ours: &[&'a str], theirs: &[&'a str]) -> Vec<Chunk<'a>> {
+  let (in_ours, in_theirs) = (kept(base, ours), kept(base, theirs));
+  let mut chunks = vec![];
+  let (mut b, mut o, mut t) = (0, 0, 0);
+
+  loop {
+    let next = (b..base.len()).find_map(|i| Some((i, in_ours[i]?, in_theirs[i]?)));
+    let (b_end, o_end, t_end) = next.unwrap_or((base.len(), ours.len(), theirs.len()));
+
+    changed(
+      &base[b..b_end],
+      &ours[o..o_end],
+      &theirs[t..t_end],
+      &mut chunks,
+    );
+
+    match next {
+      Some((i, o_kept, t_kept)) => {
+        clean(&base[i..i + 1], &mut chunks);
+        (b, o, t) = (i + 1, o_kept + 1, t_kept + 1);
+      }
+      None => return chunks,
+    }
+  }
+}
+
+// for every base line the line of `side` it is kept as
+fn kept(base: &[&str], side: &[&str]) -> Vec<Option<usize>> {
+  let mut kept = vec![None; base.len()];
+
+  for op in capture_diff_slices(Algorithm::Myers, base, side) {
+    if let DiffOp::Equal {
+      old_index,
+      new_index,
+      len,
+    } = op
+    {
+      for i in 0..len {
+        kept[old_index + i] = Some(new_index + i);
+      }
+    }
+  }
+
+  kept
+}
+
+fn changed<'a>(
+  base: &[&'a str],
+  ours: &[&'a str],
+  theirs: &[&'a str],
+  chunks: &mut Vec<Chunk<'a>>,
+) {
+  if ours == base {
+    clean(theirs, chunks);
+  } else if theirs == base || ours == theirs {
+    clean(ours, chunks);
+  } else {
+    chunks.push(Chunk::Conflict {
+      base: base.to_vec(),
+      ours: ours.to_vec(),
+      theirs: theirs.to_vec(),
+    });
+  }
+}
+
+fn clean<'a>(lines: &[&'a str], chunks: &mut Vec<Chunk<'a>>) {
+  match chunks.last_mut() {
+    _ if lines.is_empty() => (),
+    Some(Chunk::Clean(clean)) => clean.extend_from_slice(lines),
+    _ => chunks.push(Chunk::Clean(lines.to_vec())),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::diff3::Chunk;
+
+  #[test]
+  fn merge() {
+    let base = ["a", "b", "c", "d"];
+
+    // one side each, far enough apart
+    assert_eq!(
+      crate::diff3::merge(&base, &["A", "b", "c", "d"], &["a", "b", "c", "D"]),
+      vec![Chunk::Clean(vec!["A", "b", "c", "D"])]
+    );
+
+    assert_eq!(
+      crate::diff3::merge(&base, &["a", "L", "c", "d"], &["a", "R", "c", "d", "e"]),
+      vec![
+        Chunk::Clean(vec!["a"]),
+        Chunk::Conflict {
+          base: vec!["b"],
+          ours: vec!["L"],
+          theirs: vec!["R"],
+        },
+        Chunk::Clean(vec!["c", "d", "e"]),
+      ]
+    );
+
+    // lines that look like markers are only text
+    assert_eq!(
+      crate::diff3::merge(&["======="], &["=======", "x"], &["======="]),
+      vec![Chunk::Clean(vec!["=======", "x"])]
+    );
+
+    // both sides adding the file
+    assert_eq!(
+      crate::diff3::merge(&[], &["same"], &["same"]),
+      vec![Chunk::Clean(vec!["same"])]
+    );
+    assert_eq!(
+      crate::diff3::merge(&[], &["L"], &["R"]),
+      vec![Chunk::Conflict {
+        base: vec![],
+        ours: vec!["L"],
+        theirs: vec!["R"],
+      }]
+    );
+  }
+}
@@ -0,0 +1,198 @@
+use encoding_rs::{EncoderResult, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+// text encoding files are read and written in, the UI always works with UTF-8
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encoding(&'static encoding_rs::Encoding);
+
+impl Default for Encoding {
+  fn default() -> Encoding {
+    Encoding(UTF_8)
+  }
+}
+
+// any label the WHATWG encoding standard knows, e.g. utf-8, latin1, shift_jis or utf-16le
+pub fn from_name(name: &str) -> Result<Encoding, String> {
+  encoding_rs::Encoding::for_label(name.trim().as_bytes())
+    .filter(|encoding| *encoding != encoding_rs::REPLACEMENT)
+    .map(Encoding)
+    .ok_or_else(|| {
+      format!(
+        "unsupported encoding `{}`, use a name like utf-8, latin1, shift_jis or utf-16le",
+        name
+      )
+    })
+}
+
+// the name `from_name` takes for the encoding
+pub fn name(encoding: Encoding) -> &'static str {
+  encoding.0.name()
+}
+
+// a UTF-16 byte order mark decides, then anything that is not valid UTF-8 is taken as latin1
+pub fn detect(bytes: &[u8]) -> Encoding {
+  match encoding_rs::Encoding::for_bom(bytes) {
+    Some((encoding, _)) => Encoding(encoding),
+    None if std::str::from_utf8(bytes).is_ok() => Encoding(UTF_8),
+    None => Encoding(WINDOWS_1252),
+  }
+}
+
+// same check as git: a NUL byte early in the file, which UTF-16 text is full of
+pub fn is_binary(bytes: &[u8]) -> bool {
+  !is_utf16(detect(bytes)) && bytes.iter().take(8000).any(|b| *b == 0)
+}
+
+fn is_utf16(encoding: Encoding) -> bool {
+  encoding.0 == UTF_16LE || encoding.0 == UTF_16BE
+}
+
+pub fn is_utf8(encoding: Encoding) -> bool {
+  encoding.0 == UTF_8
+}
+
+pub fn check_utf8(bytes: &[u8]) -> Result<&str, String> {
+  std::str::from_utf8(bytes).map_err(|err| format!("not valid UTF-8 at byte {}", err.valid_up_to()))
+}
+
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<String, String> {
+  if encoding.0 == UTF_8 {
+    return check_utf8(bytes).map(String::from);
+  }
+
+  // the UTF-16 byte order mark is written back by `encode`
+  let text = match encoding_rs::Encoding::for_bom(bytes) {
+    Some((bom, length)) if bom == encoding.0 && is_utf16(encoding) => &bytes[length..],
+    _ => bytes,
+  };
+
+  encoding
+    .0
+    .decode_without_bom_handling_and_without_replacement(text)
+    .map(|text| text.into_owned())
+    .ok_or_else(|| format!("not valid {}", encoding.0.name()))
+}
+
+// fails on the first character the encoding has no bytes for instead of writing a substitute
+pub fn encode(text: &str, encoding: Encoding) -> Result<Vec<u8>, String> {
+  // encoding_rs only decodes UTF-16, the web never sends it
+  if is_utf16(encoding) {
+    let units = std::iter::once(0xfeff).chain(text.encode_utf16());
+    return Ok(match encoding.0 == UTF_16LE {
+      true => units.flat_map(u16::to_le_bytes).collect(),
+      false => units.flat_map(u16::to_be_bytes).collect(),
+    });
+  }
+
+  let mut encoder = encoding.0.new_encoder();
+  let mut bytes = Vec::with_capacity(text.len());
+  let mut read = 0;
+
+  loop {
+    let (result, done) =
+      encoder.encode_from_utf8_to_vec_without_replacement(&text[read..], &mut bytes, true);
+    read += done;
+
+    match result {
+      EncoderResult::InputEmpty => return Ok(bytes),
+      EncoderResult::OutputFull => bytes.reserve(text.len() - read + 16),
+      EncoderResult::Unmappable(c) => {
+        return Err(format!("{} has no bytes for `{}`", encoding.0.name(), c))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::encoding::Encoding;
+  use encoding_rs::{SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+  #[test]
+  fn from_name() {
+    assert_eq!(crate::encoding::from_name("UTF-8"), Ok(Encoding(UTF_8)));
+    assert_eq!(
+      crate::encoding::from_name("iso-8859-1"),
+      Ok(Encoding(WINDOWS_1252))
+    );
+    assert_eq!(
+      crate::encoding::from_name("shift_jis"),
+      Ok(Encoding(SHIFT_JIS))
+    );
+    assert!(crate::encoding::from_name("iso-2022-kr").is_err());
+    assert!(crate::encoding::from_name("klingon").is_err());
+  }
+
+  #[test]
+  fn name() {
+    for encoding in [UTF_8, WINDOWS_1252, SHIFT_JIS, UTF_16LE, UTF_16BE] {
+      assert_eq!(
+        crate::encoding::from_name(crate::encoding::name(Encoding(encoding))),
+        Ok(Encoding(encoding))
+      );
+    }
+  }
+
+  #[test]
+  fn detect() {
+    assert_eq!(crate::encoding::detect("čau".as_bytes()), Encoding(UTF_8));
+    assert_eq!(crate::encoding::detect(b"caf\xe9"), Encoding(WINDOWS_1252));
+    assert_eq!(
+      crate::encoding::detect(b"\xff\xfea\x00"),
+      Encoding(UTF_16LE)
+    );
+  }
+
+  #[test]
+  fn is_binary() {
+    assert!(crate::encoding::is_binary(b"\x89PNG\r\n\x1a\n\x00\x00"));
+    assert!(!crate::encoding::is_binary(b"\xff\xfea\x00"));
+    assert!(!crate::encoding::is_binary(b"caf\xe9"));
+  }
+
+  #[test]
+  fn decode() {
+    assert_eq!(
+      crate::encoding::decode(b"caf\xe9", Encoding(WINDOWS_1252)).unwrap(),
+      "café"
+    );
+    assert_eq!(
+      crate::encoding::decode(b"\xfe\xff\x00a\x00b", Encoding(UTF_16BE)).unwrap(),
+      "ab"
+    );
+    assert_eq!(
+      crate::encoding::decode(b"\x93\xfa\x96\x7b", Encoding(SHIFT_JIS)).unwrap(),
+      "日本"
+    );
+    assert!(crate::encoding::decode(b"caf\xe9", Encoding(UTF_8)).is_err());
+    assert!(crate::encoding::decode(b"\xff\xfea", Encoding(UTF_16LE)).is_err());
+  }
+
+  #[test]
+  fn encode() {
+    assert_eq!(
+      crate::encoding::encode("café", Encoding(WINDOWS_1252)),
+      Ok(b"caf\xe9".to_vec())
+    );
+    assert_eq!(
+      crate::encoding::encode("日本", Encoding(SHIFT_JIS)),
+      Ok(b"\x93\xfa\x96\x7b".to_vec())
+    );
+    assert_eq!(
+      crate::encoding::encode("a", Encoding(UTF_16LE)),
+      Ok(b"\xff\xfea\x00".to_vec())
+    );
+    assert_eq!(
+      crate::encoding::encode("café ť", Encoding(WINDOWS_1252)),
+      Err(String::from("windows-1252 has no bytes for `ť`"))
+    );
+
+    let text = "žluťoučký\n";
+    for encoding in [UTF_8, UTF_16LE, UTF_16BE] {
+      let bytes = crate::encoding::encode(text, Encoding(encoding)).unwrap();
+      assert_eq!(
+        crate::encoding::decode(&bytes, Encoding(encoding)).unwrap(),
+        text
+      );
+    }
+  }
+}
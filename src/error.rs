@@ -0,0 +1,66 @@
+use std::fmt;
+
+// everything that stops a file from being read or written, or the terminal from being drawn
+#[derive(Debug)]
+pub enum Error {
+  Terminal(std::io::Error),
+  Read(String, String),
+  Binary(String),
+  Write(String, std::io::Error),
+  // a character the file's encoding has no bytes for
+  Encode(String, String),
+  Backup(String, std::io::Error),
+  AuditLog(String, std::io::Error),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Terminal(err) => write!(f, "Terminal error: {}", err),
+      Error::Read(path, reason) => write!(f, "Could not read {}: {}", path, reason),
+      Error::Binary(path) => write!(
+        f,
+        "{} looks like a binary file, pick a side with `git checkout --ours` or `--theirs`",
+        path
+      ),
+      Error::Write(path, err) => write!(f, "Could not write {}: {}", path, err),
+      Error::Encode(path, reason) => write!(f, "Could not write {}: {}", path, reason),
+      Error::Backup(path, err) => write!(f, "Could not back up to {}: {}", path, err),
+      Error::AuditLog(path, err) => {
+        write!(f, "Could not append to the audit log {}: {}", path, err)
+      }
+    }
+  }
+}
+
+impl From<std::io::Error> for Error {
+  fn from(err: std::io::Error) -> Error {
+    Error::Terminal(err)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::error::Error;
+
+  #[test]
+  fn display() {
+    let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+
+    assert_eq!(
+      Error::Read(String::from("a.txt"), String::from("not valid UTF-16")).to_string(),
+      "Could not read a.txt: not valid UTF-16"
+    );
+    assert!(Error::Write(String::from("a.txt"), denied)
+      .to_string()
+      .starts_with("Could not write a.txt: "));
+    assert_eq!(
+      Error::Encode(
+        String::from("a.txt"),
+        String::from("windows-1252 has no bytes for `ť`")
+      )
+      .to_string(),
+      "Could not write a.txt: windows-1252 has no bytes for `ť`"
+    );
+  }
+}
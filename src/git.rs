@@ -0,0 +1,517 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// repository operations, implementations differ in how they talk to git
+pub trait GitBackend {
+  fn is_repository(&self, file_name: &str) -> bool;
+  fn toplevel(&self, file_name: &str) -> Option<String>;
+  fn config(&self, file_name: &str, key: &str) -> Option<String>;
+  fn head(&self, file_name: &str) -> Option<String>;
+  fn stage(&self, file_name: &str) -> Result<(), String>;
+  fn attribute(&self, file_name: &str, name: &str) -> Option<String>;
+  fn stages(&self, file_name: &str) -> Result<Stages, String>;
+  fn list_files(&self, dir: &str) -> Option<Vec<String>>;
+}
+
+// blobs of a conflicted file in index stages 1, 2 and 3, both sides adding the file leaves no base
+pub struct Stages {
+  pub base: Option<Vec<u8>>,
+  pub ours: Vec<u8>,
+  pub theirs: Vec<u8>,
+}
+
+pub fn backend(name: &str) -> Result<Box<dyn GitBackend>, String> {
+  match name {
+    "subprocess" => Ok(Box::new(Subprocess)),
+    #[cfg(feature = "git2")]
+    "git2" => Ok(Box::new(Git2)),
+    #[cfg(not(feature = "git2"))]
+    "git2" => Err(String::from(
+      "git backend `git2` needs mersge built with the `git2` feature",
+    )),
+    _ => Err(format!("unknown git backend `{}`", name)),
+  }
+}
+
+// runs the `git` binary from PATH, needs no native libraries
+pub struct Subprocess;
+
+impl Subprocess {
+  // directory git commands for `file_name` run in
+  fn dir(file_name: &str) -> &Path {
+    match Path::new(file_name).parent() {
+      Some(parent) if !parent.as_os_str().is_empty() => parent,
+      _ => Path::new("."),
+    }
+  }
+
+  fn run(file_name: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+      .arg("-C")
+      .arg(Subprocess::dir(file_name))
+      .args(args)
+      .stdin(Stdio::null())
+      .stderr(Stdio::null())
+      .output()
+      .ok()?;
+
+    if !output.status.success() {
+      return None;
+    }
+
+    String::from_utf8(output.stdout)
+      .ok()
+      .map(|stdout| String::from(stdout.trim_end()))
+  }
+}
+
+impl GitBackend for Subprocess {
+  fn is_repository(&self, file_name: &str) -> bool {
+    Subprocess::run(file_name, &["rev-parse", "--is-inside-work-tree"]).as_deref() == Some("true")
+  }
+
+  fn toplevel(&self, file_name: &str) -> Option<String> {
+    Subprocess::run(file_name, &["rev-parse", "--show-toplevel"])
+  }
+
+  fn config(&self, file_name: &str, key: &str) -> Option<String> {
+    Subprocess::run(file_name, &["config", "--get", key])
+  }
+
+  fn head(&self, file_name: &str) -> Option<String> {
+    Subprocess::run(file_name, &["rev-parse", "HEAD"])
+  }
+
+  fn stage(&self, file_name: &str) -> Result<(), String> {
+    // git runs in the directory of the file, so only the name is left of the path
+    let name = Path::new(file_name)
+      .file_name()
+      .and_then(|name| name.to_str())
+      .ok_or_else(|| format!("invalid path {}", file_name))?;
+
+    Subprocess::run(file_name, &["add", "--", name])
+      .map(|_| ())
+      .ok_or_else(|| String::from("git add failed"))
+  }
+
+  fn attribute(&self, file_name: &str, name: &str) -> Option<String> {
+    let path = Path::new(file_name).file_name()?.to_str()?;
+    let output = Subprocess::run(file_name, &["check-attr", name, "--", path])?;
+
+    attribute_value(&output)
+  }
+
+  fn stages(&self, file_name: &str) -> Result<Stages, String> {
+    let name = Path::new(file_name)
+      .file_name()
+      .and_then(|name| name.to_str())
+      .ok_or_else(|| format!("invalid path {}", file_name))?;
+    let show = |stage: usize| {
+      Command::new("git")
+        .arg("-C")
+        .arg(Subprocess::dir(file_name))
+        .args(["show", &format!(":{}:./{}", stage, name)])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| err.to_string())
+        .map(|output| output.status.success().then_some(output.stdout))
+    };
+    let missing = |side| format!("{} has no {} stage in the index", file_name, side);
+
+    Ok(Stages {
+      base: show(1)?,
+      ours: show(2)?.ok_or_else(|| missing("ours"))?,
+      theirs: show(3)?.ok_or_else(|| missing("theirs"))?,
+    })
+  }
+
+  // tracked and untracked files under `dir` that .gitignore does not exclude, `None` outside a
+  // repository
+  fn list_files(&self, dir: &str) -> Option<Vec<String>> {
+    let output = Command::new("git")
+      .arg("-C")
+      .arg(dir)
+      .args([
+        "ls-files",
+        "-z",
+        "--cached",
+        "--others",
+        "--exclude-standard",
+      ])
+      .stdin(Stdio::null())
+      .stderr(Stdio::null())
+      .output()
+      .ok()
+      .filter(|output| output.status.success())?;
+
+    let mut files: Vec<String> = String::from_utf8(output.stdout)
+      .ok()?
+      .split('\0')
+      .filter(|path| !path.is_empty())
+      .map(|path| Path::new(dir).join(path).to_string_lossy().into_owned())
+      .collect();
+    // a file with conflicts is listed once per index stage
+    files.sort();
+    files.dedup();
+
+    Some(files)
+  }
+}
+
+// talks to the repository through libgit2, no git binary needed
+#[cfg(feature = "git2")]
+pub struct Git2;
+
+#[cfg(feature = "git2")]
+impl Git2 {
+  fn open(file_name: &str) -> Option<git2::Repository> {
+    git2::Repository::discover(Subprocess::dir(file_name)).ok()
+  }
+
+  // the repository of the file and the path of the file in it, like the index has it
+  fn open_path(file_name: &str) -> Option<(git2::Repository, String)> {
+    let repository = Git2::open(file_name)?;
+    let root = std::fs::canonicalize(repository.workdir()?).ok()?;
+    let dir = std::fs::canonicalize(Subprocess::dir(file_name)).ok()?;
+    let name = Path::new(file_name).file_name()?;
+    let path = dir.strip_prefix(root).ok()?.join(name);
+
+    Some((repository, path.to_str()?.replace('\\', "/")))
+  }
+}
+
+#[cfg(feature = "git2")]
+impl GitBackend for Git2 {
+  fn is_repository(&self, file_name: &str) -> bool {
+    Git2::open(file_name).is_some_and(|repository| !repository.is_bare())
+  }
+
+  fn toplevel(&self, file_name: &str) -> Option<String> {
+    let repository = Git2::open(file_name)?;
+    let workdir = repository.workdir()?.to_str()?;
+
+    Some(String::from(workdir.trim_end_matches('/')))
+  }
+
+  fn config(&self, file_name: &str, key: &str) -> Option<String> {
+    Git2::open(file_name)?.config().ok()?.get_string(key).ok()
+  }
+
+  fn head(&self, file_name: &str) -> Option<String> {
+    let repository = Git2::open(file_name)?;
+    let commit = repository.head().ok()?.peel_to_commit().ok()?;
+
+    Some(commit.id().to_string())
+  }
+
+  // adding the path also drops its conflict stages, like `git add`
+  fn stage(&self, file_name: &str) -> Result<(), String> {
+    let (repository, path) =
+      Git2::open_path(file_name).ok_or_else(|| format!("{} is not in a repository", file_name))?;
+    let mut index = repository
+      .index()
+      .map_err(|err| err.message().to_string())?;
+
+    index
+      .add_path(Path::new(&path))
+      .and_then(|_| index.write())
+      .map_err(|err| err.message().to_string())
+  }
+
+  fn attribute(&self, file_name: &str, name: &str) -> Option<String> {
+    let (repository, path) = Git2::open_path(file_name)?;
+    let value = repository
+      .get_attr(Path::new(&path), name, git2::AttrCheckFlags::default())
+      .ok()?;
+
+    match git2::AttrValue::from_string(value) {
+      git2::AttrValue::String(value) => Some(String::from(value)),
+      _ => None,
+    }
+  }
+
+  fn stages(&self, file_name: &str) -> Result<Stages, String> {
+    let (repository, path) =
+      Git2::open_path(file_name).ok_or_else(|| format!("{} is not in a repository", file_name))?;
+    let index = repository
+      .index()
+      .map_err(|err| err.message().to_string())?;
+    let blob = |stage| {
+      index
+        .get_path(Path::new(&path), stage)
+        .map(|entry| repository.find_blob(entry.id))
+        .transpose()
+        .map(|blob| blob.map(|blob| blob.content().to_vec()))
+        .map_err(|err| err.message().to_string())
+    };
+    let missing = |side| format!("{} has no {} stage in the index", file_name, side);
+
+    Ok(Stages {
+      base: blob(1)?,
+      ours: blob(2)?.ok_or_else(|| missing("ours"))?,
+      theirs: blob(3)?.ok_or_else(|| missing("theirs"))?,
+    })
+  }
+
+  fn list_files(&self, dir: &str) -> Option<Vec<String>> {
+    let repository = git2::Repository::discover(dir).ok()?;
+    let root = std::fs::canonicalize(repository.workdir()?).ok()?;
+    let prefix = std::fs::canonicalize(dir)
+      .ok()?
+      .strip_prefix(root)
+      .ok()?
+      .to_path_buf();
+    let mut paths = vec![];
+
+    for entry in repository.index().ok()?.iter() {
+      paths.push(String::from_utf8(entry.path).ok()?);
+    }
+
+    let mut options = git2::StatusOptions::new();
+    options
+      .include_untracked(true)
+      .recurse_untracked_dirs(true)
+      .include_unmodified(false);
+
+    for entry in repository.statuses(Some(&mut options)).ok()?.iter() {
+      if entry.status().is_wt_new() {
+        paths.push(String::from(entry.path()?));
+      }
+    }
+
+    let mut files: Vec<String> = paths
+      .iter()
+      .filter_map(|path| Path::new(path).strip_prefix(&prefix).ok())
+      .map(|path| Path::new(dir).join(path).to_string_lossy().into_owned())
+      .collect();
+    // a file with conflicts is listed once per index stage
+    files.sort();
+    files.dedup();
+
+    Some(files)
+  }
+}
+
+// value from `git check-attr` output, `<path>: <attribute>: <value>`
+fn attribute_value(output: &str) -> Option<String> {
+  match output.rsplit(": ").next()? {
+    "unspecified" | "unset" | "set" => None,
+    value => Some(String::from(value)),
+  }
+}
+
+// marker length set with the conflict-marker-size attribute in .gitattributes
+pub fn marker_size(git: &dyn GitBackend, file_name: &str) -> Option<usize> {
+  git
+    .attribute(file_name, "conflict-marker-size")?
+    .parse()
+    .ok()
+    .filter(|size| *size > 0)
+}
+
+// `merge.conflictStyle` as seen from the repository of the file, repository config wins over global
+pub fn conflict_style(git: &dyn GitBackend, file_name: &str) -> String {
+  git
+    .config(file_name, "merge.conflictStyle")
+    .map(|style| style.to_lowercase())
+    .unwrap_or_else(|| String::from("merge"))
+}
+
+// path of the file relative to the repository root, with forward slashes
+pub fn repository_path(git: &dyn GitBackend, file_name: &str) -> Option<String> {
+  let root = std::fs::canonicalize(git.toplevel(file_name)?).ok()?;
+  let path = std::fs::canonicalize(file_name).ok()?;
+  let relative = path.strip_prefix(root).ok()?;
+
+  Some(
+    relative
+      .components()
+      .map(|component| component.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/"),
+  )
+}
+
+// paths of files with conflict markers in `git status --porcelain` output, relative to the
+// repository root; delete/modify conflicts have no markers and are left out
+pub fn conflicted_paths(porcelain: &str) -> Vec<String> {
+  porcelain
+    .lines()
+    .filter(|line| line.len() > 3 && (line.starts_with("UU ") || line.starts_with("AA ")))
+    .map(|line| unquote(&line[3..]))
+    .collect()
+}
+
+// git quotes paths with unusual characters C-style
+fn unquote(path: &str) -> String {
+  if !(path.len() >= 2 && path.starts_with('"') && path.ends_with('"')) {
+    return String::from(path);
+  }
+
+  let mut bytes = vec![];
+  let mut chars = path[1..path.len() - 1].chars();
+
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      let mut buffer = [0; 4];
+      bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+      continue;
+    }
+
+    match chars.next() {
+      Some('n') => bytes.push(b'\n'),
+      Some('t') => bytes.push(b'\t'),
+      Some(digit @ '0'..='7') => {
+        // octal escaped byte of a multi-byte character
+        let octal: String = std::iter::once(digit)
+          .chain(chars.by_ref().take(2))
+          .collect();
+        bytes.push(u8::from_str_radix(&octal, 8).unwrap_or(b'?'));
+      }
+      Some(c) => bytes.push(c as u8),
+      None => (),
+    }
+  }
+
+  String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn backend() {
+    assert!(crate::git::backend("subprocess").is_ok());
+    assert!(crate::git::backend("svn").is_err());
+    assert_eq!(crate::git::backend("git2").is_ok(), cfg!(feature = "git2"));
+  }
+
+  #[cfg(feature = "git2")]
+  #[test]
+  fn git2() {
+    use crate::git::GitBackend;
+
+    let dir = std::env::temp_dir().join(format!("mersge-git2-{}", std::process::id()));
+    let repository = git2::Repository::init(&dir).unwrap();
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/a.txt"), "a\n").unwrap();
+    std::fs::write(
+      dir.join(".gitattributes"),
+      "*.txt conflict-marker-size=10\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+    std::fs::write(dir.join("sub/b.log"), "b\n").unwrap();
+
+    let git = crate::git::Git2;
+    let file = dir.join("sub/a.txt").to_string_lossy().into_owned();
+    let root = std::fs::canonicalize(&dir).unwrap();
+
+    assert!(git.is_repository(&file));
+    assert_eq!(
+      git
+        .toplevel(&file)
+        .map(|path| std::fs::canonicalize(path).unwrap()),
+      Some(root)
+    );
+    assert_eq!(
+      git.attribute(&file, "conflict-marker-size"),
+      Some(String::from("10"))
+    );
+    assert_eq!(git.head(&file), None);
+
+    let sub = dir.join("sub").to_string_lossy().into_owned();
+    assert_eq!(git.list_files(&sub), Some(vec![file.clone()]));
+
+    git.stage(&file).unwrap();
+    let index = repository.index().unwrap();
+    assert!(index
+      .get_path(std::path::Path::new("sub/a.txt"), 0)
+      .is_some());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[cfg(feature = "git2")]
+  #[test]
+  fn git2_stages() {
+    use crate::git::GitBackend;
+
+    let dir = std::env::temp_dir().join(format!("mersge-git2-stages-{}", std::process::id()));
+    let repository = git2::Repository::init(&dir).unwrap();
+    let mut index = repository.index().unwrap();
+
+    for (stage, content) in [(2, "a\ny\n"), (3, "a\nz\n")] {
+      index
+        .add(&git2::IndexEntry {
+          ctime: git2::IndexTime::new(0, 0),
+          mtime: git2::IndexTime::new(0, 0),
+          dev: 0,
+          ino: 0,
+          mode: 0o100644,
+          uid: 0,
+          gid: 0,
+          file_size: content.len() as u32,
+          id: repository.blob(content.as_bytes()).unwrap(),
+          flags: stage << 12,
+          flags_extended: 0,
+          path: b"a.txt".to_vec(),
+        })
+        .unwrap();
+    }
+    index.write().unwrap();
+    std::fs::write(dir.join("a.txt"), "markers\n").unwrap();
+
+    let file = dir.join("a.txt").to_string_lossy().into_owned();
+    let stages = crate::git::Git2.stages(&file).unwrap();
+    assert_eq!(stages.base, None);
+    assert_eq!(stages.ours, b"a\ny\n");
+    assert_eq!(stages.theirs, b"a\nz\n");
+
+    // both sides adding the file writes nothing to the object store
+    let objects = || {
+      let mut count = 0;
+      let odb = repository.odb().unwrap();
+      odb
+        .foreach(|_| {
+          count += 1;
+          true
+        })
+        .unwrap();
+      count
+    };
+    assert_eq!(objects(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn attribute_value() {
+    assert_eq!(
+      crate::git::attribute_value("a.txt: conflict-marker-size: 10"),
+      Some(String::from("10"))
+    );
+    assert_eq!(
+      crate::git::attribute_value("a.txt: conflict-marker-size: unspecified"),
+      None
+    );
+  }
+
+  #[test]
+  fn conflicted_paths() {
+    let porcelain =
+      "UU src/main.rs\n M README.md\nAA \"with space.txt\"\nDU gone.txt\n?? new.txt\n";
+
+    assert_eq!(
+      crate::git::conflicted_paths(porcelain),
+      vec!["src/main.rs", "with space.txt"]
+    );
+  }
+
+  #[test]
+  fn unquote() {
+    assert_eq!(crate::git::unquote("plain"), "plain");
+    assert_eq!(crate::git::unquote("\"a\\\"b\\\\c\""), "a\"b\\c");
+    assert_eq!(crate::git::unquote("\"\\303\\251.txt\""), "é.txt");
+  }
+}
@@ -0,0 +1,92 @@
+use crate::encoding;
+use std::io::Read;
+
+// bytes of a file being opened, a file on disk is mapped into memory so a big one is never
+// copied onto the heap as a whole
+pub enum Bytes {
+  Mapped(memmap2::Mmap),
+  Read(Vec<u8>),
+}
+
+impl std::ops::Deref for Bytes {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    match self {
+      Bytes::Mapped(map) => map,
+      Bytes::Read(bytes) => bytes,
+    }
+  }
+}
+
+// `-` reads stdin, which cannot be mapped
+pub fn read(file_name: &str) -> std::io::Result<Bytes> {
+  if file_name == "-" {
+    let mut bytes = vec![];
+    std::io::stdin().read_to_end(&mut bytes)?;
+    return Ok(Bytes::Read(bytes));
+  }
+
+  let file = std::fs::File::open(file_name)?;
+
+  // mapping nothing fails on some systems
+  if file.metadata()?.len() == 0 {
+    return Ok(Bytes::Read(vec![]));
+  }
+
+  // the mapping only lives while the file is parsed, a change to the file in that moment is
+  // the same risk as reading it half written
+  unsafe { memmap2::Mmap::map(&file) }.map(Bytes::Mapped)
+}
+
+// text of a file, UTF-8 stays in the bytes it was read to and other encodings are decoded
+pub enum Input {
+  Utf8(Bytes),
+  Decoded(String),
+}
+
+impl std::ops::Deref for Input {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    match self {
+      // checked by `decode`
+      Input::Utf8(bytes) => unsafe { std::str::from_utf8_unchecked(bytes) },
+      Input::Decoded(text) => text,
+    }
+  }
+}
+
+pub fn decode(bytes: Bytes, encoding: encoding::Encoding) -> Result<Input, String> {
+  if !encoding::is_utf8(encoding) {
+    return encoding::decode(&bytes, encoding).map(Input::Decoded);
+  }
+
+  encoding::check_utf8(&bytes)?;
+  Ok(Input::Utf8(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn read() {
+    let path = std::env::temp_dir().join(format!("mersge-input-{}", std::process::id()));
+    let name = path.to_str().unwrap();
+
+    std::fs::write(&path, "čau\n").unwrap();
+    let bytes = crate::input::read(name).unwrap();
+    assert!(matches!(bytes, crate::input::Bytes::Mapped(_)));
+
+    let input = crate::input::decode(bytes, crate::encoding::Encoding::default()).unwrap();
+    assert_eq!(&*input, "čau\n");
+
+    std::fs::write(&path, b"caf\xe9").unwrap();
+    let bytes = crate::input::read(name).unwrap();
+    assert!(crate::input::decode(bytes, crate::encoding::Encoding::default()).is_err());
+
+    std::fs::write(&path, "").unwrap();
+    assert!(crate::input::read(name).unwrap().is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,541 @@
+// a JSON document, objects keep their keys in file order and strings and numbers keep the text
+// they were written with, so an unchanged document prints back the way it was formatted
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Null,
+  Bool(bool),
+  Number(String),
+  String(String),
+  Array(Vec<Value>, Source),
+  Object(Vec<(String, Value)>, Source),
+}
+
+// text a parsed array or object was written as, printed back instead of laying it out again;
+// formatting is not part of the value, two of them are always equal
+#[derive(Clone, Debug, Default)]
+pub struct Source(Option<String>);
+
+impl PartialEq for Source {
+  fn eq(&self, _: &Source) -> bool {
+    true
+  }
+}
+
+// result of merging one value, conflicts hold what each side has (None when it deleted it)
+#[derive(Debug, PartialEq)]
+pub enum Merged {
+  Value(Value),
+  Object(Vec<(String, Merged)>),
+  Conflict(Option<Value>, Option<Value>),
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+  let mut parser = Parser {
+    chars: text.chars().collect(),
+    position: 0,
+  };
+
+  let value = parser.value()?;
+  parser.whitespace();
+
+  match parser.peek() {
+    None => Ok(value),
+    Some(_) => Err(parser.error("unexpected text after the document")),
+  }
+}
+
+struct Parser {
+  chars: Vec<char>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.position).copied()
+  }
+
+  fn error(&self, message: &str) -> String {
+    let line = self.chars[..self.position.min(self.chars.len())]
+      .iter()
+      .filter(|c| **c == '\n')
+      .count();
+
+    format!("line {}: {}", line + 1, message)
+  }
+
+  fn whitespace(&mut self) {
+    while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+      self.position += 1;
+    }
+  }
+
+  fn expect(&mut self, c: char) -> Result<(), String> {
+    self.whitespace();
+
+    match self.peek() == Some(c) {
+      true => {
+        self.position += 1;
+        Ok(())
+      }
+      false => Err(self.error(&format!("expected `{}`", c))),
+    }
+  }
+
+  fn keyword(&mut self, word: &str, value: Value) -> Result<Value, String> {
+    let end = self.position + word.chars().count();
+
+    match self.chars.get(self.position..end) {
+      Some(chars) if chars.iter().copied().eq(word.chars()) => {
+        self.position = end;
+        Ok(value)
+      }
+      _ => Err(self.error("unexpected character")),
+    }
+  }
+
+  fn value(&mut self) -> Result<Value, String> {
+    self.whitespace();
+
+    match self.peek() {
+      Some('{') => self.object(),
+      Some('[') => self.array(),
+      Some('"') => Ok(Value::String(self.string()?)),
+      Some('t') => self.keyword("true", Value::Bool(true)),
+      Some('f') => self.keyword("false", Value::Bool(false)),
+      Some('n') => self.keyword("null", Value::Null),
+      Some('-' | '0'..='9') => Ok(self.number()),
+      Some(_) => Err(self.error("unexpected character")),
+      None => Err(self.error("unexpected end of the document")),
+    }
+  }
+
+  // text from `start` up to where the parser is
+  fn source(&self, start: usize) -> Source {
+    Source(Some(self.chars[start..self.position].iter().collect()))
+  }
+
+  fn object(&mut self) -> Result<Value, String> {
+    let mut members = vec![];
+    let start = self.position;
+    self.position += 1;
+    self.whitespace();
+
+    if self.peek() == Some('}') {
+      self.position += 1;
+      return Ok(Value::Object(members, self.source(start)));
+    }
+
+    loop {
+      self.whitespace();
+      if self.peek() != Some('"') {
+        return Err(self.error("expected a key"));
+      }
+
+      let key = self.string()?;
+      self.expect(':')?;
+      members.push((key, self.value()?));
+      self.whitespace();
+
+      match self.peek() {
+        Some(',') => self.position += 1,
+        Some('}') => {
+          self.position += 1;
+          return Ok(Value::Object(members, self.source(start)));
+        }
+        _ => return Err(self.error("expected `,` or `}`")),
+      }
+    }
+  }
+
+  fn array(&mut self) -> Result<Value, String> {
+    let mut items = vec![];
+    let start = self.position;
+    self.position += 1;
+    self.whitespace();
+
+    if self.peek() == Some(']') {
+      self.position += 1;
+      return Ok(Value::Array(items, self.source(start)));
+    }
+
+    loop {
+      items.push(self.value()?);
+      self.whitespace();
+
+      match self.peek() {
+        Some(',') => self.position += 1,
+        Some(']') => {
+          self.position += 1;
+          return Ok(Value::Array(items, self.source(start)));
+        }
+        _ => return Err(self.error("expected `,` or `]`")),
+      }
+    }
+  }
+
+  // the literal between the quotes, escapes are kept as written
+  fn string(&mut self) -> Result<String, String> {
+    let start = self.position + 1;
+    self.position += 1;
+
+    loop {
+      match self.peek() {
+        Some('"') => {
+          self.position += 1;
+          return Ok(self.chars[start..self.position - 1].iter().collect());
+        }
+        Some('\\') => self.position += 2,
+        Some('\n') | None => return Err(self.error("unterminated string")),
+        Some(_) => self.position += 1,
+      }
+    }
+  }
+
+  fn number(&mut self) -> Value {
+    let start = self.position;
+
+    while matches!(self.peek(), Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+      self.position += 1;
+    }
+
+    Value::Number(self.chars[start..self.position].iter().collect())
+  }
+}
+
+// three-way merge, `None` is a value missing from that side; without a base a value only one
+// side has is taken as added by it
+pub fn merge(base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>) -> Option<Merged> {
+  if ours == theirs || theirs == base {
+    return ours.cloned().map(Merged::Value);
+  }
+
+  if ours == base {
+    return theirs.cloned().map(Merged::Value);
+  }
+
+  match (base, ours, theirs) {
+    (_, Some(Value::Object(ours, _)), Some(Value::Object(theirs, _))) => {
+      let base = match base {
+        Some(Value::Object(base, _)) => base.as_slice(),
+        _ => &[],
+      };
+      let keys = ours
+        .iter()
+        .chain(theirs)
+        .map(|(key, _)| key)
+        .fold(vec![], |mut keys, key| {
+          if !keys.contains(&key) {
+            keys.push(key);
+          }
+          keys
+        });
+
+      let members = keys
+        .into_iter()
+        .filter_map(|key| {
+          merge(member(base, key), member(ours, key), member(theirs, key))
+            .map(|merged| (key.clone(), merged))
+        })
+        .collect();
+
+      Some(Merged::Object(members))
+    }
+    // items both sides appended to the base array are kept, ours first
+    (Some(Value::Array(base, _)), Some(Value::Array(ours, _)), Some(Value::Array(theirs, _)))
+      if ours.starts_with(base) && theirs.starts_with(base) =>
+    {
+      let mut items = ours.clone();
+      for item in &theirs[base.len()..] {
+        if !ours[base.len()..].contains(item) {
+          items.push(item.clone());
+        }
+      }
+
+      Some(Merged::Value(array(items)))
+    }
+    (_, ours, theirs) => Some(Merged::Conflict(ours.cloned(), theirs.cloned())),
+  }
+}
+
+fn member<'a>(members: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+  members
+    .iter()
+    .find(|(name, _)| name == key)
+    .map(|(_, value)| value)
+}
+
+// the text of `value` is appended to the last line, further lines are added below it; a parsed
+// array or object keeps the lines it was written on
+fn print_value(value: &Value, indent: &str, level: usize, lines: &mut Vec<String>) {
+  let text = |lines: &mut Vec<String>, text: &str| lines.last_mut().unwrap().push_str(text);
+
+  match value {
+    Value::Null => text(lines, "null"),
+    Value::Bool(value) => text(lines, &value.to_string()),
+    Value::Number(number) => text(lines, number),
+    Value::String(string) => text(lines, &format!("\"{}\"", string)),
+    Value::Array(_, Source(Some(source))) | Value::Object(_, Source(Some(source))) => {
+      let mut source = source.lines();
+      text(lines, source.next().unwrap_or_default());
+      lines.extend(source.map(String::from));
+    }
+    Value::Array(items, _) if items.is_empty() => text(lines, "[]"),
+    Value::Object(members, _) if members.is_empty() => text(lines, "{}"),
+    Value::Array(items, _) => {
+      text(lines, "[");
+      for (i, item) in items.iter().enumerate() {
+        lines.push(indent.repeat(level + 1));
+        print_value(item, indent, level + 1, lines);
+        if i + 1 < items.len() {
+          text(lines, ",");
+        }
+      }
+      lines.push(format!("{}]", indent.repeat(level)));
+    }
+    Value::Object(members, _) => {
+      text(lines, "{");
+      for (i, (key, value)) in members.iter().enumerate() {
+        lines.push(format!("{}\"{}\": ", indent.repeat(level + 1), key));
+        print_value(value, indent, level + 1, lines);
+        if i + 1 < members.len() {
+          text(lines, ",");
+        }
+      }
+      lines.push(format!("{}}}", indent.repeat(level)));
+    }
+  }
+}
+
+pub fn print(value: &Value, indent: &str) -> Vec<String> {
+  let mut lines = vec![String::new()];
+  print_value(value, indent, 0, &mut lines);
+  lines
+}
+
+pub fn array(items: Vec<Value>) -> Value {
+  Value::Array(items, Source::default())
+}
+
+pub fn object(members: Vec<(String, Value)>) -> Value {
+  Value::Object(members, Source::default())
+}
+
+// string value for `text`, quotes, backslashes and control characters escaped
+pub fn string(text: &str) -> Value {
+  let mut escaped = String::new();
+
+  for c in text.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      '\r' => escaped.push_str("\\r"),
+      '\t' => escaped.push_str("\\t"),
+      c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+      c => escaped.push(c),
+    }
+  }
+
+  Value::String(escaped)
+}
+
+// merged document with a conflict block for every value the sides disagree on, `markers` are
+// the `<<<<<<<`, `=======` and `>>>>>>>` lines to use; returns the lines and the conflict count
+pub fn print_merged(merged: &Merged, indent: &str, markers: [&str; 3]) -> (Vec<String>, usize) {
+  let mut lines = vec![];
+  let mut conflicts = 0;
+  print_member(
+    None,
+    merged,
+    true,
+    indent,
+    0,
+    markers,
+    &mut lines,
+    &mut conflicts,
+  );
+  (lines, conflicts)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn print_member(
+  key: Option<&str>,
+  merged: &Merged,
+  last: bool,
+  indent: &str,
+  level: usize,
+  markers: [&str; 3],
+  lines: &mut Vec<String>,
+  conflicts: &mut usize,
+) {
+  let prefix = match key {
+    Some(key) => format!("{}\"{}\": ", indent.repeat(level), key),
+    None => indent.repeat(level),
+  };
+  let comma = if last { "" } else { "," };
+
+  match merged {
+    Merged::Value(value) => {
+      lines.push(prefix);
+      print_value(value, indent, level, lines);
+      lines.last_mut().unwrap().push_str(comma);
+    }
+    Merged::Object(members) if members.is_empty() => lines.push(format!("{}{{}}{}", prefix, comma)),
+    Merged::Object(members) => {
+      lines.push(format!("{}{{", prefix));
+      for (i, (key, member)) in members.iter().enumerate() {
+        let last = i + 1 == members.len();
+        print_member(
+          Some(key),
+          member,
+          last,
+          indent,
+          level + 1,
+          markers,
+          lines,
+          conflicts,
+        );
+      }
+      lines.push(format!("{}}}{}", indent.repeat(level), comma));
+    }
+    Merged::Conflict(ours, theirs) => {
+      *conflicts += 1;
+      lines.push(String::from(markers[0]));
+      for (side, marker) in [(ours, markers[1]), (theirs, markers[2])] {
+        if let Some(value) = side {
+          lines.push(prefix.clone());
+          print_value(value, indent, level, lines);
+          lines.last_mut().unwrap().push_str(comma);
+        }
+        lines.push(String::from(marker));
+      }
+    }
+  }
+}
+
+// one level of indentation as used by the first indented line, two spaces when there is none
+pub fn indent(text: &str) -> String {
+  text
+    .lines()
+    .map(|line| &line[..line.len() - line.trim_start().len()])
+    .find(|indent| !indent.is_empty())
+    .map(String::from)
+    .unwrap_or_else(|| String::from("  "))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::json::{Merged, Value};
+
+  #[test]
+  fn parse() {
+    let value = crate::json::parse("{\"a\": [1, true, null], \"b\": \"x\\\"y\"}").unwrap();
+
+    assert_eq!(
+      value,
+      crate::json::object(vec![
+        (
+          String::from("a"),
+          crate::json::array(vec![
+            Value::Number(String::from("1")),
+            Value::Bool(true),
+            Value::Null
+          ])
+        ),
+        (String::from("b"), Value::String(String::from("x\\\"y"))),
+      ])
+    );
+    assert_eq!(
+      crate::json::parse("{\"a\": 1,}"),
+      Err(String::from("line 1: expected a key"))
+    );
+    assert!(crate::json::parse("[1] 2").is_err());
+  }
+
+  #[test]
+  fn print() {
+    let value = crate::json::object(vec![
+      (String::from("a"), crate::json::string("x\"y\\\t")),
+      (String::from("b"), crate::json::array(vec![])),
+    ]);
+
+    assert_eq!(
+      crate::json::print(&value, "  ").join("\n"),
+      "{\n  \"a\": \"x\\\"y\\\\\\t\",\n  \"b\": []\n}"
+    );
+  }
+
+  #[test]
+  fn merge() {
+    let parse = |text| crate::json::parse(text).unwrap();
+    let base = parse("{\"a\": 1, \"b\": 1, \"c\": [1]}");
+    let ours = parse("{\"a\": 2, \"b\": 1, \"c\": [1, 2]}");
+    let theirs = parse("{\"a\": 1, \"b\": 3, \"c\": [1, 3], \"d\": 4}");
+
+    let merged = crate::json::merge(Some(&base), Some(&ours), Some(&theirs)).unwrap();
+    let (lines, conflicts) = crate::json::print_merged(&merged, "  ", ["<", "=", ">"]);
+
+    assert_eq!(conflicts, 0);
+    assert_eq!(
+      lines,
+      vec![
+        "{",
+        "  \"a\": 2,",
+        "  \"b\": 3,",
+        "  \"c\": [",
+        "    1,",
+        "    2,",
+        "    3",
+        "  ],",
+        "  \"d\": 4",
+        "}"
+      ]
+    );
+
+    let theirs = parse("{\"a\": 3, \"b\": 1, \"c\": [1]}");
+    let merged = crate::json::merge(Some(&base), Some(&ours), Some(&theirs)).unwrap();
+    let number = |n: &str| Some(Value::Number(String::from(n)));
+
+    match &merged {
+      Merged::Object(members) => {
+        assert_eq!(members[0].1, Merged::Conflict(number("2"), number("3")))
+      }
+      _ => panic!("expected an object"),
+    }
+
+    let (lines, conflicts) = crate::json::print_merged(&merged, "  ", ["<", "=", ">"]);
+    assert_eq!(conflicts, 1);
+    assert_eq!(lines[1..6], ["<", "  \"a\": 2,", "=", "  \"a\": 3,", ">"]);
+  }
+
+  #[test]
+  fn merge_keeps_formatting() {
+    let parse = |text| crate::json::parse(text).unwrap();
+    let base = parse("{\n  \"a\": 1,\n  \"b\": {\"x\": [1,2]},\n  \"c\": [\n    1\n  ]\n}");
+    let ours = parse("{\n  \"a\": 2,\n  \"b\": {\"x\": [1,2]},\n  \"c\": [\n    1\n  ]\n}");
+    let theirs = parse("{\"a\": 1, \"b\": {\"x\": [1, 2]}, \"c\": [1], \"d\": [3,  4]}");
+
+    let merged = crate::json::merge(Some(&base), Some(&ours), Some(&theirs)).unwrap();
+    let (lines, _) = crate::json::print_merged(&merged, "  ", ["<", "=", ">"]);
+
+    assert_eq!(
+      lines,
+      vec![
+        "{",
+        "  \"a\": 2,",
+        "  \"b\": {\"x\": [1,2]},",
+        "  \"c\": [",
+        "    1",
+        "  ],",
+        "  \"d\": [3,  4]",
+        "}"
+      ]
+    );
+  }
+
+  #[test]
+  fn indent() {
+    assert_eq!(crate::json::indent("{\n\t\"a\": 1\n}"), "\t");
+    assert_eq!(crate::json::indent("{}"), "  ");
+  }
+}
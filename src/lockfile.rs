@@ -0,0 +1,362 @@
+use crate::json;
+use crate::outline;
+
+// lockfiles are written by a package manager, nobody should resolve them line by line; a
+// resolver merges both sides when it can and otherwise names the command that rebuilds the file
+#[derive(Clone, Copy)]
+pub struct Resolver {
+  pub merge: Option<Merge>,
+  pub regenerate: &'static str,
+}
+
+// local and incoming side to the merged lines
+pub type Merge = fn(&str, &str) -> Result<Vec<String>, String>;
+
+// file name and how to resolve it, a lockfile without a merge is only rebuilt
+const RESOLVERS: [(&str, Resolver); 5] = [
+  (
+    "Cargo.lock",
+    Resolver {
+      merge: Some(cargo_lock),
+      regenerate: "cargo update --workspace",
+    },
+  ),
+  (
+    "package-lock.json",
+    Resolver {
+      merge: Some(package_lock),
+      regenerate: "npm install",
+    },
+  ),
+  (
+    "npm-shrinkwrap.json",
+    Resolver {
+      merge: Some(package_lock),
+      regenerate: "npm install",
+    },
+  ),
+  (
+    "yarn.lock",
+    Resolver {
+      merge: Some(yarn_lock),
+      regenerate: "yarn install",
+    },
+  ),
+  (
+    "pnpm-lock.yaml",
+    Resolver {
+      merge: None,
+      regenerate: "pnpm install",
+    },
+  ),
+];
+
+pub fn resolver(file_name: &str) -> Option<Resolver> {
+  let name = std::path::Path::new(file_name).file_name()?.to_str()?;
+
+  RESOLVERS
+    .iter()
+    .find(|(file_name, _)| *file_name == name)
+    .map(|(_, resolver)| *resolver)
+}
+
+// every `[[package]]` of both sides, a package both have with different dependencies gets all
+// of them; cargo drops the ones nothing uses any more on the next build
+fn cargo_lock(local: &str, incoming: &str) -> Result<Vec<String>, String> {
+  let local = outline::parse_toml(local)?;
+  let incoming = outline::parse_toml(incoming)?;
+  let mut packages: Vec<outline::Entry> = vec![];
+
+  for package in local
+    .iter()
+    .chain(&incoming)
+    .filter(|entry| is_package(entry))
+  {
+    let id = package_id(package);
+
+    match packages.iter_mut().find(|known| package_id(known) == id) {
+      Some(known) if known.children == package.children => (),
+      Some(known) => *known = merge_packages(known, package)?,
+      None => packages.push(package.clone()),
+    }
+  }
+
+  packages.sort_by_key(package_id);
+
+  let mut lines = vec![];
+  for entry in local.iter().filter(|entry| !is_package(entry)) {
+    print(entry, &mut lines);
+  }
+  for package in &packages {
+    print(package, &mut lines);
+  }
+
+  Ok(lines)
+}
+
+fn is_package(entry: &outline::Entry) -> bool {
+  entry.key.starts_with("[[package]]")
+}
+
+fn value<'a>(package: &'a outline::Entry, key: &str) -> &'a str {
+  package
+    .children
+    .iter()
+    .find(|child| child.key == key)
+    .and_then(|child| child.lines.last())
+    .and_then(|line| line.split_once('='))
+    .map_or("", |(_, value)| value.trim().trim_matches('"'))
+}
+
+// name, version and source, the same crate can be locked at two versions
+fn package_id(package: &outline::Entry) -> (String, String, String) {
+  (
+    String::from(value(package, "name")),
+    String::from(value(package, "version")),
+    String::from(value(package, "source")),
+  )
+}
+
+fn merge_packages(
+  local: &outline::Entry,
+  incoming: &outline::Entry,
+) -> Result<outline::Entry, String> {
+  let others = |package: &outline::Entry| {
+    package
+      .children
+      .iter()
+      .filter(|child| child.key != "dependencies")
+      .cloned()
+      .collect::<Vec<_>>()
+  };
+
+  if others(local) != others(incoming) {
+    return Err(format!(
+      "package {} {} differs in more than its dependencies",
+      value(local, "name"),
+      value(local, "version")
+    ));
+  }
+
+  let mut names = dependencies(local);
+  for name in dependencies(incoming) {
+    if !names.contains(&name) {
+      names.push(name);
+    }
+  }
+  names.sort();
+
+  let mut lines = vec![String::from("dependencies = [")];
+  lines.extend(names.iter().map(|name| format!(" \"{}\",", name)));
+  lines.push(String::from("]"));
+
+  let mut merged = local.clone();
+  merged.children = others(local);
+  merged.children.push(outline::Entry {
+    key: String::from("dependencies"),
+    lines,
+    children: vec![],
+  });
+
+  Ok(merged)
+}
+
+fn dependencies(package: &outline::Entry) -> Vec<String> {
+  package
+    .children
+    .iter()
+    .filter(|child| child.key == "dependencies")
+    .flat_map(|child| child.lines.iter())
+    .flat_map(|line| line.split('"').skip(1).step_by(2))
+    .map(String::from)
+    .collect()
+}
+
+fn print(entry: &outline::Entry, lines: &mut Vec<String>) {
+  lines.extend(entry.lines.iter().cloned());
+  for child in &entry.children {
+    print(child, lines);
+  }
+}
+
+// every package of both sides by its path in `packages` (and by name in the `dependencies` of
+// older lockfiles), the rest of the file is taken from the local side
+fn package_lock(local: &str, incoming: &str) -> Result<Vec<String>, String> {
+  let mut merged = json::parse(local)?;
+  let incoming = json::parse(incoming)?;
+
+  if let (json::Value::Object(members, source), json::Value::Object(incoming, _)) =
+    (&mut merged, &incoming)
+  {
+    // what changed is laid out again, everything else keeps its lines
+    *source = json::Source::default();
+
+    for (key, value) in members.iter_mut() {
+      let other = incoming.iter().find(|(name, _)| name == key);
+      if let (
+        "packages" | "dependencies",
+        json::Value::Object(packages, source),
+        Some((_, json::Value::Object(other, _))),
+      ) = (key.as_str(), value, other)
+      {
+        *source = json::Source::default();
+        for (path, package) in other {
+          match packages.iter().find(|(known, _)| known == path) {
+            Some((_, known)) if known == package => (),
+            Some(_) => return Err(format!("{} is locked differently on both sides", path)),
+            None => packages.push((path.clone(), package.clone())),
+          }
+        }
+        packages.sort_by(|a, b| a.0.cmp(&b.0));
+      }
+    }
+  }
+
+  let (lines, _) = json::print_merged(
+    &json::Merged::Value(merged),
+    &json::indent(local),
+    ["", "", ""],
+  );
+  Ok(lines)
+}
+
+// entries of both sides, an entry is its unindented `name@range:` line and the lines below it;
+// comments at the top come from the local side
+fn yarn_lock(local: &str, incoming: &str) -> Result<Vec<String>, String> {
+  let (header, mut entries) = yarn_entries(local);
+  let (_, other) = yarn_entries(incoming);
+
+  for entry in other {
+    match entries.iter().find(|known| known[0] == entry[0]) {
+      Some(known) if *known == entry => (),
+      Some(_) => return Err(format!("{} is locked differently on both sides", entry[0])),
+      None => entries.push(entry),
+    }
+  }
+
+  // berry keeps its `__metadata` first, quotes do not count when sorting
+  entries.sort_by_key(|entry| (entry[0] != "__metadata:", entry[0].replace('"', "")));
+
+  let mut lines = header;
+  for (i, entry) in entries.into_iter().enumerate() {
+    if i > 0 {
+      lines.push(String::new());
+    }
+    lines.extend(entry);
+  }
+
+  Ok(lines)
+}
+
+fn yarn_entries(text: &str) -> (Vec<String>, Vec<Vec<String>>) {
+  let mut header = vec![];
+  let mut entries: Vec<Vec<String>> = vec![];
+
+  for line in text.lines() {
+    if !line.is_empty() && !line.starts_with([' ', '#']) {
+      entries.push(vec![String::from(line)]);
+    } else if let Some(entry) = entries.last_mut() {
+      entry.push(String::from(line));
+    } else {
+      header.push(String::from(line));
+    }
+  }
+
+  for entry in &mut entries {
+    while entry.last().is_some_and(|line| line.trim().is_empty()) {
+      entry.pop();
+    }
+  }
+
+  (header, entries)
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn resolver() {
+    assert!(crate::lockfile::resolver("dir/Cargo.lock").is_some());
+    assert!(crate::lockfile::resolver("Cargo.toml").is_none());
+    assert!(crate::lockfile::resolver("pnpm-lock.yaml")
+      .unwrap()
+      .merge
+      .is_none());
+  }
+
+  #[test]
+  fn cargo_lock() {
+    let package = |name: &str, dependencies: &[&str]| {
+      let mut text = format!("\n[[package]]\nname = \"{}\"\nversion = \"1.0.0\"\n", name);
+      if !dependencies.is_empty() {
+        text.push_str("dependencies = [\n");
+        for dependency in dependencies {
+          text.push_str(&format!(" \"{}\",\n", dependency));
+        }
+        text.push_str("]\n");
+      }
+      text
+    };
+    let header = "# This file is automatically @generated by Cargo.\nversion = 3\n";
+    let local = format!("{}{}{}", header, package("app", &["b"]), package("b", &[]));
+    let incoming = format!("{}{}{}", header, package("app", &["c"]), package("c", &[]));
+
+    assert_eq!(
+      crate::lockfile::cargo_lock(&local, &incoming)
+        .unwrap()
+        .join("\n"),
+      format!(
+        "{}{}{}{}",
+        header,
+        package("app", &["b", "c"]),
+        package("b", &[]),
+        package("c", &[])
+      )
+      .trim_end()
+    );
+
+    let incoming = local.replace(
+      "name = \"b\"\nversion = \"1.0.0\"",
+      "name = \"b\"\nversion = \"1.0.0\"\nsource = \"git\"",
+    );
+    assert!(crate::lockfile::cargo_lock(&local, &incoming).is_ok());
+  }
+
+  #[test]
+  fn package_lock() {
+    let lock = |packages: &str| {
+      format!(
+        "{{\n  \"name\": \"app\",\n  \"lockfileVersion\": 3,\n  \"packages\": {{\n{}\n  }}\n}}",
+        packages
+      )
+    };
+    let a = "    \"node_modules/a\": {\n      \"version\": \"1.0.0\"\n    }";
+    let b = "    \"node_modules/b\": {\n      \"version\": \"2.0.0\"\n    }";
+
+    assert_eq!(
+      crate::lockfile::package_lock(&lock(a), &lock(b))
+        .unwrap()
+        .join("\n"),
+      lock(&format!("{},\n{}", a, b))
+    );
+    assert!(crate::lockfile::package_lock(&lock(a), &lock(&a.replace("1.0.0", "1.1.0"))).is_err());
+  }
+
+  #[test]
+  fn yarn_lock() {
+    let header = "# yarn lockfile v1\n\n";
+    let a = "a@^1.0.0:\n  version \"1.0.0\"";
+    let b = "\"b@^2.0.0\":\n  version \"2.0.0\"";
+    let c = "c@^1.0.0:\n  version \"1.0.0\"\n  dependencies:\n    a \"^1.0.0\"";
+
+    assert_eq!(
+      crate::lockfile::yarn_lock(
+        &format!("{}{}\n\n{}\n", header, a, c),
+        &format!("{}{}\n\n{}\n", header, a, b)
+      )
+      .unwrap()
+      .join("\n"),
+      format!("{}{}\n\n{}\n\n{}", header, a, b, c)
+    );
+    assert!(crate::lockfile::yarn_lock(a, &a.replace("1.0.0\"", "1.1.0\"")).is_err());
+  }
+}
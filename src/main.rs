@@ -1,10 +1,32 @@
+mod audit;
+mod cli;
+mod clipboard;
+mod config;
+mod diff3;
+mod encoding;
+mod error;
+mod git;
+mod input;
+mod json;
+mod lockfile;
+mod outline;
+mod patch;
+mod script;
+mod state;
+mod text;
+mod tool;
+
+use clap::{CommandFactory, Parser};
 use crossterm::{event, terminal};
+use std::io::{IsTerminal, Read, Write};
 use tui::{
   layout::{Constraint, Direction, Layout, Rect},
-  style::{Color, Style},
+  style::{Color, Modifier, Style},
   text::{Span, Spans},
-  widgets::{Block, Borders, Paragraph},
+  widgets::{Block, Borders, Clear, Paragraph},
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 #[derive(PartialEq)]
 enum Column {
@@ -13,6 +35,133 @@ enum Column {
   Right,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+  MoveUp,
+  MoveDown,
+  PageUp,
+  PageDown,
+  MoveFirst,
+  MoveLast,
+  AcceptLocal,
+  AcceptIncoming,
+  AcceptLocalConflict,
+  AcceptIncomingConflict,
+  Unresolve,
+  Cycle,
+  MergeTool,
+  DiffTool,
+  MergeImports,
+  Search,
+  GotoLine,
+  ToggleRaw,
+  ToggleUnified,
+  ToggleResult,
+  FocusLeft,
+  FocusRight,
+  GrowPane,
+  ShrinkPane,
+  Zoom,
+  ToggleWrap,
+  ToggleGutter,
+  ToggleIgnoreWhitespace,
+  NextMatch,
+  PreviousMatch,
+  AcceptLocalAll,
+  AcceptIncomingAll,
+  ToggleFold,
+  CollapseContext,
+  // scroll so the cursor line is in the middle, at the top or at the bottom of the panes
+  CenterView,
+  ViewTop,
+  ViewBottom,
+  Summary,
+  Diffstat,
+  ResolveRemaining,
+  Select,
+  Yank,
+  YankPermalink,
+  Paste,
+  Write,
+  WriteAll,
+  PreviousFile,
+  NextFile,
+  // switch to the file at this index, from a click on its tab
+  OpenFile(usize),
+  // move to this line, from a click on the minimap
+  JumpTo(usize),
+  Picker,
+  Quit,
+  QuitAll,
+}
+
+const CONTROLS: [(&str, &str, Action); 6] = [
+  ("[Up] ", "Move up ", Action::MoveUp),
+  ("[Down] ", "Move down ", Action::MoveDown),
+  ("[L] ", "Accept local ", Action::AcceptLocal),
+  ("[R] ", "Accept incoming ", Action::AcceptIncoming),
+  ("[W] ", "Write ", Action::Write),
+  ("[Q] ", "Quit ", Action::Quit),
+];
+
+// longest the editor waits for input before its loop runs again
+const TICK: std::time::Duration = std::time::Duration::from_millis(250);
+
+// files this big are parsed on a worker thread while a loading screen shows the progress
+const BACKGROUND_PARSE: usize = 4 * 1024 * 1024;
+
+// Tab steps a conflict line through these, None puts the placeholder back
+const CYCLE: [(Option<Strategy>, &str); 4] = [
+  (Some(Strategy::Ours), "local"),
+  (Some(Strategy::Theirs), "incoming"),
+  (Some(Strategy::Union), "both"),
+  (None, "unresolved"),
+];
+
+// how conflicts nobody decided yet get resolved
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Strategy {
+  Ours,
+  Theirs,
+  Union,
+}
+
+// what a patch of the resolution is made against, the file with its markers or the version
+// both sides started from
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PatchBase {
+  Input,
+  Base,
+}
+
+// what fills the screen, the raw and unified views show a single text pane
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum View {
+  #[default]
+  Columns,
+  Raw,
+  Unified,
+}
+
+// part of a conflict a line of file text belongs to
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Section {
+  Outside,
+  Marker,
+  Local,
+  Base,
+  Incoming,
+}
+
+// tool that wrote the markers, all of them use `<` `|` `=` `>` but label the sides differently
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum ConflictStyle {
+  #[default]
+  Git,
+  Mercurial,
+  Subversion,
+}
+
 #[derive(Debug, PartialEq)]
 enum Change {
   None,
@@ -21,24 +170,615 @@ enum Change {
 }
 
 struct Line {
-  value: String,
+  value: text::Text,
   change: Change,
 }
 
+// range of lines in the three buffers that came from a single conflict block
+#[derive(Debug, PartialEq)]
+struct Conflict {
+  start: usize,
+  end: usize,
+  // common ancestor lines, only present in diff3 style conflicts
+  base: Option<Vec<String>>,
+  // `za` flips whether the conflict folds once resolved
+  toggled: bool,
+}
+
+#[derive(Default)]
 struct Context {
   file_name: String,
   local_changes: Vec<Line>,
   incoming_changes: Vec<Line>,
   result: Vec<Line>,
+  conflicts: Vec<Conflict>,
+  // length of the conflict markers, git allows changing it per file
+  marker_size: usize,
+  // text after `<<<<<<<` and `>>>>>>>` of the first conflict, usually a branch or commit
+  local_label: Option<String>,
+  incoming_label: Option<String>,
+  style: ConflictStyle,
+  // most lines end with \r\n, `lines()` drops the \r and writing puts it back
+  crlf: bool,
+  // the file started with a UTF-8 byte order mark, hidden while editing
+  bom: bool,
+  trailing_newline: bool,
+  output: Option<String>,
+  // copy the input to `<file>.orig` before the first write
+  backup: bool,
+  backed_up: bool,
+  // saved since the session last looked, it may need staging
+  written: bool,
+  // the file is shown as UTF-8 and written back in this
+  encoding: encoding::Encoding,
+  // input file as it was read, shown by the raw view
+  original: Vec<text::Text>,
+  view: View,
+  // first line shown by the raw and unified views
+  view_offset: usize,
   current_line: usize,
   line_offset: usize,
   column_height: usize,
+  column_rects: [Rect; 3],
+  result_rows: Vec<usize>,
+  side_rows: Vec<usize>,
+  control_rects: Vec<(Rect, Action)>,
+  guide_column: Option<usize>,
+  layout: config::Layout,
+  // widths of the three panes in percent and the pane `<` and `>` resize
+  ratios: [u16; 3],
+  focus: usize,
+  // the focused pane takes the whole width
+  zoom: bool,
+  // long lines continue on the next rows instead of being cut off
+  wrap: bool,
+  // changes are marked with `+` and `-` in a column left of the text instead of coloring it
+  gutter: bool,
+  tab_width: usize,
+  // sides of a conflict that differ only in whitespace are shown dimmed and count as identical
+  ignore_whitespace: bool,
+  // the two-pane layout shows the result instead of local and incoming
+  show_result: bool,
+  // resolved conflicts start folded
+  fold_resolved: bool,
+  // hide unchanged lines except `context_lines` around each conflict, `expanded` holds the
+  // starts of hidden regions opened again
+  collapse_context: bool,
+  context_lines: usize,
+  expanded: Vec<usize>,
+  merge_tool: Option<String>,
+  diff_tool: Option<String>,
+  permalink: Option<String>,
+  audit_log: Option<String>,
+  // where the file sits in its git repository, used by permalinks
+  repository_path: Option<String>,
+  head: Option<String>,
+  modified: bool,
+  selection: Option<usize>,
+  message: Option<String>,
+  popup: Option<Popup>,
+  prompt: Option<Prompt>,
+  search: Option<String>,
+  pending_key: Option<char>,
+  // digits typed before a motion, which then runs that many times
+  count: Option<usize>,
+  // resolution `.` runs again
+  last_resolution: Option<Action>,
+  // line and position in CYCLE of the last Tab press
+  cycle: Option<(usize, usize)>,
+  // progress left unwritten by an earlier session, waiting for the resume popup
+  resume: Option<state::State>,
+  // conflict just resolved whose resolution the popup offers for the ones with the same sides
+  similar_to: Option<usize>,
+  // closing the file with changes not written was confirmed
+  close: bool,
+}
+
+// single line input in the bottom bar, `kind` is the key that opened it
+struct Prompt {
+  kind: char,
+  input: String,
+}
+
+// key, label and what happens when the key is pressed
+type Choice = (char, &'static str, fn(&mut Context));
+
+// modal dialog, a key press picks one of the choices or dismisses it
+struct Popup {
+  title: String,
+  lines: Vec<Spans<'static>>,
+  choices: Vec<Choice>,
+}
+
+// all files opened from the command line, only the current one is shown
+struct Session {
+  files: Vec<Context>,
+  current: usize,
+  // an external program drew over the screen, repaint everything
+  clear: bool,
+  // write a file as soon as its last conflict gets resolved
+  autowrite: bool,
+  // `git add` a file written with no conflicts left
+  autostage: bool,
+  git: Box<dyn git::GitBackend>,
+  // file picked on the review screen shown before exiting
+  review: Option<usize>,
+  // files no longer open, written before the editor opened or closed in it, one line each
+  // for the review screen
+  touched: Vec<String>,
+  // pane widths last set with `<` and `>`, saved to the config once the editor closes
+  pane_ratios: Option<[u16; 3]>,
+  // file picked on the list of files, shown first when there are several
+  picker: Option<usize>,
+  // something on screen changed since the last frame, mouse moves and unbound keys leave it
+  dirty: bool,
 }
 
 fn main() -> Result<(), std::io::Error> {
-  let args: Vec<String> = std::env::args().collect();
-  if args.len() != 2 {
-    println!("Usage: mersge <filename>");
+  // without arguments there is nothing to open, show what can be passed
+  if std::env::args().len() < 2 {
+    println!("{}", cli::Cli::command().render_help());
+    return Ok(());
+  }
+
+  let cli = cli::Cli::parse();
+
+  match cli.command {
+    Some(cli::Command::Check { count, paths }) => std::process::exit(check(count, paths)),
+    Some(cli::Command::List { json, files }) => std::process::exit(list(json, &files)),
+    Some(cli::Command::Completions { shell }) => {
+      print!("{}", cli::completions(shell));
+      return Ok(());
+    }
+    None => (),
+  }
+
+  let config = match config::Config::load() {
+    Ok(config) => config,
+    Err(err) => fail(format!("Invalid config: {}", err)),
+  };
+
+  let git = match git::backend(&config.git_backend) {
+    Ok(git) => git,
+    Err(err) => fail(format!("Invalid config: {}", err)),
+  };
+
+  let strategy = match (cli.ours, cli.theirs, cli.union) {
+    (true, _, _) => Some(Strategy::Ours),
+    (_, true, _) => Some(Strategy::Theirs),
+    (_, _, true) => Some(Strategy::Union),
+    _ => None,
+  };
+  let encoding = match cli.encoding.as_deref().map(encoding::from_name) {
+    Some(Ok(encoding)) => Some(encoding),
+    Some(Err(err)) => fail(err),
+    None => None,
+  };
+  let script = cli.script.map(|path| match read_script(&path) {
+    Ok(commands) => commands,
+    Err(err) => fail(format!("{}: {}", path, err)),
+  });
+  // clap lets nothing else than input and base through
+  let patch = cli.patch.as_deref().map(|against| match against {
+    "base" => PatchBase::Base,
+    _ => PatchBase::Input,
+  });
+  let whitespace = match cli.whitespace.as_deref().map(config::parse_whitespace) {
+    Some(Ok(preference)) => Some(preference),
+    Some(Err(err)) => fail(err),
+    None => config.whitespace,
+  };
+  let structured = config.structured_merge && !cli.lines;
+  let (output, to_stdout, dump_json) = (cli.output, cli.stdout, cli.dump_json);
+  let (open_clean, force, from_index) = (cli.open_clean, cli.force, cli.index);
+  let (backup, stage, reference) = (cli.backup, cli.stage, cli.reference);
+  let mut file_names = cli.files;
+
+  for dir in &cli.scan {
+    match scan(git.as_ref(), dir) {
+      Ok(paths) => file_names.extend(paths),
+      Err(err) => fail(format!("Could not scan {}: {}", dir, err)),
+    }
+  }
+
+  if cli.from_git_status {
+    match read_git_status(git.as_ref()) {
+      Ok(paths) => file_names.extend(paths),
+      Err(err) => fail(format!("Could not read git status from stdin: {}", err)),
+    }
+  }
+
+  if file_names.is_empty() {
+    fail("No conflicted files");
+  }
+
+  if output.is_some() && file_names.len() > 1 {
+    fail("--output works with a single file only");
+  }
+
+  if reference.is_some() && file_names.len() > 1 {
+    fail("--reference works with a single file only");
+  }
+
+  let reference: Option<Vec<String>> = match reference.map(|path| read_file(&path, encoding)) {
+    Some(Ok((file, _))) => Some(file.lines().map(String::from).collect()),
+    Some(Err(err)) => fail(err),
+    None => None,
+  };
+
+  // `-` has no file the result could go back to
+  if file_names.iter().any(|file_name| file_name == "-") && output.is_none() && !to_stdout {
+    fail("reading from stdin needs --output or --stdout");
+  }
+
+  // the editor draws on stdout, so only a batch run can print the result there
+  if to_stdout && (strategy.is_none() || file_names.len() > 1 || output.is_some()) {
+    fail("--stdout needs --ours, --theirs or --union and a single file");
+  }
+
+  if script.is_some() && strategy.is_some() {
+    fail("--script decides the conflicts itself, leave out --ours, --theirs and --union");
+  }
+
+  if dump_json && (to_stdout || patch.is_some()) {
+    fail("--dump-json prints the files itself, leave out --stdout and --patch");
+  }
+
+  if patch.is_some() && (to_stdout || output.is_some()) {
+    fail("--patch prints the result instead of writing it, leave out --stdout and --output");
+  }
+
+  let mut session = Session {
+    files: vec![],
+    current: 0,
+    clear: false,
+    autowrite: config.autowrite,
+    autostage: config.autostage || stage,
+    git,
+    review: None,
+    touched: vec![],
+    pane_ratios: None,
+    picker: None,
+    dirty: true,
+  };
+  let git = session.git.as_ref();
+
+  let mut auto_merged = 0;
+  // a file that could not be written fails the run once the others are done
+  let mut failed = false;
+  // only a run ending in the editor has a terminal to show the loading on
+  let interactive =
+    strategy.is_none() && !dump_json && script.is_none() && std::io::stdout().is_terminal();
+
+  for file_name in &file_names {
+    let mut ctx = Context {
+      file_name: file_name.clone(),
+      guide_column: config.guide_column,
+      fold_resolved: config.fold_resolved,
+      layout: config.layout,
+      ratios: config.pane_ratios,
+      wrap: config.wrap,
+      gutter: config.gutter,
+      ignore_whitespace: config.ignore_whitespace,
+      tab_width: config.tab_width,
+      backup: config.backup || backup,
+      output: output.clone(),
+      focus: 1,
+      context_lines: config.context_lines,
+      merge_tool: config.merge_tool.clone(),
+      diff_tool: config.diff_tool.clone(),
+      audit_log: config.audit_log.clone(),
+      permalink: config.permalink.clone(),
+      ..Default::default()
+    };
+
+    let in_repository = ctx.file_name != "-" && git.is_repository(&ctx.file_name);
+
+    if from_index && !in_repository {
+      fail(format!(
+        "{}: --index needs a file in a git repository",
+        ctx.file_name
+      ));
+    }
+
+    // the conflict is made again from the index, markers in the worktree file do not matter
+    let stages = from_index.then(|| match read_stages(git, &ctx.file_name, encoding) {
+      Ok(stages) => stages,
+      Err(err) => fail(err),
+    });
+    let file = match (read_file(&ctx.file_name, encoding), &stages) {
+      (Ok((file, encoding)), _) => {
+        ctx.encoding = encoding;
+        file
+      }
+      // the worktree file is only the input patches are made against
+      (Err(_), Some(_)) => input::Input::Decoded(String::new()),
+      (Err(err), None) => fail(err),
+    };
+    if let Some((_, _, _, encoding)) = &stages {
+      ctx.encoding = *encoding;
+    }
+    let text = file.strip_prefix('\u{feff}').unwrap_or(&file);
+
+    ctx.marker_size = in_repository
+      .then(|| git::marker_size(git, &ctx.file_name))
+      .flatten()
+      .unwrap_or_else(|| marker_size(text));
+    // markers in the worktree file mean nothing when the conflicts come from the index
+    let (problems, nested) = match stages {
+      Some(_) => (vec![], 0),
+      None => (
+        marker_problems(text, ctx.marker_size),
+        nested_conflicts(text, ctx.marker_size),
+      ),
+    };
+
+    if !problems.is_empty() && !force {
+      eprintln!(
+        "{}: malformed conflict markers, --force opens it anyway",
+        ctx.file_name
+      );
+      for problem in problems {
+        eprintln!("  {}", problem);
+      }
+      std::process::exit(1);
+    }
+
+    let driver = config.driver(&ctx.file_name);
+    let merged = match (driver, &stages) {
+      (_, Some(_)) => None,
+      (Some(command), None) => driver_merge(command, &ctx.file_name, text, ctx.marker_size),
+      (None, None) => structured
+        .then(|| structured_merge(&ctx.file_name, text, ctx.marker_size))
+        .flatten(),
+    };
+
+    let mut merge_message = None;
+    // a lockfile or a driver merged without conflicts needs no look
+    let auto_message = match (&merged, driver) {
+      (Some(Ok((_, 0))), Some(command)) => Some(format!("merged by `{}`", command)),
+      (Some(Ok((_, 0))), None) if lockfile::resolver(&ctx.file_name).is_some() => {
+        Some(String::from("merged the packages of both sides"))
+      }
+      _ => None,
+    };
+
+    match (merged, &stages) {
+      (_, Some((base, ours, theirs, _))) => {
+        parse_stages(base, ours, theirs, &mut ctx);
+        ctx.original = text.lines().map(text::Text::from).collect();
+      }
+      (Some(Ok((merged, conflicts))), None) => {
+        let bom = if file.starts_with('\u{feff}') {
+          "\u{feff}"
+        } else {
+          ""
+        };
+
+        parse_input_file(&format!("{}{}", bom, merged), &mut ctx);
+        ctx.original = text.lines().map(text::Text::from).collect();
+        ctx.modified = true;
+        merge_message = Some(match driver {
+          Some(command) => format!("Merged by `{}`, {} conflict(s) left", command, conflicts),
+          None => format!(
+            "Merged structurally, {} conflicting value(s) left",
+            conflicts
+          ),
+        });
+      }
+      (Some(Err(err)), None) => {
+        parse_input_file(&file, &mut ctx);
+        merge_message = Some(format!("Merging lines, {}", err));
+      }
+      (None, None) if interactive && file.len() >= BACKGROUND_PARSE => {
+        ctx = parse_showing_progress(file, ctx)?;
+      }
+      (None, None) => parse_input_file(&file, &mut ctx),
+    }
+
+    // only a run writing files in place writes it right away, a patch prints it and json or a
+    // script get it like any other file
+    if let (Some(_), None, Some(against)) = (&auto_message, strategy, patch) {
+      print_patch(&ctx, against);
+      auto_merged += 1;
+      continue;
+    }
+
+    if let (Some(auto_message), None, false, None) = (&auto_message, strategy, dump_json, &script) {
+      match save(&mut ctx) {
+        Ok(warning) => {
+          if let Some(warning) = warning {
+            eprintln!("{}", warning);
+          }
+          let staged = stage_written(git, session.autostage, &mut ctx);
+          println!("{}: {}{}", destination(&ctx), auto_message, staged);
+          session.touched.push(format!(
+            "{} → {}: {}{}",
+            ctx.file_name,
+            destination(&ctx),
+            auto_message,
+            staged
+          ));
+        }
+        Err(err) => {
+          eprintln!("{}", err);
+          session.touched.push(format!("{}: {}", ctx.file_name, err));
+          failed = true;
+        }
+      }
+      auto_merged += 1;
+      continue;
+    }
+
+    let resolved = whitespace.map_or(0, |preference| resolve_whitespace(preference, &mut ctx));
+    let referenced = reference
+      .as_ref()
+      .map_or(0, |reference| resolve_from_reference(reference, &mut ctx));
+    jump_to_first_conflict(&mut ctx);
+
+    // a pipeline gets the file back unchanged, otherwise there is nothing to do with it
+    if ctx.conflicts.is_empty() && !ctx.modified && !open_clean && !to_stdout {
+      eprintln!("{}: no conflicts found", ctx.file_name);
+      continue;
+    }
+
+    if ctx.conflicts.is_empty() && !ctx.modified {
+      ctx.view = View::Raw;
+    }
+
+    if in_repository {
+      ctx.repository_path = git::repository_path(git, &ctx.file_name);
+      ctx.head = git.head(&ctx.file_name);
+    }
+
+    // merge.conflictStyle says nothing about markers written by other tools
+    if in_repository && ctx.style == ConflictStyle::Git {
+      ctx.message = conflict_style_warning(&git::conflict_style(git, &ctx.file_name), &ctx);
+    }
+
+    if merge_message.is_some() {
+      ctx.message = merge_message;
+    }
+
+    if resolved > 0 {
+      ctx.message = Some(format!(
+        "{} conflict(s) differing only in whitespace resolved",
+        resolved
+      ));
+    }
+
+    if referenced > 0 {
+      ctx.message = Some(format!(
+        "{} conflict(s) resolved like the reference",
+        referenced
+      ));
+    }
+
+    if nested > 0 {
+      ctx.message = Some(format!(
+        "{} nested conflict(s) kept as content of the outer one",
+        nested
+      ));
+    }
+
+    if let Some(problem) = problems.first() {
+      ctx.message = Some(format!("Malformed markers, {}", problem));
+    }
+
+    if strategy.is_none() {
+      ctx.resume = state::load(&ctx.file_name, &ctx.original.join("\n"));
+    }
+
+    if ctx.resume.is_some() {
+      ctx.popup = Some(Popup {
+        title: String::from("Resume"),
+        lines: vec![Spans::from(
+          "This file was left unwritten with progress, pick up where it stopped?",
+        )],
+        choices: vec![('r', "Resume", resume), ('n', "Start over", start_over)],
+      });
+    }
+
+    session.files.push(ctx);
+  }
+
+  if session.files.is_empty() && auto_merged == 0 {
+    std::process::exit(2);
+  }
+
+  if session.files.is_empty() {
+    if failed {
+      std::process::exit(1);
+    }
+    return Ok(());
+  }
+
+  if session.files.len() > 1 {
+    session.picker = Some(0);
+  }
+
+  // the files as the editor would get them, after a strategy when one is given
+  if dump_json {
+    let files = session
+      .files
+      .iter_mut()
+      .map(|ctx| {
+        if let Some(strategy) = strategy {
+          resolve_remaining(strategy, ctx);
+        }
+        context_json(ctx)
+      })
+      .collect();
+
+    println!("{}", json::print(&json::array(files), "  ").join("\n"));
+    return Ok(());
+  }
+
+  if let Some(commands) = script {
+    if let Err(err) = run_script(&commands, &mut session) {
+      eprintln!("{}", err);
+      std::process::exit(1);
+    }
+
+    for ctx in &session.files {
+      if let Some(line) = open_file_line(ctx) {
+        println!("{}", line);
+      }
+    }
+
+    return Ok(());
+  }
+
+  // without a terminal nobody can answer the editor, fail instead of waiting for input
+  if strategy.is_none() && !std::io::stdout().is_terminal() {
+    eprintln!(
+      "stdout is not a terminal, pass --ours, --theirs or --union to resolve without the editor"
+    );
+    std::process::exit(1);
+  }
+
+  if let Some(strategy) = strategy {
+    for ctx in &mut session.files {
+      resolve_remaining(strategy, ctx);
+
+      if let Some(against) = patch {
+        print_patch(ctx, against);
+        continue;
+      }
+
+      if to_stdout {
+        match encoding::encode(&file_content(ctx), ctx.encoding) {
+          Ok(bytes) => std::io::stdout().write_all(&bytes)?,
+          Err(err) => {
+            eprintln!("{}: {}", ctx.file_name, err);
+            failed = true;
+          }
+        }
+        continue;
+      }
+
+      match save(ctx) {
+        Ok(Some(warning)) => eprintln!("{}", warning),
+        Ok(None) => (),
+        Err(err) => {
+          eprintln!("{}", err);
+          failed = true;
+          continue;
+        }
+      }
+
+      let staged = stage_written(session.git.as_ref(), session.autostage, ctx);
+      println!(
+        "{}: {} conflict(s) resolved{}",
+        ctx.file_name,
+        ctx.conflicts.len(),
+        staged
+      );
+    }
+
+    if failed {
+      std::process::exit(1);
+    }
     return Ok(());
   }
 
@@ -51,457 +791,6813 @@ fn main() -> Result<(), std::io::Error> {
     event::EnableMouseCapture,
   )?;
 
+  // a panic would otherwise leave the shell in raw mode on the alternate screen
+  let panic_hook = std::panic::take_hook();
+  std::panic::set_hook(Box::new(move |info| {
+    let _ = restore_terminal();
+    panic_hook(info);
+  }));
+
   let backend = tui::backend::CrosstermBackend::new(buffer);
-  let mut terminal = tui::Terminal::new(backend)?;
+  let result = tui::Terminal::new(backend)
+    .map_err(error::Error::from)
+    .and_then(|mut terminal| edit(&mut terminal, &mut session));
 
-  let mut ctx = Context {
-    file_name: args[1].clone(),
-    local_changes: vec![],
-    incoming_changes: vec![],
-    result: vec![],
-    current_line: 0,
-    line_offset: 0,
-    column_height: 0,
-  };
+  restore_terminal()?;
+
+  if let Some(ratios) = session.pane_ratios {
+    let value = ratios.map(|ratio| ratio.to_string()).join(" ");
+    if let Err(err) = config::save("pane_ratios", &value) {
+      eprintln!("Could not save pane widths: {}", err);
+    }
+  }
 
-  let file = std::fs::read_to_string(&ctx.file_name).expect("Could not read a input file!");
-  parse_input_file(file, &mut ctx);
+  if let Err(err) = result {
+    eprintln!("{}", err);
+    std::process::exit(1);
+  }
 
-  loop {
-    if !handle_events(&mut ctx) {
-      break;
+  // files still open after quit-all that need more work
+  for ctx in &session.files {
+    if let Err(err) = keep_state(ctx) {
+      eprintln!("{}: could not keep the progress: {}", ctx.file_name, err);
+    }
+
+    if let Some(line) = open_file_line(ctx) {
+      println!("{}", line);
     }
-    render(&mut terminal, &mut ctx);
   }
 
-  terminal::disable_raw_mode()?;
-  crossterm::execute!(
-    terminal.backend_mut(),
-    terminal::LeaveAlternateScreen,
-    event::DisableMouseCapture
-  )?;
+  if let Some(against) = patch {
+    for ctx in &session.files {
+      print_patch(ctx, against);
+    }
+  }
 
   Ok(())
 }
 
-fn parse_input_file(file: String, ctx: &mut Context) {
-  let mut column = Column::Middle;
+// a run that cannot go on, the reason goes to stderr and the exit code tells the caller
+fn fail(message: impl std::fmt::Display) -> ! {
+  eprintln!("{}", message);
+  std::process::exit(1);
+}
 
-  for line in file.lines() {
-    if line.starts_with("<<<<<<<") {
-      column = Column::Left;
-      continue;
-    }
-    if line.starts_with("=======") {
-      column = Column::Right;
-      continue;
+// everything parsing the file produced, for tools and tests checking the parser
+fn context_json(ctx: &Context) -> json::Value {
+  let number = |n: usize| json::Value::Number(n.to_string());
+  let text = |text: &Option<String>| text.as_deref().map_or(json::Value::Null, json::string);
+  let lines = |lines: &[String]| json::array(lines.iter().map(|line| json::string(line)).collect());
+  let buffer = |lines: &[Line]| {
+    json::array(
+      lines
+        .iter()
+        .map(|line| {
+          let change = match line.change {
+            Change::None => "none",
+            Change::Addition => "addition",
+            Change::Deletion => "deletion",
+          };
+          json::object(vec![
+            (String::from("change"), json::string(change)),
+            (String::from("value"), json::string(&line.value)),
+          ])
+        })
+        .collect(),
+    )
+  };
+  let conflicts = ctx
+    .conflicts
+    .iter()
+    .map(|conflict| {
+      let resolution = match is_unresolved(ctx, conflict) {
+        true => "unresolved",
+        false => resolution(ctx, conflict.start..conflict.end),
+      };
+
+      json::object(vec![
+        (String::from("start"), number(conflict.start)),
+        (String::from("end"), number(conflict.end)),
+        (
+          String::from("base"),
+          conflict.base.as_deref().map_or(json::Value::Null, lines),
+        ),
+        (String::from("resolution"), json::string(resolution)),
+      ])
+    })
+    .collect();
+  let style = match ctx.style {
+    ConflictStyle::Git => "git",
+    ConflictStyle::Mercurial => "mercurial",
+    ConflictStyle::Subversion => "subversion",
+  };
+
+  json::object(vec![
+    (String::from("file"), json::string(&ctx.file_name)),
+    (
+      String::from("encoding"),
+      json::string(encoding::name(ctx.encoding)),
+    ),
+    (String::from("bom"), json::Value::Bool(ctx.bom)),
+    (String::from("crlf"), json::Value::Bool(ctx.crlf)),
+    (
+      String::from("trailing_newline"),
+      json::Value::Bool(ctx.trailing_newline),
+    ),
+    (String::from("marker_size"), number(ctx.marker_size)),
+    (String::from("style"), json::string(style)),
+    (String::from("local_label"), text(&ctx.local_label)),
+    (String::from("incoming_label"), text(&ctx.incoming_label)),
+    (String::from("current_line"), number(ctx.current_line)),
+    (
+      String::from("unresolved"),
+      number(unresolved_conflicts(ctx)),
+    ),
+    (String::from("conflicts"), json::array(conflicts)),
+    (String::from("local"), buffer(&ctx.local_changes)),
+    (String::from("result"), buffer(&ctx.result)),
+    (String::from("incoming"), buffer(&ctx.incoming_changes)),
+  ])
+}
+
+fn print_patch(ctx: &Context, against: PatchBase) {
+  match resolution_patch(ctx, against) {
+    Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+    Err(err) => eprintln!("{}: {}", ctx.file_name, err),
+  }
+}
+
+// unified diff from the file as it was read (or its base) to what writing it would leave
+fn resolution_patch(ctx: &Context, against: PatchBase) -> Result<Vec<String>, String> {
+  let old = match against {
+    PatchBase::Input => ctx
+      .original
+      .iter()
+      .map(text::Text::to_string)
+      .collect::<Vec<_>>(),
+    PatchBase::Base => conflict_sides(&ctx.original.join("\n"), ctx.marker_size)
+      .1
+      .ok_or("no base in the conflict markers, merge with merge.conflictStyle diff3")?
+      .lines()
+      .map(String::from)
+      .collect(),
+  };
+  let path = ctx
+    .repository_path
+    .as_deref()
+    .unwrap_or(&ctx.file_name)
+    .trim_start_matches('/');
+
+  Ok(patch::unified(
+    &old,
+    &result_lines(ctx),
+    &format!("a/{}", path),
+    &format!("b/{}", path),
+  ))
+}
+
+// a file still needing work when the session ends
+fn open_file_line(ctx: &Context) -> Option<String> {
+  let unresolved = unresolved_conflicts(ctx);
+
+  (unresolved > 0 || ctx.modified).then(|| {
+    format!(
+      "{}: {} unresolved conflict(s){}",
+      ctx.file_name,
+      unresolved,
+      if ctx.modified { ", not written" } else { "" }
+    )
+  })
+}
+
+fn edit<B: tui::backend::Backend>(
+  terminal: &mut tui::Terminal<B>,
+  session: &mut Session,
+) -> Result<(), error::Error> {
+  // draw before the first key, a resume popup has to be seen to be answered
+  render(terminal, session)?;
+
+  loop {
+    let ctx = &session.files[session.current];
+    let (file_name, unresolved) = (ctx.file_name.clone(), unresolved_conflicts(ctx));
+
+    if !handle_events(session)? {
+      return Ok(());
     }
-    if line.starts_with(">>>>>>>") {
-      column = Column::Middle;
-      continue;
+    after_event(session, &file_name, unresolved);
+
+    if session.dirty {
+      render(terminal, session)?;
+      session.dirty = false;
     }
+  }
+}
 
-    match column {
-      Column::Left => {
-        ctx.local_changes.push(Line {
-          value: String::from(line),
-          change: Change::Addition,
-        });
-        ctx.result.push(Line {
-          value: String::from("#"),
-          change: Change::None,
-        });
-        ctx.incoming_changes.push(Line {
-          value: String::from("-"),
-          change: Change::Deletion,
-        });
-      }
-      Column::Middle => {
-        ctx.local_changes.push(Line {
-          value: String::from(line),
-          change: Change::None,
-        });
-        ctx.result.push(Line {
-          value: String::from(line),
-          change: Change::None,
-        });
-        ctx.incoming_changes.push(Line {
-          value: String::from(line),
-          change: Change::None,
-        });
-      }
-      Column::Right => {
-        ctx.local_changes.push(Line {
-          value: String::from("-"),
-          change: Change::Deletion,
-        });
-        ctx.result.push(Line {
-          value: String::from("#"),
-          change: Change::None,
-        });
-        ctx.incoming_changes.push(Line {
-          value: String::from(line),
-          change: Change::Addition,
-        });
+// parse a big file on a worker thread, a loading screen shows how far it got meanwhile
+fn parse_showing_progress(file: input::Input, ctx: Context) -> Result<Context, std::io::Error> {
+  let file_name = ctx.file_name.clone();
+  let receiver = parse_in_background(file, ctx);
+
+  crossterm::execute!(std::io::stdout(), terminal::EnterAlternateScreen)?;
+  let result = tui::Terminal::new(tui::backend::CrosstermBackend::new(std::io::stdout()))
+    .and_then(|mut terminal| loading(&mut terminal, &file_name, &receiver));
+  crossterm::execute!(std::io::stdout(), terminal::LeaveAlternateScreen)?;
+
+  result
+}
+
+fn loading<B: tui::backend::Backend>(
+  terminal: &mut tui::Terminal<B>,
+  file_name: &str,
+  receiver: &std::sync::mpsc::Receiver<Parsing>,
+) -> Result<Context, std::io::Error> {
+  let (mut percent, mut tick) = (0, 0);
+
+  loop {
+    render_loading(terminal, file_name, percent, tick)?;
+
+    match receiver.recv_timeout(std::time::Duration::from_millis(100)) {
+      Ok(Parsing::Progress(done)) => percent = done,
+      Ok(Parsing::Done(ctx)) => return Ok(*ctx),
+      Err(std::sync::mpsc::RecvTimeoutError::Timeout) => tick += 1,
+      Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+        return Err(std::io::Error::other(format!(
+          "{}: parsing stopped",
+          file_name
+        )))
       }
     }
   }
 }
 
-fn render(
-  terminal: &mut tui::Terminal<tui::backend::CrosstermBackend<std::io::Stdout>>,
-  ctx: &mut Context,
-) {
-  terminal
-    .draw(|frame| {
-      let Rect { height, .. } = frame.size();
+fn render_loading<B: tui::backend::Backend>(
+  terminal: &mut tui::Terminal<B>,
+  file_name: &str,
+  percent: usize,
+  tick: usize,
+) -> Result<(), std::io::Error> {
+  let spinner = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
-      let rows = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(height - 3), Constraint::Min(3)].as_ref())
-        .split(frame.size());
+  terminal.draw(|frame| {
+    let size = frame.size();
+    let text = Paragraph::new(format!(
+      "{} Loading {}… {}%",
+      spinner[tick % spinner.len()],
+      file_name,
+      percent
+    ))
+    .alignment(tui::layout::Alignment::Center);
 
-      let columns = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(
-          [
-            Constraint::Percentage(30),
-            Constraint::Percentage(40),
-            Constraint::Percentage(30),
-          ]
-          .as_ref(),
-        )
-        .split(rows[0]);
-
-      let column_height = columns[0].height as usize - 2; // remove top and bottom border
-      ctx.column_height = if ctx.result.len() < column_height {
-        ctx.result.len()
-      } else {
-        column_height
-      };
+    frame.render_widget(
+      text,
+      Rect {
+        y: size.y + size.height / 2,
+        height: size.height.min(1),
+        ..size
+      },
+    );
+  })?;
 
-      let current_line_style = Style::default().bg(Color::Yellow);
-      let add_style = Style::default().fg(Color::Green);
-      let remove_style = Style::default().fg(Color::Red);
-      let control_style = Style::default().fg(Color::LightBlue);
+  Ok(())
+}
 
-      let mut local_changes: Vec<Spans> = vec![];
-      let mut incoming_changes: Vec<Spans> = vec![];
-      let mut result: Vec<Spans> = vec![];
+// write and stage what the last key press or command finished
+fn after_event(session: &mut Session, file_name: &str, unresolved: usize) {
+  autowrite(session, file_name, unresolved);
 
-      let line_from = ctx.line_offset;
-      let line_to = ctx.line_offset + ctx.column_height;
+  let git = session.git.as_ref();
+  for ctx in session.files.iter_mut() {
+    let staged = stage_written(git, session.autostage, ctx);
+    if !staged.is_empty() {
+      ctx.message = Some(format!("Wrote {}{}", destination(ctx), staged));
+      session.dirty = true;
+    }
+  }
+}
 
-      for i in line_from..line_to {
-        let mut style = Style::default();
+fn read_script(path: &str) -> Result<Vec<(usize, script::Command)>, String> {
+  let mut text = String::new();
+  let read = match path {
+    "-" => std::io::stdin().read_to_string(&mut text).map(|_| ()),
+    path => std::fs::read_to_string(path).map(|content| text = content),
+  };
 
-        if i == ctx.current_line {
-          style = style.patch(current_line_style);
-        }
+  read.map_err(|err| err.to_string())?;
+  script::parse(&text)
+}
+
+// feed the commands through the same actions keys trigger, messages the editor would show
+// are printed; stops at the first command that cannot run
+fn run_script(commands: &[(usize, script::Command)], session: &mut Session) -> Result<(), String> {
+  for (line, command) in commands {
+    let files = session.files.len();
+    let ctx = &mut session.files[session.current];
+    let (file_name, unresolved) = (ctx.file_name.clone(), unresolved_conflicts(ctx));
+    let fail = |err: String| Err(format!("script line {}: {}", line, err));
+    let pick = |side: script::Side, local: Action, incoming: Action| match side {
+      script::Side::Ours => local,
+      _ => incoming,
+    };
+
+    let action = match *command {
+      script::Command::File(n) if n > files => return fail(format!("there are {} file(s)", files)),
+      script::Command::File(n) => Some(Action::OpenFile(n - 1)),
+      script::Command::Conflict(n, _) if n > ctx.conflicts.len() => {
+        return fail(format!(
+          "{} has {} conflict(s)",
+          file_name,
+          ctx.conflicts.len()
+        ))
+      }
+      script::Command::Conflict(n, side) => {
+        move_to(ctx.conflicts[n - 1].start, ctx);
+        Some(pick(
+          side,
+          Action::AcceptLocalConflict,
+          Action::AcceptIncomingConflict,
+        ))
+      }
+      script::Command::Line(n, _) | script::Command::Unresolve(n) if n > ctx.result.len() => {
+        return fail(format!("{} has {} line(s)", file_name, ctx.result.len()))
+      }
+      script::Command::Line(n, side) => {
+        move_to(n - 1, ctx);
+        Some(pick(side, Action::AcceptLocal, Action::AcceptIncoming))
+      }
+      script::Command::Unresolve(n) => {
+        move_to(n - 1, ctx);
+        Some(Action::Unresolve)
+      }
+      script::Command::All(side) => Some(pick(
+        side,
+        Action::AcceptLocalAll,
+        Action::AcceptIncomingAll,
+      )),
+      script::Command::Remaining(side) => {
+        perform(Action::ResolveRemaining, session);
+        let key = match side {
+          script::Side::Ours => 'o',
+          script::Side::Theirs => 't',
+          script::Side::Union => 'u',
+        };
+        handle_event(key_event(event::KeyCode::Char(key)), session);
+        None
+      }
+      script::Command::Key(key) => {
+        let code = match key {
+          script::Key::Char(c) => event::KeyCode::Char(c),
+          script::Key::Enter => event::KeyCode::Enter,
+          script::Key::Esc => event::KeyCode::Esc,
+          script::Key::Tab => event::KeyCode::Tab,
+          script::Key::Up => event::KeyCode::Up,
+          script::Key::Down => event::KeyCode::Down,
+          script::Key::Backspace => event::KeyCode::Backspace,
+        };
+        if !handle_event(key_event(code), session) {
+          return Ok(());
+        }
+        None
+      }
+      script::Command::Write => Some(Action::Write),
+      script::Command::WriteAll => Some(Action::WriteAll),
+    };
+
+    if let Some(action) = action {
+      if !perform(action, session) {
+        return Ok(());
+      }
+    }
+    after_event(session, &file_name, unresolved);
+
+    for ctx in session.files.iter_mut() {
+      if let Some(message) = ctx.message.take() {
+        println!("{}: {}", ctx.file_name, message);
+      }
+    }
+
+    let ctx = &session.files[session.current];
+    if let Some(popup) = &ctx.popup {
+      let choices: Vec<String> = popup
+        .choices
+        .iter()
+        .map(|(key, label, _)| format!("[{}] {}", key, label))
+        .collect();
+      println!("{}: {}, {}", ctx.file_name, popup.title, choices.join(" "));
+    }
+  }
+
+  Ok(())
+}
+
+fn key_event(code: event::KeyCode) -> event::Event {
+  event::Event::Key(event::KeyEvent::new(code, event::KeyModifiers::NONE))
+}
+
+fn restore_terminal() -> Result<(), std::io::Error> {
+  terminal::disable_raw_mode()?;
+  crossterm::execute!(
+    std::io::stdout(),
+    terminal::LeaveAlternateScreen,
+    event::DisableMouseCapture
+  )
+}
+
+// file content as UTF-8, in `encoding` or in the one detected from the bytes, `-` reads stdin
+fn read_file(
+  file_name: &str,
+  encoding: Option<encoding::Encoding>,
+) -> Result<(input::Input, encoding::Encoding), error::Error> {
+  let error = |reason: String| error::Error::Read(String::from(file_name), reason);
+  let bytes = input::read(file_name).map_err(|err| error(err.to_string()))?;
+
+  if encoding.is_none() && encoding::is_binary(&bytes) {
+    return Err(error::Error::Binary(String::from(file_name)));
+  }
+
+  let encoding = encoding.unwrap_or_else(|| encoding::detect(&bytes));
+
+  Ok((input::decode(bytes, encoding).map_err(error)?, encoding))
+}
+
+// base, local and incoming text of the file from the index and the encoding they are in, the
+// local side decides it unless --encoding does
+fn read_stages(
+  git: &dyn git::GitBackend,
+  file_name: &str,
+  encoding: Option<encoding::Encoding>,
+) -> Result<(String, String, String, encoding::Encoding), String> {
+  let stages = git.stages(file_name)?;
+  let encoding = encoding.unwrap_or_else(|| encoding::detect(&stages.ours));
+  let decode = |bytes: Vec<u8>| {
+    encoding::decode(&bytes, encoding).map_err(|err| format!("{}: {}", file_name, err))
+  };
+
+  Ok((
+    decode(stages.base.unwrap_or_default())?,
+    decode(stages.ours)?,
+    decode(stages.theirs)?,
+    encoding,
+  ))
+}
+
+// `mersge check`, report conflict markers left in files without opening anything; exits with 1
+// when there are some, 2 when a file could not be read
+fn check(count: bool, mut paths: Vec<String>) -> i32 {
+  let mut file_names = vec![];
+  let mut code = 0;
+
+  if paths.is_empty() {
+    paths.push(String::from("-"));
+  }
+
+  for path in &paths {
+    if let Err(err) = files_under(std::path::Path::new(path), &mut file_names) {
+      println!("Could not read {}: {}", path, err);
+      code = 2;
+    }
+  }
+
+  let mut total = 0;
+  let mut files = 0;
+
+  for file_name in &file_names {
+    let file = match read_file(file_name, None) {
+      Ok((file, _)) => file,
+      Err(error::Error::Binary(_)) => continue,
+      Err(err) => {
+        eprintln!("{}", err);
+        code = 2;
+        continue;
+      }
+    };
+    let lines = conflict_lines(&file);
+
+    if lines.is_empty() {
+      continue;
+    }
+
+    total += lines.len();
+    files += 1;
+
+    if count {
+      println!("{}: {} conflict(s)", file_name, lines.len());
+    } else {
+      for line in lines {
+        println!("{}:{}: conflict", file_name, line);
+      }
+    }
+  }
+
+  if count {
+    println!("{} conflict(s) in {} file(s)", total, files);
+  }
+
+  match (code, total) {
+    (0, 0) => 0,
+    (0, _) => 1,
+    _ => code,
+  }
+}
+
+// `mersge list`, every conflict of the files with its lines and labels, as JSON with `--json`
+fn list(as_json: bool, file_names: &[String]) -> i32 {
+  let mut files = vec![];
+  let mut code = 0;
+
+  for file_name in file_names {
+    let file = match read_file(file_name, None) {
+      Ok((file, _)) => file,
+      Err(err) => {
+        eprintln!("{}", err);
+        code = 2;
+        continue;
+      }
+    };
+    let text = file.strip_prefix('\u{feff}').unwrap_or(&file);
+    let conflicts = conflict_list(text, marker_size(text));
+
+    if !as_json {
+      for conflict in &conflicts {
+        println!(
+          "{}:{}-{}: {} {} line(s), {} {} line(s)",
+          file_name,
+          conflict.start,
+          conflict.end,
+          side_title("local", &conflict.labels[0]),
+          conflict.local.len(),
+          side_title("incoming", &conflict.labels[2]),
+          conflict.incoming.len()
+        );
+      }
+    }
+
+    files.push(json::object(vec![
+      (String::from("file"), json::string(file_name)),
+      (
+        String::from("conflicts"),
+        json::array(conflicts.iter().map(ListedConflict::to_json).collect()),
+      ),
+    ]));
+  }
+
+  if as_json {
+    println!("{}", json::print(&json::array(files), "  ").join("\n"));
+  }
+
+  code
+}
+
+// a conflict as `mersge list` reports it, lines are numbered from 1 and include the markers
+struct ListedConflict {
+  start: usize,
+  end: usize,
+  // labels after the `<<<<<<<`, `|||||||` and `>>>>>>>` markers
+  labels: [Option<String>; 3],
+  local: Vec<String>,
+  base: Option<Vec<String>>,
+  incoming: Vec<String>,
+}
+
+impl ListedConflict {
+  fn to_json(&self) -> json::Value {
+    let label = |label: &Option<String>| label.as_deref().map_or(json::Value::Null, json::string);
+    let lines =
+      |lines: &[String]| json::array(lines.iter().map(|line| json::string(line)).collect());
+
+    json::object(vec![
+      (
+        String::from("start"),
+        json::Value::Number(self.start.to_string()),
+      ),
+      (
+        String::from("end"),
+        json::Value::Number(self.end.to_string()),
+      ),
+      (String::from("local_label"), label(&self.labels[0])),
+      (String::from("base_label"), label(&self.labels[1])),
+      (String::from("incoming_label"), label(&self.labels[2])),
+      (String::from("local"), lines(&self.local)),
+      (
+        String::from("base"),
+        self.base.as_deref().map_or(json::Value::Null, lines),
+      ),
+      (String::from("incoming"), lines(&self.incoming)),
+    ])
+  }
+}
+
+fn conflict_list(file: &str, size: usize) -> Vec<ListedConflict> {
+  let lines: Vec<String> = file.lines().map(String::from).collect();
+  let markers = markers(lines.iter().map(String::as_str), size);
+  let mut conflicts: Vec<ListedConflict> = vec![];
+  let mut section = Section::Outside;
+
+  for (i, (line, marker)) in lines.iter().zip(markers).enumerate() {
+    match (marker, section) {
+      (Some('<'), _) => {
+        section = Section::Local;
+        conflicts.push(ListedConflict {
+          start: i + 1,
+          end: lines.len(),
+          labels: [marker_label(line, size), None, None],
+          local: vec![],
+          base: None,
+          incoming: vec![],
+        });
+      }
+      (Some('|'), Section::Local) => {
+        section = Section::Base;
+        let conflict = conflicts.last_mut().unwrap();
+        conflict.labels[1] = marker_label(line, size);
+        conflict.base = Some(vec![]);
+      }
+      (Some('='), Section::Local | Section::Base) => section = Section::Incoming,
+      (Some('>'), Section::Incoming) => {
+        section = Section::Outside;
+        let conflict = conflicts.last_mut().unwrap();
+        conflict.labels[2] = marker_label(line, size);
+        conflict.end = i + 1;
+      }
+      (_, Section::Outside) => (),
+      (_, section) => {
+        let conflict = conflicts.last_mut().unwrap();
+        let side = match section {
+          Section::Local => &mut conflict.local,
+          Section::Base => conflict.base.get_or_insert_with(Vec::new),
+          _ => &mut conflict.incoming,
+        };
+        side.push(line.clone());
+      }
+    }
+  }
+
+  conflicts
+}
+
+// files under `dir` with conflict markers, skipping what .gitignore excludes in a repository
+fn scan(git: &dyn git::GitBackend, dir: &str) -> Result<Vec<String>, std::io::Error> {
+  let files = match git.list_files(dir) {
+    Some(files) => files,
+    None => {
+      let mut files = vec![];
+      files_under(std::path::Path::new(dir), &mut files)?;
+      files
+    }
+  };
+
+  Ok(
+    files
+      .into_iter()
+      .filter(|file_name| {
+        read_file(file_name, None).is_ok_and(|(file, _)| !conflict_lines(&file).is_empty())
+      })
+      .collect(),
+  )
+}
+
+// files under `path`, directories are walked without going into `.git`; a link to a directory
+// inside is left out, it could lead back up the tree
+fn files_under(path: &std::path::Path, file_names: &mut Vec<String>) -> std::io::Result<()> {
+  if !path.is_dir() {
+    file_names.push(path.to_string_lossy().into_owned());
+    return Ok(());
+  }
+
+  let mut entries = std::fs::read_dir(path)?
+    .map(|entry| entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))))
+    .collect::<std::io::Result<Vec<_>>>()?;
+  entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+  for (entry, file_type) in entries.iter().filter(|(entry, _)| !entry.ends_with(".git")) {
+    match file_type.is_symlink() {
+      true if entry.is_dir() => continue,
+      true => file_names.push(entry.to_string_lossy().into_owned()),
+      false => files_under(entry, file_names)?,
+    }
+  }
+
+  Ok(())
+}
+
+// line numbers the conflicts of a file start on
+fn conflict_lines(file: &str) -> Vec<usize> {
+  let text = file.strip_prefix('\u{feff}').unwrap_or(file);
+
+  markers(text.lines(), marker_size(text))
+    .into_iter()
+    .enumerate()
+    .filter(|(_, marker)| *marker == Some('<'))
+    .map(|(i, _)| i + 1)
+    .collect()
+}
+
+fn parse_input_file(file: &str, ctx: &mut Context) {
+  parse_reporting(file, ctx, &mut |_| ());
+}
+
+// parse calling `progress` with the percentage of the file done each time it grows
+fn parse_reporting(file: &str, ctx: &mut Context, progress: &mut dyn FnMut(usize)) {
+  let mut column = Column::Middle;
+  let mut conflict_start = 0;
+  let mut base: Option<Vec<String>> = None;
+  let placeholders = placeholders();
+
+  let file = match file.strip_prefix('\u{feff}') {
+    Some(rest) => {
+      ctx.bom = true;
+      rest
+    }
+    None => file,
+  };
+
+  // unless the conflict-marker-size attribute already set it
+  if ctx.marker_size == 0 {
+    ctx.marker_size = marker_size(file);
+  }
+  ctx.crlf = is_crlf(file);
+  ctx.trailing_newline = file.ends_with('\n');
+  let size = ctx.marker_size;
+  let (mut done, mut percent) = (0, 0);
+
+  // markers out of sequence are kept as text, `marker_problems` reports them
+  for (line, marker) in file.lines().zip(markers(file.lines(), size)) {
+    done += line.len() + 1;
+    if done * 100 / file.len() > percent {
+      percent = done * 100 / file.len();
+      progress(percent.min(100));
+    }
+
+    // the buffers hold the same text as the original lines, not a copy of it
+    let value = text::Text::from(line);
+    ctx.original.push(value.clone());
+
+    if marker == Some('<') && column == Column::Middle {
+      column = Column::Left;
+      conflict_start = ctx.result.len();
+      if ctx.local_label.is_none() {
+        ctx.local_label = marker_label(line, size);
+      }
+      continue;
+    }
+    if marker == Some('|') && column == Column::Left && base.is_none() {
+      base = Some(vec![]);
+      continue;
+    }
+    if marker == Some('=') && column == Column::Left {
+      column = Column::Right;
+      continue;
+    }
+    if marker == Some('>') && column == Column::Right {
+      column = Column::Middle;
+      if ctx.incoming_label.is_none() {
+        ctx.incoming_label = marker_label(line, size);
+      }
+      ctx.conflicts.push(Conflict {
+        start: conflict_start,
+        end: ctx.result.len(),
+        base: base.take(),
+        toggled: false,
+      });
+      continue;
+    }
+
+    // diff3 base section sits between local and incoming and is not shown
+    if let (Column::Left, Some(base)) = (&column, &mut base) {
+      base.push(String::from(line));
+      continue;
+    }
+
+    push_line(ctx, &column, value, &placeholders);
+  }
+
+  // a conflict left open runs to the end of the file
+  if column != Column::Middle {
+    ctx.conflicts.push(Conflict {
+      start: conflict_start,
+      end: ctx.result.len(),
+      base: base.take(),
+      toggled: false,
+    });
+  }
+
+  ctx.style = conflict_style(ctx.local_label.as_deref(), ctx.incoming_label.as_deref());
+  ctx.local_label = ctx
+    .local_label
+    .take()
+    .map(|label| short_label(ctx.style, &label));
+  ctx.incoming_label = ctx
+    .incoming_label
+    .take()
+    .map(|label| short_label(ctx.style, &label));
+}
+
+// placeholders every unresolved line shares, for the result and for the other side
+fn placeholders() -> (text::Text, text::Text) {
+  (text::Text::from("#"), text::Text::from("-"))
+}
+
+// a line of the local or the incoming side of a conflict, or one around the conflicts that all
+// three buffers show
+fn push_line(
+  ctx: &mut Context,
+  column: &Column,
+  value: text::Text,
+  (unresolved, deleted): &(text::Text, text::Text),
+) {
+  let (local, incoming) = match column {
+    Column::Left => (
+      (value, Change::Addition),
+      (deleted.clone(), Change::Deletion),
+    ),
+    Column::Middle => ((value.clone(), Change::None), (value, Change::None)),
+    Column::Right => (
+      (deleted.clone(), Change::Deletion),
+      (value, Change::Addition),
+    ),
+  };
+  let result = match column {
+    Column::Middle => local.0.clone(),
+    _ => unresolved.clone(),
+  };
+
+  ctx.local_changes.push(Line {
+    value: local.0,
+    change: local.1,
+  });
+  ctx.result.push(Line {
+    value: result,
+    change: Change::None,
+  });
+  ctx.incoming_changes.push(Line {
+    value: incoming.0,
+    change: incoming.1,
+  });
+}
+
+// the buffers made from the base, local and incoming versions of the file in the index, the
+// conflicts come from merging them so nothing in the text is taken for a marker
+fn parse_stages(base: &str, ours: &str, theirs: &str, ctx: &mut Context) {
+  let placeholders = placeholders();
+  fn lines(side: &str) -> Vec<&str> {
+    side
+      .strip_prefix('\u{feff}')
+      .unwrap_or(side)
+      .lines()
+      .collect()
+  }
+
+  ctx.bom = ours.starts_with('\u{feff}');
+  ctx.crlf = is_crlf(ours);
+  ctx.trailing_newline = ours.ends_with('\n');
+  ctx.style = ConflictStyle::Git;
+  ctx.local_label = Some(String::from("HEAD"));
+  ctx.incoming_label = Some(String::from("MERGE_HEAD"));
+
+  for chunk in diff3::merge(&lines(base), &lines(ours), &lines(theirs)) {
+    match chunk {
+      diff3::Chunk::Clean(clean) => {
+        for line in clean {
+          push_line(ctx, &Column::Middle, text::Text::from(line), &placeholders);
+        }
+      }
+      diff3::Chunk::Conflict { base, ours, theirs } => {
+        let start = ctx.result.len();
+
+        for line in ours {
+          push_line(ctx, &Column::Left, text::Text::from(line), &placeholders);
+        }
+        for line in theirs {
+          push_line(ctx, &Column::Right, text::Text::from(line), &placeholders);
+        }
+
+        ctx.conflicts.push(Conflict {
+          start,
+          end: ctx.result.len(),
+          base: Some(base.into_iter().map(String::from).collect()),
+          toggled: false,
+        });
+      }
+    }
+  }
+}
+
+// what a worker thread parsing a file sends back
+enum Parsing {
+  Progress(usize),
+  Done(Box<Context>),
+}
+
+fn parse_in_background(file: input::Input, ctx: Context) -> std::sync::mpsc::Receiver<Parsing> {
+  let (sender, receiver) = std::sync::mpsc::channel();
+
+  std::thread::spawn(move || {
+    let mut ctx = ctx;
+    parse_reporting(&file, &mut ctx, &mut |percent| {
+      let _ = sender.send(Parsing::Progress(percent));
+    });
+    let _ = sender.send(Parsing::Done(Box::new(ctx)));
+  });
+
+  receiver
+}
+
+// `<<<<<<< working copy: ...` / `>>>>>>> merge rev: ...` for hg, `<<<<<<< .mine` /
+// `>>>>>>> .r42` for svn, anything else is taken as git
+fn conflict_style(local: Option<&str>, incoming: Option<&str>) -> ConflictStyle {
+  let local = local.unwrap_or_default();
+  let incoming = incoming.unwrap_or_default();
+
+  if ["working copy", "destination"]
+    .iter()
+    .any(|label| local.starts_with(label))
+    || ["merge rev", "source"]
+      .iter()
+      .any(|label| incoming.starts_with(label))
+  {
+    ConflictStyle::Mercurial
+  } else if local.starts_with('.') || incoming.starts_with('.') {
+    ConflictStyle::Subversion
+  } else {
+    ConflictStyle::Git
+  }
+}
+
+// hg follows the revision with ` - user: description`, svn starts file suffixes with a dot
+fn short_label(style: ConflictStyle, label: &str) -> String {
+  let label = match style {
+    ConflictStyle::Git => label,
+    ConflictStyle::Mercurial => label.split(" - ").next().unwrap_or(label),
+    ConflictStyle::Subversion => label.trim_start_matches('.'),
+  };
+
+  String::from(label.trim_end())
+}
+
+// whole file sides handed to the merge driver configured for the file, what it leaves is used
+// like a structured merge; `None` when there is nothing to merge
+fn driver_merge(
+  command: &str,
+  file_name: &str,
+  file: &str,
+  size: usize,
+) -> Option<Result<(String, usize), String>> {
+  if !markers(file.lines(), size).contains(&Some('<')) {
+    return None;
+  }
+
+  let (local, base, incoming) = conflict_sides(file, size);
+  let lines = |side: &str| side.lines().map(String::from).collect::<Vec<_>>();
+  let merged = tool::driver(
+    command,
+    file_name,
+    &lines(&local),
+    &lines(&base.unwrap_or_default()),
+    &lines(&incoming),
+  )
+  .map_err(|err| format!("`{}` failed: {}", command, err))
+  .and_then(|(merged, success)| {
+    let conflicts = markers(merged.iter().map(String::as_str), size)
+      .iter()
+      .filter(|marker| **marker == Some('<'))
+      .count();
+
+    match success || conflicts > 0 {
+      true => Ok((join_lines(&merged, file), conflicts)),
+      false => Err(format!("`{}` failed without leaving conflicts", command)),
+    }
+  });
+
+  Some(merged)
+}
+
+// files merged by their structure instead of line by line, `None` for any other file
+fn structured_merge(
+  file_name: &str,
+  file: &str,
+  size: usize,
+) -> Option<Result<(String, usize), String>> {
+  let extension = std::path::Path::new(file_name)
+    .extension()?
+    .to_str()?
+    .to_lowercase();
+
+  if !markers(file.lines(), size).contains(&Some('<')) {
+    return None;
+  }
+
+  if let Some(resolver) = lockfile::resolver(file_name) {
+    return Some(merge_lockfile(resolver, file, size));
+  }
+
+  match extension.as_str() {
+    "json" => Some(merge_json(file, size)),
+    "yaml" | "yml" => Some(merge_outline(file, size, "YAML", outline::parse_yaml)),
+    "toml" => Some(merge_outline(file, size, "TOML", outline::parse_toml)),
+    _ => None,
+  }
+}
+
+// the file with every value the sides agree on merged and a conflict for each one they do not,
+// and the number of those conflicts
+fn merge_json(file: &str, size: usize) -> Result<(String, usize), String> {
+  let (local, base, incoming) = conflict_sides(file, size);
+  let parse = |name: &str, side: &str| {
+    json::parse(side).map_err(|err| format!("{} side is not valid JSON: {}", name, err))
+  };
+
+  let base = base.map(|base| parse("base", &base)).transpose()?;
+  let merged = json::merge(
+    base.as_ref(),
+    Some(&parse("local", &local)?),
+    Some(&parse("incoming", &incoming)?),
+  )
+  .ok_or_else(|| String::from("both sides are empty"))?;
+
+  let markers = marker_lines(file, size);
+  let (lines, conflicts) = json::print_merged(
+    &merged,
+    &json::indent(&local),
+    [&markers[0], &markers[1], &markers[2]],
+  );
+
+  Ok((join_lines(&lines, file), conflicts))
+}
+
+fn merge_lockfile(
+  resolver: lockfile::Resolver,
+  file: &str,
+  size: usize,
+) -> Result<(String, usize), String> {
+  let rebuild = format!("run `{}` to rebuild it", resolver.regenerate);
+  let merge = resolver.merge.ok_or_else(|| rebuild.clone())?;
+  let (local, _, incoming) = conflict_sides(file, size);
+  let lines = merge(&local, &incoming).map_err(|err| format!("{}, {}", err, rebuild))?;
+
+  Ok((join_lines(&lines, file), 0))
+}
+
+// like `merge_json` for YAML mappings and TOML tables, comments stay with the key below them
+fn merge_outline(
+  file: &str,
+  size: usize,
+  format: &str,
+  parse: fn(&str) -> Result<Vec<outline::Entry>, String>,
+) -> Result<(String, usize), String> {
+  let (local, base, incoming) = conflict_sides(file, size);
+  let parse = |name: &str, side: &str| {
+    parse(side).map_err(|err| format!("{} side is not valid {}: {}", name, format, err))
+  };
+
+  let base = base.map(|base| parse("base", &base)).transpose()?;
+  let merged = outline::merge(
+    base.as_deref().unwrap_or_default(),
+    &parse("local", &local)?,
+    &parse("incoming", &incoming)?,
+  );
+
+  let markers = marker_lines(file, size);
+  let (lines, conflicts) = outline::print_merged(&merged, [&markers[0], &markers[1], &markers[2]]);
+
+  Ok((join_lines(&lines, file), conflicts))
+}
+
+// the whole file as local and incoming have it, and the base when the conflicts carry one
+fn conflict_sides(file: &str, size: usize) -> (String, Option<String>, String) {
+  let lines: Vec<String> = file.lines().map(String::from).collect();
+  let sections = sections(lines.iter().map(String::as_str), size);
+  let side = |keep: Section| {
+    lines
+      .iter()
+      .zip(&sections)
+      .filter(|(_, section)| **section == Section::Outside || **section == keep)
+      .map(|(line, _)| line.as_str())
+      .collect::<Vec<_>>()
+      .join("\n")
+  };
+
+  let base = sections
+    .contains(&Section::Base)
+    .then(|| side(Section::Base));
+
+  (side(Section::Local), base, side(Section::Incoming))
+}
+
+// the `<<<<<<<`, `=======` and `>>>>>>>` lines of the first conflict, labels included
+fn marker_lines(file: &str, size: usize) -> [String; 3] {
+  let markers = markers(file.lines(), size);
+
+  ['<', '=', '>'].map(|c| {
+    file
+      .lines()
+      .zip(&markers)
+      .find(|(_, marker)| **marker == Some(c))
+      .map(|(line, _)| String::from(line))
+      .unwrap_or_else(|| c.to_string().repeat(size))
+  })
+}
+
+// lines with the line endings of `file`, ending in a newline when it does
+fn join_lines(lines: &[String], file: &str) -> String {
+  let newline = if is_crlf(file) { "\r\n" } else { "\n" };
+  let mut text = lines.join(newline);
+
+  if file.ends_with('\n') {
+    text.push_str(newline);
+  }
+
+  text
+}
+
+// markers that do not follow `<` `|` `=` `>` order, with 1-based line numbers
+fn marker_problems(file: &str, size: usize) -> Vec<String> {
+  let mut problems = vec![];
+  // line the open conflict started on and the last marker seen in it
+  let mut open: Option<(usize, char)> = None;
+
+  for (i, marker) in markers(file.lines(), size).into_iter().enumerate() {
+    let number = i + 1;
+    let marker = match marker {
+      Some(marker) => marker,
+      None => continue,
+    };
+
+    match (marker, open) {
+      ('<', None) => open = Some((number, '<')),
+      ('|', Some((start, '<'))) => open = Some((start, '|')),
+      ('=', Some((start, '<' | '|'))) => open = Some((start, '=')),
+      ('>', Some((_, '='))) => open = None,
+      (_, None) => problems.push(format!(
+        "line {}: `{}` outside a conflict",
+        number,
+        marker.to_string().repeat(size)
+      )),
+      (_, Some((start, _))) => problems.push(format!(
+        "line {}: unexpected `{}` in the conflict from line {}",
+        number,
+        marker.to_string().repeat(size),
+        start
+      )),
+    }
+  }
+
+  if let Some((start, _)) = open {
+    problems.push(format!("line {}: conflict is never closed", start));
+  }
+
+  problems
+}
+
+// conflicted files listed by `git status --porcelain` on stdin
+fn read_git_status(git: &dyn git::GitBackend) -> Result<Vec<String>, std::io::Error> {
+  let mut porcelain = String::new();
+  std::io::Read::read_to_string(&mut std::io::stdin(), &mut porcelain)?;
+
+  // porcelain paths are relative to the repository root, not the working directory
+  let root = git.toplevel(".").unwrap_or_else(|| String::from("."));
+
+  Ok(
+    git::conflicted_paths(&porcelain)
+      .iter()
+      .map(|path| {
+        std::path::Path::new(&root)
+          .join(path)
+          .to_string_lossy()
+          .into_owned()
+      })
+      .collect(),
+  )
+}
+
+// marker length used by the first conflict in the file, 7 unless changed with the
+// conflict-marker-size attribute
+fn marker_size(file: &str) -> usize {
+  file
+    .lines()
+    .map(|line| line.chars().take_while(|c| *c == '<').count())
+    .find(|size| *size >= 7)
+    .unwrap_or(7)
+}
+
+// marker character of each line, markers of a conflict nested inside another one (left by a
+// recursive merge or a file merged again with its markers) are content of the outer conflict
+fn markers<'a>(lines: impl Iterator<Item = &'a str>, size: usize) -> Vec<Option<char>> {
+  let mut in_conflict = false;
+  let mut nested = false;
+
+  lines
+    .map(|line| {
+      let marker = ['<', '|', '=', '>']
+        .into_iter()
+        .find(|c| is_marker(line, *c, size));
+
+      if nested {
+        nested = marker != Some('>');
+        return None;
+      }
+
+      match marker {
+        Some('<') if in_conflict => {
+          nested = true;
+          None
+        }
+        Some('<') => {
+          in_conflict = true;
+          marker
+        }
+        Some('>') => {
+          in_conflict = false;
+          marker
+        }
+        _ => marker,
+      }
+    })
+    .collect()
+}
+
+// number of conflicts `markers` found nested inside another one
+fn nested_conflicts(file: &str, size: usize) -> usize {
+  file
+    .lines()
+    .zip(markers(file.lines(), size))
+    .filter(|(line, marker)| marker.is_none() && is_marker(line, '<', size))
+    .count()
+}
+
+fn marker_label(line: &str, size: usize) -> Option<String> {
+  Some(String::from(line[size..].trim())).filter(|label| !label.is_empty())
+}
+
+// exactly `size` marker characters, optionally followed by a label
+fn is_marker(line: &str, c: char, size: usize) -> bool {
+  let count = line.chars().take_while(|x| *x == c).count();
+  count == size && (line.len() == size || line[size..].starts_with(' '))
+}
+
+// the top of a file is usually untouched, start at the first conflict with a bit of context above
+fn jump_to_first_conflict(ctx: &mut Context) {
+  if let Some(conflict) = ctx.conflicts.first() {
+    ctx.current_line = conflict.start;
+    ctx.line_offset = conflict.start.saturating_sub(3);
+  }
+}
+
+// which lines of the original file belong to a conflict, markers included
+fn conflict_regions<'a>(lines: impl Iterator<Item = &'a str>, size: usize) -> Vec<bool> {
+  let mut in_conflict = false;
+
+  markers(lines, size)
+    .into_iter()
+    .map(|marker| {
+      if marker == Some('<') {
+        in_conflict = true;
+      } else if marker == Some('>') && in_conflict {
+        in_conflict = false;
+        return true;
+      }
+      in_conflict
+    })
+    .collect()
+}
+
+// whether most line endings in the file are \r\n
+fn is_crlf(file: &str) -> bool {
+  let crlf = file.matches("\r\n").count();
+  let lf = file.matches('\n').count() - crlf;
+
+  crlf > lf
+}
+
+// lines inside conflict markers and all lines of the file as it was read
+fn conflict_density(ctx: &Context) -> (usize, usize) {
+  let regions = conflict_regions(ctx.original.iter().map(text::Text::as_str), ctx.marker_size);
+
+  (
+    regions.iter().filter(|region| **region).count(),
+    regions.len(),
+  )
+}
+
+// one line per file, the most tangled files first and colored hotter
+fn summary_lines(files: &[Context]) -> Vec<Spans<'static>> {
+  let mut files: Vec<(&Context, usize, usize)> = files
+    .iter()
+    .map(|ctx| {
+      let (conflicting, total) = conflict_density(ctx);
+      (ctx, conflicting, total)
+    })
+    .collect();
+
+  let percent = |conflicting: usize, total: usize| conflicting * 100 / total.max(1);
+  files.sort_by_key(|(_, conflicting, total)| std::cmp::Reverse(percent(*conflicting, *total)));
+
+  files
+    .into_iter()
+    .map(|(ctx, conflicting, total)| {
+      let percent = percent(conflicting, total);
+      let color = match percent {
+        50.. => Color::Red,
+        20..=49 => Color::LightRed,
+        5..=19 => Color::Yellow,
+        _ => Color::Green,
+      };
+
+      Spans::from(vec![
+        Span::styled(format!("{:>3}% ", percent), Style::default().fg(color)),
+        Span::from(format!(
+          "{} ({}/{} lines, {} unresolved)",
+          ctx.file_name,
+          conflicting,
+          total,
+          unresolved_conflicts(ctx)
+        )),
+      ])
+    })
+    .collect()
+}
+
+// lines of the conflicts by where the result got them from
+#[derive(Debug, Default, PartialEq)]
+struct Diffstat {
+  local: usize,
+  incoming: usize,
+  // kept lines neither side had, pasted or typed in a merge tool
+  edited: usize,
+  deleted: usize,
+  unresolved: usize,
+}
+
+fn diffstat(ctx: &Context) -> Diffstat {
+  let mut stat = Diffstat::default();
+
+  for i in ctx
+    .conflicts
+    .iter()
+    .flat_map(|conflict| conflict.start..conflict.end)
+  {
+    match ctx.result[i].change {
+      Change::None => stat.unresolved += 1,
+      Change::Deletion => stat.deleted += 1,
+      Change::Addition if ctx.local_changes[i].change == Change::Addition => stat.local += 1,
+      Change::Addition if ctx.incoming_changes[i].change == Change::Addition => stat.incoming += 1,
+      Change::Addition => stat.edited += 1,
+    }
+  }
+
+  stat
+}
+
+// what the resolution of the file adds up to, to look over before writing
+fn diffstat_lines(ctx: &Context) -> Vec<Spans<'static>> {
+  let stat = diffstat(ctx);
+  let count = ctx.conflicts.len();
+  let row = |label: &str, lines: usize, color: Color| {
+    Spans::from(vec![
+      Span::from(format!("{:<11}", label)),
+      Span::styled(format!("{:>6}", lines), Style::default().fg(color)),
+      Span::from(match lines {
+        1 => " line",
+        _ => " lines",
+      }),
+    ])
+  };
+
+  vec![
+    Spans::from(format!(
+      "{}/{} conflicts resolved",
+      count - unresolved_conflicts(ctx),
+      count
+    )),
+    Spans::default(),
+    row("Local", stat.local, Color::Green),
+    row("Incoming", stat.incoming, Color::Green),
+    row("Edited", stat.edited, Color::Yellow),
+    row("Deleted", stat.deleted, Color::Red),
+    row("Unresolved", stat.unresolved, Color::DarkGray),
+  ]
+}
+
+// classify lines of file text by the conflict markers around them
+fn sections<'a>(lines: impl Iterator<Item = &'a str>, size: usize) -> Vec<Section> {
+  let mut section = Section::Outside;
+
+  markers(lines, size)
+    .into_iter()
+    .map(|marker| {
+      match (marker, section) {
+        (Some('<'), _) => section = Section::Local,
+        (Some('|'), Section::Local) => section = Section::Base,
+        (Some('='), Section::Local | Section::Base) => section = Section::Incoming,
+        (Some('>'), Section::Incoming) => section = Section::Outside,
+        _ => return section,
+      }
+
+      Section::Marker
+    })
+    .collect()
+}
+
+// switch to `view`, or back to the columns when it is already shown
+fn toggle_view(current: View, view: View) -> View {
+  if current == view {
+    View::Columns
+  } else {
+    view
+  }
+}
+
+// git's merge.conflictStyle tells whether conflicts should carry a base section
+fn conflict_style_warning(style: &str, ctx: &Context) -> Option<String> {
+  if ctx.conflicts.is_empty() {
+    return None;
+  }
+
+  let has_base = ctx.conflicts.iter().any(|conflict| conflict.base.is_some());
+  let expects_base = style == "diff3" || style == "zdiff3";
+
+  if expects_base && !has_base {
+    Some(format!(
+      "merge.conflictStyle is {} but the file has no base sections",
+      style
+    ))
+  } else if !expects_base && has_base {
+    Some(format!(
+      "merge.conflictStyle is {} but the file has diff3 base sections",
+      style
+    ))
+  } else {
+    None
+  }
+}
+
+// narrow terminals show local and incoming side by side, or the result alone
+fn pane_constraints(ctx: &Context, width: u16) -> [Constraint; 3] {
+  let two_panes = match ctx.layout {
+    config::Layout::Auto => width < 100,
+    config::Layout::Three => false,
+    config::Layout::Two => true,
+  };
+
+  let mut zoomed = [0; 3];
+  zoomed[ctx.focus] = 100;
+
+  let percentages = match (two_panes, ctx.show_result) {
+    _ if ctx.zoom => zoomed,
+    (false, _) => ctx.ratios,
+    (true, false) => [50, 0, 50],
+    (true, true) => [0, 100, 0],
+  };
+
+  percentages.map(Constraint::Percentage)
+}
+
+// percentages of some widths leave a column out between the panes, they are moved together
+// and the last one takes what is left
+fn close_gaps(mut columns: Vec<Rect>, area: Rect) -> Vec<Rect> {
+  let last = columns.iter().rposition(|column| column.width > 0);
+  let mut x = area.x;
+
+  for (i, column) in columns.iter_mut().enumerate() {
+    column.x = x;
+    if Some(i) == last {
+      column.width = area.right() - x;
+    }
+    x += column.width;
+  }
+
+  columns
+}
+
+// grow or shrink pane `focus` by `delta` percent, the difference goes to or comes from the
+// other panes, no pane gets narrower than 10%
+fn resize_pane(ratios: [u16; 3], focus: usize, delta: i16) -> [u16; 3] {
+  let mut ratios = ratios;
+  let others: Vec<usize> = (0..3).filter(|i| *i != focus).collect();
+
+  if delta > 0 {
+    for _ in 0..delta {
+      // take from the widest other pane
+      let widest = *others.iter().max_by_key(|i| ratios[**i]).unwrap();
+      if ratios[widest] <= 10 {
+        break;
+      }
+      ratios[widest] -= 1;
+      ratios[focus] += 1;
+    }
+  } else {
+    for _ in 0..-delta {
+      if ratios[focus] <= 10 {
+        break;
+      }
+      let narrowest = *others.iter().min_by_key(|i| ratios[**i]).unwrap();
+      ratios[narrowest] += 1;
+      ratios[focus] -= 1;
+    }
+  }
+
+  ratios
+}
+
+fn render<B: tui::backend::Backend>(
+  terminal: &mut tui::Terminal<B>,
+  session: &mut Session,
+) -> Result<(), std::io::Error> {
+  if session.clear {
+    terminal.clear()?;
+    session.clear = false;
+  }
+
+  if let Some(selected) = session.review {
+    return render_review(terminal, &session.files, &session.touched, selected);
+  }
+
+  if let Some(selected) = session.picker {
+    return render_picker(terminal, &session.files, selected);
+  }
+
+  let file_count = session.files.len();
+  let file_index = session.current;
+  let progress = progress(&session.files, file_index);
+  let tabs: Vec<String> = match file_count {
+    1 => vec![],
+    _ => session.files.iter().map(tab_title).collect(),
+  };
+  let ctx = &mut session.files[session.current];
+
+  terminal.draw(|frame| {
+    // one row of tabs above the panes when there are several files
+    let area = match tabs.is_empty() {
+      true => frame.size(),
+      false => Rect {
+        y: frame.size().y + 1,
+        height: frame.size().height.saturating_sub(1),
+        ..frame.size()
+      },
+    };
+    let Rect { height, .. } = area;
+
+    let rows = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints(
+        [
+          Constraint::Length(height.saturating_sub(3)),
+          Constraint::Min(3),
+        ]
+        .as_ref(),
+      )
+      .split(area);
+
+    // a one column minimap of the conflicts right of the panes
+    let (panes, strip) = match ctx.view == View::Columns && !ctx.conflicts.is_empty() {
+      true if rows[0].width > 1 => (
+        Rect {
+          width: rows[0].width - 1,
+          ..rows[0]
+        },
+        Some(Rect {
+          x: rows[0].x + rows[0].width - 1,
+          width: 1,
+          ..track(rows[0])
+        }),
+      ),
+      _ => (rows[0], None),
+    };
+
+    let columns = close_gaps(
+      Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(pane_constraints(ctx, frame.size().width).as_ref())
+        .split(panes),
+      panes,
+    );
+
+    ctx.column_rects = [columns[0], columns[1], columns[2]];
+
+    let column_height = (columns[0].height as usize).saturating_sub(2); // remove top and bottom border
+    let height = ctx.result.len().min(column_height);
+
+    if height != ctx.column_height {
+      fit_viewport(ctx, height);
+    }
+
+    let current_line_style = Style::default().bg(Color::Yellow);
+    let current_conflict_style = Style::default().bg(Color::Indexed(236));
+    let selection_style = Style::default().bg(Color::Blue);
+    let add_style = Style::default().fg(Color::Green);
+    let remove_style = Style::default().fg(Color::Red);
+    let control_style = Style::default().fg(Color::LightBlue);
+    let guide_style = Style::default().bg(Color::DarkGray);
+    let search_style = Style::default().bg(Color::Magenta);
+    let formatting_style = Style::default().fg(Color::DarkGray);
+    let resolved_style = Style::default().add_modifier(Modifier::DIM);
+
+    // whether the sides of a conflict only differ in formatting, their changes are not worth
+    // the color; only worked out for the conflicts that are on screen
+    let mut formatting_only: Vec<Option<bool>> = vec![None; ctx.conflicts.len()];
+    // resolved conflicts fade so the ones left stand out, worked out the same way
+    let mut resolved: Vec<Option<bool>> = vec![None; ctx.conflicts.len()];
+    // every line of the conflict under the cursor gets a faint background
+    let current_conflict = conflict_index(ctx, ctx.current_line)
+      .map(|c| ctx.conflicts[c].start..ctx.conflicts[c].end)
+      .unwrap_or_default();
+    let regions = context_regions(ctx);
+
+    let mut local_changes: Vec<Spans> = Vec::with_capacity(column_height);
+    let mut incoming_changes: Vec<Spans> = Vec::with_capacity(column_height);
+    let mut result: Vec<Spans> = Vec::with_capacity(column_height);
+
+    // the gutter takes a column from the text of every pane
+    let gutter = usize::from(ctx.gutter);
+    let (width_left, width_middle, width_right) = (
+      (columns[0].width as usize).saturating_sub(gutter),
+      (columns[1].width as usize).saturating_sub(gutter),
+      (columns[2].width as usize).saturating_sub(gutter),
+    );
+    // text per row when wrapping, a line that does not fit continues on the next rows; the
+    // only copy of a line made per frame, everything after works on it in place
+    let chunks = |value: &str, width: usize| {
+      let value = expand_tabs(value, ctx.tab_width);
+
+      match ctx.wrap {
+        true => wrap(&value, width.saturating_sub(2)),
+        false => vec![value.into_owned()],
+      }
+    };
+
+    // a line takes as many rows as it needs in the pane it wraps most in, so the panes stay
+    // side by side; a run of removed lines is one marker in the result pane, counted on the
+    // first line of the run shown from `from`
+    let line_rows = |ctx: &Context, i: usize, from: usize| {
+      let continued = i > from
+        && ctx.result[i - 1].change == Change::Deletion
+        && ctx.local_changes[i - 1].change == ctx.local_changes[i].change;
+      let middle = match ctx.result[i].change {
+        Change::Deletion if continued => 0,
+        _ => chunks(&result_value(ctx, i), width_middle).len(),
+      };
+
+      chunks(&ctx.local_changes[i].value, width_left)
+        .len()
+        .max(chunks(&ctx.incoming_changes[i].value, width_right).len())
+        .max(middle)
+    };
+
+    if ctx.wrap {
+      ctx.line_offset = wrapped_offset(ctx.line_offset, ctx.current_line, column_height, |i| {
+        line_rows(ctx, i, 0)
+      });
+    }
+
+    let line_from = ctx.line_offset;
+
+    let mut i = line_from;
+
+    ctx.side_rows.clear();
+
+    while i < ctx.result.len() && local_changes.len() < column_height {
+      let mut style = Style::default();
+
+      if let Some(fold) = fold_among(ctx, i, &regions) {
+        if fold.contains(&ctx.current_line) {
+          style = style.patch(current_line_style);
+        }
+
+        let summary = fold_summary(ctx, fold.clone());
+        local_changes.push(Spans::from(Span::styled(
+          pad(summary.clone(), columns[0].width as usize),
+          style,
+        )));
+        incoming_changes.push(Spans::from(Span::styled(
+          pad(summary, columns[2].width as usize),
+          style,
+        )));
+        ctx.side_rows.push(i);
+
+        i = fold.end;
+        continue;
+      }
+
+      if current_conflict.contains(&i) {
+        style = style.patch(current_conflict_style);
+      }
+
+      if i == ctx.current_line {
+        style = style.patch(current_line_style);
+      } else if ctx.selection.is_some() && selected_lines(ctx).contains(&i) {
+        style = style.patch(selection_style);
+      }
+
+      if i != ctx.current_line
+        && conflict_index(ctx, i).is_some_and(|c| {
+          *resolved[c].get_or_insert_with(|| !is_unresolved(ctx, &ctx.conflicts[c]))
+        })
+      {
+        style = style.patch(resolved_style);
+      }
+
+      let formatting = ctx.ignore_whitespace
+        && conflict_index(ctx, i).is_some_and(|c| {
+          *formatting_only[c].get_or_insert_with(|| identical_sides(ctx, &ctx.conflicts[c]))
+        });
+      let left_style = match ctx.local_changes[i].change {
+        Change::None => style,
+        _ if formatting => style.patch(formatting_style),
+        _ if ctx.gutter => style,
+        Change::Addition => style.patch(add_style),
+        Change::Deletion => style.patch(remove_style),
+      };
+      let right_style = match ctx.incoming_changes[i].change {
+        Change::None => style,
+        _ if formatting => style.patch(formatting_style),
+        _ if ctx.gutter => style,
+        Change::Addition => style.patch(add_style),
+        Change::Deletion => style.patch(remove_style),
+      };
+
+      let mut left = chunks(&ctx.local_changes[i].value, width_left);
+      let mut right = chunks(&ctx.incoming_changes[i].value, width_right);
+
+      for row in 0..line_rows(ctx, i, line_from) {
+        if local_changes.len() >= column_height {
+          break;
+        }
+
+        let span_content = pad(take_row(&mut left, row), width_left);
+        let overlays = search_overlays(&span_content, ctx, search_style);
+        let mut spans = styled(span_content, left_style, &overlays);
+        if ctx.gutter {
+          spans
+            .0
+            .insert(0, gutter_mark(&ctx.local_changes[i].change, row, style));
+        }
+        local_changes.push(spans);
+
+        let span_content = pad(take_row(&mut right, row), width_right);
+        let overlays = search_overlays(&span_content, ctx, search_style);
+        let mut spans = styled(span_content, right_style, &overlays);
+        if ctx.gutter {
+          spans
+            .0
+            .insert(0, gutter_mark(&ctx.incoming_changes[i].change, row, style));
+        }
+        incoming_changes.push(spans);
+
+        ctx.side_rows.push(i);
+      }
+
+      i += 1;
+    }
+
+    // middle column collapses deleted lines into a single marker row
+    let mut i = line_from;
+
+    ctx.result_rows.clear();
+
+    while i < ctx.result.len() && result.len() < column_height {
+      if let Some(fold) = fold_among(ctx, i, &regions) {
+        let style = if fold.contains(&ctx.current_line) {
+          current_line_style
+        } else {
+          Style::default()
+        };
+
+        result.push(Spans::from(Span::styled(
+          pad(fold_summary(ctx, fold.clone()), columns[1].width as usize),
+          style,
+        )));
+        ctx.result_rows.push(i);
+
+        i = fold.end;
+        continue;
+      }
+
+      let end = if ctx.result[i].change == Change::Deletion {
+        removed_run(ctx, i)
+      } else {
+        i + 1
+      };
+
+      let mut style = Style::default();
+
+      if current_conflict.contains(&i) {
+        style = style.patch(current_conflict_style);
+      }
+
+      if (i..end).contains(&ctx.current_line) {
+        style = style.patch(current_line_style);
+      } else if ctx.selection.is_some() && selected_lines(ctx).contains(&i) {
+        style = style.patch(selection_style);
+      }
+
+      if !(i..end).contains(&ctx.current_line)
+        && conflict_index(ctx, i).is_some_and(|c| {
+          *resolved[c].get_or_insert_with(|| !is_unresolved(ctx, &ctx.conflicts[c]))
+        })
+      {
+        style = style.patch(resolved_style);
+      }
+
+      let span_style = match ctx.result[i].change {
+        Change::None => style,
+        _ if ctx.gutter => style,
+        Change::Addition => style.patch(add_style),
+        Change::Deletion => style.patch(remove_style),
+      };
+
+      let mut value = chunks(&result_value(ctx, i), width_middle);
+      // padded to the rows the side panes give the same lines
+      let height = match ctx.wrap {
+        true => (i..end).map(|j| line_rows(ctx, j, line_from)).sum(),
+        false => value.len(),
+      };
+
+      for row in 0..height {
+        if result.len() >= column_height {
+          break;
+        }
+
+        // a wrapped line never reaches the guide column
+        let chunk = take_row(&mut value, row);
+        let (span_content, guide_range) = match ctx.wrap {
+          true => (pad(chunk, width_middle), None),
+          false => guide(pad(chunk, width_middle), ctx.guide_column),
+        };
+        let mut overlays = search_overlays(&span_content, ctx, search_style);
+
+        if let Some(range) = guide_range {
+          overlays.push((range, guide_style));
+        }
+
+        let mut spans = styled(span_content, span_style, &overlays);
+        if ctx.gutter {
+          spans
+            .0
+            .insert(0, gutter_mark(&ctx.result[i].change, row, style));
+        }
+        result.push(spans);
+        ctx.result_rows.push(i);
+      }
+
+      i = end;
+    }
+
+    let row_top = Block::default();
+    let mut row_bottom = Block::default().borders(Borders::ALL);
+
+    let mut title_bottom = vec![];
+
+    if file_count > 1 {
+      title_bottom.push(format!("{}, {}", ctx.file_name, progress));
+    }
+
+    if let Some(message) = &ctx.message {
+      title_bottom.push(message.clone());
+    }
+
+    if !title_bottom.is_empty() {
+      row_bottom = row_bottom.title(title_bottom.join(" | "));
+    }
+
+    let title_left = side_title("Local changes", &ctx.local_label);
+    let title_middle = result_title(ctx);
+    let title_right = side_title("Incoming changes", &ctx.incoming_label);
+
+    ctx.control_rects.clear();
+
+    if !tabs.is_empty() {
+      let mut spans = vec![];
+      let mut x = frame.size().x;
+
+      for (i, title) in tabs_from(&tabs, file_index, frame.size().width as usize) {
+        let width = title.width() as u16 + 2;
+        let style = match i == file_index {
+          true => current_line_style,
+          false => Style::default(),
+        };
+
+        ctx
+          .control_rects
+          .push((Rect::new(x, frame.size().y, width, 1), Action::OpenFile(i)));
+        spans.push(Span::styled(format!(" {} ", title), style));
+        spans.push(Span::styled("│", control_style));
+        x += width + 1;
+      }
+
+      frame.render_widget(
+        Paragraph::new(Spans::from(spans)),
+        Rect {
+          height: 1,
+          ..frame.size()
+        },
+      );
+    }
+
+    // clicking a side pane title takes that side for the whole conflict
+    for (column, title, action) in [
+      (columns[0], &title_left, Action::AcceptLocalConflict),
+      (columns[2], &title_right, Action::AcceptIncomingConflict),
+    ] {
+      if column.width > 0 {
+        ctx.control_rects.push((
+          Rect::new(column.x + 1, column.y, title.width() as u16, 1),
+          action,
+        ));
+      }
+    }
+
+    let border_style = |column: usize| match column == ctx.focus {
+      true => control_style,
+      false => Style::default(),
+    };
+
+    let block_left = Block::default()
+      .title(title_left.as_str())
+      .borders(Borders::ALL)
+      .border_style(border_style(0));
+    let block_middle = Block::default()
+      .title(title_middle.as_str())
+      .borders(Borders::ALL)
+      .border_style(border_style(1));
+    let block_right = Block::default()
+      .title(title_right.as_str())
+      .borders(Borders::ALL)
+      .border_style(border_style(2));
+
+    let text_left = Paragraph::new(local_changes).block(block_left);
+    let text_middle = Paragraph::new(result).block(block_middle);
+    let text_right = Paragraph::new(incoming_changes).block(block_right);
+
+    let mut control_spans: Vec<Span> = vec![];
+    let mut x = rows[1].x + 1; // skip left border
+
+    for (key, label, action) in CONTROLS {
+      let width = (key.len() + label.len()) as u16;
+      ctx
+        .control_rects
+        .push((Rect::new(x, rows[1].y + 1, width, 1), action));
+      x += width;
+
+      control_spans.push(Span::styled(key, control_style));
+      control_spans.push(Span::from(label));
+    }
+
+    if let Some(prompt) = &ctx.prompt {
+      control_spans = vec![
+        Span::styled(prompt.kind.to_string(), control_style),
+        Span::from(prompt.input.clone()),
+        Span::styled(" ", current_line_style),
+      ];
+    }
+
+    let controls = Paragraph::new(vec![Spans::from(control_spans)]).block(row_bottom);
+
+    frame.render_widget(row_top, rows[0]);
+    frame.render_widget(controls, rows[1]);
+
+    if ctx.view != View::Columns {
+      let unified;
+      let (title, text): (&str, Vec<&str>) = match ctx.view {
+        View::Raw => (
+          "Original file",
+          ctx.original.iter().map(text::Text::as_str).collect(),
+        ),
+        _ => {
+          unified = result_lines(ctx);
+          (
+            "Unified result",
+            unified.iter().map(String::as_str).collect(),
+          )
+        }
+      };
+      let height = rows[0].height.saturating_sub(2) as usize;
+      // sections depend on the markers above, nothing below the view is needed
+      let end = (ctx.view_offset + height).min(text.len());
+      let sections = sections(text[..end].iter().copied(), ctx.marker_size);
+      let lines: Vec<Spans> = text[..end]
+        .iter()
+        .zip(sections)
+        .skip(ctx.view_offset)
+        .map(|(line, section)| {
+          let style = match section {
+            Section::Outside => Style::default(),
+            Section::Marker => control_style,
+            Section::Local => add_style,
+            Section::Base => Style::default().fg(Color::DarkGray),
+            Section::Incoming => remove_style,
+          };
+          Spans::from(Span::styled(expand_tabs(line, ctx.tab_width), style))
+        })
+        .collect();
+
+      let text_view =
+        Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL));
+      let total = text.len();
+
+      frame.render_widget(text_view, rows[0]);
+      frame.render_widget(
+        Scrollbar(scrollbar(
+          ctx.view_offset,
+          height,
+          total,
+          track(rows[0]).height,
+        )),
+        track(rows[0]),
+      );
+    } else {
+      let thumb = scrollbar(
+        ctx.line_offset,
+        ctx.column_height,
+        ctx.result.len(),
+        track(columns[1]).height,
+      );
+
+      // the two-pane layout hides some columns by giving them no width
+      for (text, column) in [
+        (text_left, columns[0]),
+        (text_middle, columns[1]),
+        (text_right, columns[2]),
+      ] {
+        if column.width > 0 {
+          frame.render_widget(text, column);
+          frame.render_widget(Scrollbar(thumb.clone()), track(column));
+        }
+      }
+    }
+
+    if let Some(strip) = strip {
+      let mut marks = vec![];
+
+      for (row, (mark, in_view, line)) in
+        minimap(ctx, strip.height as usize).into_iter().enumerate()
+      {
+        let color = match mark {
+          Mark::Unresolved => Color::Red,
+          Mark::Resolved => Color::Green,
+          Mark::Context => Color::DarkGray,
+        };
+        let symbol = match in_view {
+          true => "█",
+          false => "▐",
+        };
+
+        marks.push(Spans::from(Span::styled(
+          symbol,
+          Style::default().fg(color),
+        )));
+        ctx.control_rects.push((
+          Rect::new(strip.x, strip.y + row as u16, 1, 1),
+          Action::JumpTo(line),
+        ));
+      }
+
+      frame.render_widget(Paragraph::new(marks), strip);
+    }
+
+    if let Some(popup) = &ctx.popup {
+      let mut lines = popup.lines.clone();
+      let mut choices: Vec<Span> = vec![];
+
+      for (key, label, _) in &popup.choices {
+        choices.push(Span::styled(format!("[{}] ", key), control_style));
+        choices.push(Span::from(format!("{} ", label)));
+      }
+
+      lines.push(Spans::default());
+      lines.push(Spans::from(choices));
+
+      let size = frame.size();
+      let width = size.width.min(80);
+      let height = (lines.len() as u16 + 2).min(size.height);
+      let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+      );
+
+      let text = Paragraph::new(lines).block(
+        Block::default()
+          .title(popup.title.as_str())
+          .borders(Borders::ALL),
+      );
+
+      frame.render_widget(Clear, area);
+      frame.render_widget(text, area);
+    }
+  })?;
+
+  Ok(())
+}
+
+// last look at every file touched before exiting, Enter goes back into the selected one, the
+// files no longer open come after the open ones and cannot be picked
+fn render_review<B: tui::backend::Backend>(
+  terminal: &mut tui::Terminal<B>,
+  files: &[Context],
+  touched: &[String],
+  selected: usize,
+) -> Result<(), std::io::Error> {
+  let closed = touched.iter().map(|line| {
+    Spans::from(Span::styled(
+      line.clone(),
+      Style::default().fg(Color::DarkGray),
+    ))
+  });
+  let lines: Vec<Spans> = files
+    .iter()
+    .enumerate()
+    .flat_map(|(i, ctx)| {
+      let style = if i == selected {
+        Style::default().bg(Color::Yellow)
+      } else {
+        Style::default()
+      };
+      let warnings = line_count_warning(ctx)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|warning| {
+          Spans::from(Span::styled(
+            format!("  ! {}", warning),
+            Style::default().fg(Color::Red),
+          ))
+        });
+
+      std::iter::once(Spans::from(Span::styled(review_line(ctx), style))).chain(warnings)
+    })
+    .chain(closed)
+    .collect();
+
+  terminal.draw(|frame| {
+    let rows = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+      .split(frame.size());
+
+    let text = Paragraph::new(lines).block(
+      Block::default()
+        .title("Review before quitting")
+        .borders(Borders::ALL),
+    );
+    let controls = Paragraph::new(Spans::from(vec![
+      Span::styled("[Enter] ", Style::default().fg(Color::LightBlue)),
+      Span::from("Back to file "),
+      Span::styled("[Esc] ", Style::default().fg(Color::LightBlue)),
+      Span::from("Cancel "),
+      Span::styled("[Q] ", Style::default().fg(Color::LightBlue)),
+      Span::from("Quit "),
+    ]))
+    .block(Block::default().borders(Borders::ALL));
+
+    frame.render_widget(text, rows[0]);
+    frame.render_widget(controls, rows[1]);
+  })?;
+
+  Ok(())
+}
+
+// every file with what is left of it, Enter opens the selected one
+fn render_picker<B: tui::backend::Backend>(
+  terminal: &mut tui::Terminal<B>,
+  files: &[Context],
+  selected: usize,
+) -> Result<(), std::io::Error> {
+  let lines: Vec<Spans> = files
+    .iter()
+    .enumerate()
+    .map(|(i, ctx)| {
+      let style = match (i == selected, unresolved_conflicts(ctx)) {
+        (true, _) => Style::default().bg(Color::Yellow),
+        (false, 0) => Style::default().fg(Color::Green),
+        (false, _) => Style::default(),
+      };
+
+      Spans::from(Span::styled(picker_line(ctx), style))
+    })
+    .collect();
+
+  terminal.draw(|frame| {
+    let rows = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+      .split(frame.size());
+
+    let resolved = files
+      .iter()
+      .filter(|ctx| unresolved_conflicts(ctx) == 0)
+      .count();
+    let text = Paragraph::new(lines).block(
+      Block::default()
+        .title(format!("Files — {}/{} resolved", resolved, files.len()))
+        .borders(Borders::ALL),
+    );
+    let controls = Paragraph::new(Spans::from(vec![
+      Span::styled("[Enter] ", Style::default().fg(Color::LightBlue)),
+      Span::from("Open file "),
+      Span::styled("[Esc] ", Style::default().fg(Color::LightBlue)),
+      Span::from("Back "),
+      Span::styled("[Q] ", Style::default().fg(Color::LightBlue)),
+      Span::from("Quit "),
+    ]))
+    .block(Block::default().borders(Borders::ALL));
+
+    frame.render_widget(text, rows[0]);
+    frame.render_widget(controls, rows[1]);
+  })?;
+
+  Ok(())
+}
+
+// file name on its tab, with the conflicts left or a check mark
+fn tab_title(ctx: &Context) -> String {
+  let name = std::path::Path::new(&ctx.file_name)
+    .file_name()
+    .map_or(ctx.file_name.clone(), |name| {
+      name.to_string_lossy().into_owned()
+    });
+
+  match unresolved_conflicts(ctx) {
+    0 => format!("{} ✓", name),
+    unresolved => format!("{} ({})", name, unresolved),
+  }
+}
+
+// tabs that fit in `width`, starting late enough for the current one to be shown
+fn tabs_from(tabs: &[String], current: usize, width: usize) -> Vec<(usize, &String)> {
+  let widths: Vec<usize> = tabs.iter().map(|title| title.width() + 3).collect();
+  let first = (0..=current)
+    .find(|first| widths[*first..=current].iter().sum::<usize>() <= width)
+    .unwrap_or(current);
+
+  tabs.iter().enumerate().skip(first).collect()
+}
+
+// position among the files and the conflicts resolved in all of them
+fn progress(files: &[Context], current: usize) -> String {
+  let count: usize = files.iter().map(|ctx| ctx.conflicts.len()).sum();
+  let unresolved: usize = files.iter().map(unresolved_conflicts).sum();
+
+  format!(
+    "file {}/{}, {}/{} conflicts resolved",
+    current + 1,
+    files.len(),
+    count - unresolved,
+    count
+  )
+}
+
+// a check mark once no conflict of the file is left
+fn picker_line(ctx: &Context) -> String {
+  let unresolved = unresolved_conflicts(ctx);
+  let state = match (unresolved, ctx.conflicts.len()) {
+    (_, 0) => String::from("no conflicts"),
+    (0, count) => format!("{} conflict(s) resolved", count),
+    (unresolved, count) => format!("{}/{} conflict(s) left", unresolved, count),
+  };
+
+  format!(
+    "{} {} — {}{}",
+    if unresolved == 0 { "✓" } else { " " },
+    ctx.file_name,
+    state,
+    if ctx.modified { ", not written" } else { "" }
+  )
+}
+
+// side pane title with the branch or commit named by the conflict markers
+fn side_title(name: &str, label: &Option<String>) -> String {
+  match label {
+    Some(label) => format!("{} ({})", name, label),
+    None => String::from(name),
+  }
+}
+
+// where the file goes, how its conflicts were resolved and what is left
+fn review_line(ctx: &Context) -> String {
+  let mut taken: Vec<(&str, usize)> = vec![];
+
+  for conflict in &ctx.conflicts {
+    let range = conflict.start..conflict.end;
+
+    if range.clone().any(|i| ctx.result[i].change == Change::None) {
+      continue;
+    }
+
+    let side = resolution(ctx, range);
+    match taken.iter_mut().find(|(name, _)| *name == side) {
+      Some((_, count)) => *count += 1,
+      None => taken.push((side, 1)),
+    }
+  }
+
+  let mut parts = vec![format!("{} conflict(s)", ctx.conflicts.len())];
+
+  if !taken.is_empty() {
+    parts.push(
+      taken
+        .iter()
+        .map(|(side, count)| format!("{} {}", count, side))
+        .collect::<Vec<_>>()
+        .join(", "),
+    );
+  }
+
+  let unresolved = unresolved_conflicts(ctx);
+  if unresolved > 0 {
+    parts.push(format!("{} unresolved", unresolved));
+  }
+
+  parts.push(String::from(if ctx.modified {
+    "not written"
+  } else {
+    "nothing to write"
+  }));
+
+  format!(
+    "{} → {}: {}",
+    ctx.file_name,
+    destination(ctx),
+    parts.join("; ")
+  )
+}
+
+// waits at most a tick for input, the editor loop gets a turn in between even when nothing
+// is pressed so work of its own never has to wait for a key
+fn handle_events(session: &mut Session) -> Result<bool, error::Error> {
+  if !event::poll(TICK)? {
+    return Ok(true);
+  }
+
+  Ok(handle_event(event::read()?, session))
+}
+
+// a key press, click or resize from the terminal or a script, false when the program should exit
+fn handle_event(input: event::Event, session: &mut Session) -> bool {
+  // events that change nothing unset it, everything else needs a new frame
+  let mut changed = true;
+  // times the action runs, more than once after a count
+  let mut times = 1;
+
+  // everything is laid out again, nothing drawn for the old size can stay
+  if let event::Event::Resize(_, _) = input {
+    session.clear = true;
+  }
+
+  // Ctrl-C works from anywhere like Shift-Q, and exits from the review screen
+  if let event::Event::Key(event::KeyEvent {
+    code: event::KeyCode::Char('c'),
+    modifiers,
+  }) = input
+  {
+    if modifiers.contains(event::KeyModifiers::CONTROL) {
+      if session.review.is_some() {
+        return false;
+      }
+
+      let ctx = &mut session.files[session.current];
+      (ctx.popup, ctx.prompt, ctx.pending_key) = (None, None, None);
+      session.picker = None;
+      session.dirty = true;
+      return perform(Action::QuitAll, session);
+    }
+  }
+
+  if let Some(selected) = session.review {
+    if let event::Event::Key(event) = input {
+      match event.code {
+        event::KeyCode::Char('q') | event::KeyCode::Char('Q') => return false,
+        event::KeyCode::Up => session.review = Some(selected.saturating_sub(1)),
+        event::KeyCode::Down => session.review = Some((selected + 1).min(session.files.len() - 1)),
+        event::KeyCode::Enter => {
+          session.current = selected;
+          session.review = None;
+        }
+        event::KeyCode::Esc => session.review = None,
+        _ => changed = false,
+      }
+    }
+
+    session.dirty |= changed;
+    return true;
+  }
+
+  if let Some(selected) = session.picker {
+    if let event::Event::Key(event) = input {
+      match event.code {
+        // quitting goes through the review screen like from a file
+        event::KeyCode::Char('q') | event::KeyCode::Char('Q') => {
+          session.picker = None;
+          session.review = Some(selected);
+        }
+        event::KeyCode::Up => session.picker = Some(selected.saturating_sub(1)),
+        event::KeyCode::Down => session.picker = Some((selected + 1).min(session.files.len() - 1)),
+        event::KeyCode::Enter => {
+          session.current = selected;
+          session.picker = None;
+        }
+        event::KeyCode::Esc => session.picker = None,
+        _ => changed = false,
+      }
+    }
+
+    session.dirty |= changed;
+    return true;
+  }
+
+  let ctx = &mut session.files[session.current];
+
+  let action = match input {
+    event::Event::Key(event) if ctx.popup.is_some() => {
+      let popup = ctx.popup.take().unwrap();
+
+      if let event::KeyCode::Char(key) = event.code {
+        if let Some((_, _, choice)) = popup.choices.iter().find(|(choice, _, _)| *choice == key) {
+          choice(ctx);
+        }
+      }
+
+      // a confirmed close goes on with the quit that asked
+      ctx.close.then_some(Action::Quit)
+    }
+
+    event::Event::Key(event) if ctx.prompt.is_some() => {
+      let prompt = ctx.prompt.as_mut().unwrap();
+
+      match event.code {
+        event::KeyCode::Char(c) => prompt.input.push(c),
+        event::KeyCode::Backspace => {
+          prompt.input.pop();
+        }
+        event::KeyCode::Enter => {
+          let prompt = ctx.prompt.take().unwrap();
+          submit_prompt(prompt, ctx);
+        }
+        event::KeyCode::Esc => ctx.prompt = None,
+        _ => (),
+      };
+
+      None
+    }
+
+    event::Event::Key(event) if ctx.view != View::Columns => {
+      let page = ctx.column_height.max(1);
+      let last = match ctx.view {
+        View::Raw => ctx.original.len(),
+        _ => result_lines(ctx).len(),
+      }
+      .saturating_sub(1);
+
+      match event.code {
+        event::KeyCode::Char('o') => Some(Action::ToggleRaw),
+        event::KeyCode::Char('u') => Some(Action::ToggleUnified),
+        event::KeyCode::Esc => {
+          ctx.view = View::Columns;
+          None
+        }
+        event::KeyCode::Char('q') => Some(Action::Quit),
+        event::KeyCode::Down => {
+          ctx.view_offset = (ctx.view_offset + 1).min(last);
+          None
+        }
+        event::KeyCode::Up => {
+          ctx.view_offset = ctx.view_offset.saturating_sub(1);
+          None
+        }
+        event::KeyCode::PageDown => {
+          ctx.view_offset = (ctx.view_offset + page).min(last);
+          None
+        }
+        event::KeyCode::PageUp => {
+          ctx.view_offset = ctx.view_offset.saturating_sub(page);
+          None
+        }
+        event::KeyCode::Home | event::KeyCode::Char('g') => {
+          ctx.view_offset = 0;
+          None
+        }
+        event::KeyCode::End | event::KeyCode::Char('G') => {
+          ctx.view_offset = last;
+          None
+        }
+        _ => {
+          changed = false;
+          None
+        }
+      }
+    }
+
+    event::Event::Key(event) => {
+      let had_message = ctx.message.take().is_some();
+      let count = ctx.count.take();
+
+      let action = match (ctx.pending_key.take(), event.code) {
+        (Some('a'), event::KeyCode::Char('l')) => Some(Action::AcceptLocalAll),
+        (Some('a'), event::KeyCode::Char('r')) => Some(Action::AcceptIncomingAll),
+        (Some('z'), event::KeyCode::Char('a')) => Some(Action::ToggleFold),
+        (Some('z'), event::KeyCode::Char('c')) => Some(Action::CollapseContext),
+        (Some('z'), event::KeyCode::Char('w')) => Some(Action::ToggleWrap),
+        (Some('z'), event::KeyCode::Char('g')) => Some(Action::ToggleGutter),
+        (Some('z'), event::KeyCode::Char('i')) => Some(Action::ToggleIgnoreWhitespace),
+        (Some('z'), event::KeyCode::Char('z')) => Some(Action::CenterView),
+        (Some('z'), event::KeyCode::Char('t')) => Some(Action::ViewTop),
+        (Some('z'), event::KeyCode::Char('b')) => Some(Action::ViewBottom),
+        (Some(_), _) => None,
+        (None, event::KeyCode::Char(c)) if chorded(event.modifiers) => chord(event.modifiers, c),
+        // a count never starts with 0
+        (None, event::KeyCode::Char(digit @ '0'..='9')) if digit != '0' || count.is_some() => {
+          // no motion goes further than the file is long, and a huge count would keep the
+          // editor busy repeating it
+          let count = count
+            .unwrap_or(0)
+            .saturating_mul(10)
+            .saturating_add(digit as usize - '0' as usize)
+            .min(ctx.result.len().max(1));
+          ctx.count = Some(count);
+          ctx.message = Some(format!("Count {}", count));
+          None
+        }
+        (None, event::KeyCode::Char('a')) => {
+          ctx.pending_key = Some('a');
+          ctx.message = Some(String::from("Accept all: [L] local [R] incoming"));
+          None
+        }
+        (None, event::KeyCode::Char('z')) => {
+          ctx.pending_key = Some('z');
+          ctx.message = Some(String::from(
+            "Fold: [A] toggle under cursor [C] collapse unchanged lines [W] wrap lines [G] gutter [I] ignore whitespace | View: [Z] center [T] top [B] bottom",
+          ));
+          None
+        }
+        (None, code) => match code {
+          event::KeyCode::Char('q') => Some(Action::Quit),
+          event::KeyCode::Char('Q') => Some(Action::QuitAll),
+          event::KeyCode::Char('l') => Some(Action::AcceptLocal),
+          event::KeyCode::Char('r') => Some(Action::AcceptIncoming),
+          event::KeyCode::Char('L') => Some(Action::AcceptLocalConflict),
+          event::KeyCode::Char('R') => Some(Action::AcceptIncomingConflict),
+          event::KeyCode::Char('w') => Some(Action::Write),
+          event::KeyCode::Char('W') => Some(Action::WriteAll),
+          event::KeyCode::Char('S') => Some(Action::Summary),
+          event::KeyCode::Char('D') => Some(Action::Diffstat),
+          event::KeyCode::Char('p') => Some(Action::Picker),
+          event::KeyCode::Char('[') => Some(Action::PreviousFile),
+          event::KeyCode::Char(']') => Some(Action::NextFile),
+          event::KeyCode::Down | event::KeyCode::Char('j') => Some(Action::MoveDown),
+          event::KeyCode::Up | event::KeyCode::Char('k') => Some(Action::MoveUp),
+          event::KeyCode::Left if event.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            Some(Action::ShrinkPane)
+          }
+          event::KeyCode::Right if event.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            Some(Action::GrowPane)
+          }
+          event::KeyCode::Left => Some(Action::FocusLeft),
+          event::KeyCode::Right => Some(Action::FocusRight),
+          event::KeyCode::Char('<') => Some(Action::ShrinkPane),
+          event::KeyCode::Char('>') => Some(Action::GrowPane),
+          event::KeyCode::Char('m') => Some(Action::Zoom),
+          event::KeyCode::PageDown => Some(Action::PageDown),
+          event::KeyCode::PageUp => Some(Action::PageUp),
+          event::KeyCode::Home | event::KeyCode::Char('g') => Some(Action::MoveFirst),
+          event::KeyCode::End | event::KeyCode::Char('G') => Some(Action::MoveLast),
+          event::KeyCode::Char('v') => Some(Action::Select),
+          event::KeyCode::Char('y') => Some(Action::Yank),
+          event::KeyCode::Char('Y') => Some(Action::YankPermalink),
+          event::KeyCode::Char('P') => Some(Action::Paste),
+          event::KeyCode::Char('f') => Some(Action::ResolveRemaining),
+          event::KeyCode::Char('x') | event::KeyCode::Backspace => Some(Action::Unresolve),
+          event::KeyCode::Tab => Some(Action::Cycle),
+          event::KeyCode::Char('t') => Some(Action::MergeTool),
+          event::KeyCode::Char('d') => Some(Action::DiffTool),
+          event::KeyCode::Char('i') => Some(Action::MergeImports),
+          event::KeyCode::Char('/') => Some(Action::Search),
+          event::KeyCode::Char(':') => Some(Action::GotoLine),
+          event::KeyCode::Char('o') => Some(Action::ToggleRaw),
+          event::KeyCode::Char('u') => Some(Action::ToggleUnified),
+          event::KeyCode::Char('s') => Some(Action::ToggleResult),
+          event::KeyCode::Char('n') => Some(Action::NextMatch),
+          event::KeyCode::Char('N') => Some(Action::PreviousMatch),
+          event::KeyCode::Char('.') => {
+            if ctx.last_resolution.is_none() {
+              ctx.message = Some(String::from("Nothing to repeat yet"));
+            }
+            ctx.last_resolution
+          }
+          _ => None,
+        },
+      };
+
+      // an unbound key only needs a frame to clear the message
+      changed = action.is_some() || had_message || ctx.message.is_some();
+      if let (Some(count), Some(action)) = (count, action) {
+        times = if repeatable(action) { count } else { 1 };
+      }
+      action
+    }
+
+    event::Event::Mouse(event) => match event.kind {
+      event::MouseEventKind::Down(event::MouseButton::Left) => {
+        let action = control_at(event.column, event.row, ctx);
+        if action.is_none() {
+          click(event.column, event.row, ctx);
+        }
+        action
+      }
+      event::MouseEventKind::ScrollDown => {
+        scroll_down(ctx, 3);
+        None
+      }
+      event::MouseEventKind::ScrollUp => {
+        scroll_up(ctx, 3);
+        None
+      }
+      _ => {
+        changed = false;
+        None
+      }
+    },
+
+    event::Event::Resize(_, _) => None,
+  };
+
+  session.dirty |= changed;
+
+  match action {
+    Some(action) => (0..times).all(|_| perform(action, session)),
+    None => true,
+  }
+}
+
+// actions a count repeats, the others run once whatever was typed before them
+fn repeatable(action: Action) -> bool {
+  matches!(
+    action,
+    Action::MoveUp
+      | Action::MoveDown
+      | Action::PageUp
+      | Action::PageDown
+      | Action::NextMatch
+      | Action::PreviousMatch
+      | Action::PreviousFile
+      | Action::NextFile
+      | Action::GrowPane
+      | Action::ShrinkPane
+  )
+}
+
+// a character typed with Ctrl or Alt held is a chord, never the plain key
+fn chorded(modifiers: event::KeyModifiers) -> bool {
+  modifiers.intersects(event::KeyModifiers::CONTROL | event::KeyModifiers::ALT)
+}
+
+fn chord(modifiers: event::KeyModifiers, c: char) -> Option<Action> {
+  match (modifiers.contains(event::KeyModifiers::CONTROL), c) {
+    (true, 's') => Some(Action::Write),
+    (true, 'z') => Some(Action::Unresolve),
+    _ => None,
+  }
+}
+
+// run an action triggered by a key or a mouse click, returns false when the program should exit
+fn perform(action: Action, session: &mut Session) -> bool {
+  // conflict under the cursor while it is unresolved, to notice when the action resolves it
+  let before = (
+    session.current,
+    session.files.len(),
+    unresolved_at(&session.files[session.current]),
+  );
+  let ctx = &mut session.files[session.current];
+
+  if matches!(
+    action,
+    Action::AcceptLocal
+      | Action::AcceptIncoming
+      | Action::AcceptLocalConflict
+      | Action::AcceptIncomingConflict
+      | Action::Unresolve
+      | Action::MergeImports
+  ) {
+    ctx.last_resolution = Some(action);
+  }
+
+  match action {
+    Action::MoveUp => move_up(ctx),
+    Action::MoveDown => move_down(ctx),
+    Action::PageUp => page_up(ctx),
+    Action::PageDown => page_down(ctx),
+    Action::MoveFirst => move_to(0, ctx),
+    Action::MoveLast => move_to(ctx.result.len().saturating_sub(1), ctx),
+    Action::AcceptLocal => process_change(Column::Left, ctx),
+    Action::AcceptIncoming => process_change(Column::Right, ctx),
+    Action::AcceptLocalConflict => process_conflict(Column::Left, ctx),
+    Action::AcceptIncomingConflict => process_conflict(Column::Right, ctx),
+    Action::Unresolve => unresolve_line(ctx.current_line, ctx),
+    Action::Cycle => cycle_line(ctx),
+    Action::Search => {
+      ctx.prompt = Some(Prompt {
+        kind: '/',
+        input: String::new(),
+      })
+    }
+    Action::GotoLine => {
+      ctx.prompt = Some(Prompt {
+        kind: ':',
+        input: String::new(),
+      })
+    }
+    Action::ToggleRaw => ctx.view = toggle_view(ctx.view, View::Raw),
+    Action::ToggleUnified => ctx.view = toggle_view(ctx.view, View::Unified),
+    Action::ToggleResult => ctx.show_result = !ctx.show_result,
+    Action::Zoom => ctx.zoom = !ctx.zoom,
+    Action::ToggleWrap => ctx.wrap = !ctx.wrap,
+    Action::ToggleGutter => ctx.gutter = !ctx.gutter,
+    Action::ToggleIgnoreWhitespace => {
+      ctx.ignore_whitespace = !ctx.ignore_whitespace;
+      ctx.message = Some(String::from(match ctx.ignore_whitespace {
+        true => "Ignoring whitespace differences",
+        false => "Showing whitespace differences",
+      }));
+    }
+    Action::FocusLeft => ctx.focus = ctx.focus.saturating_sub(1),
+    Action::FocusRight => ctx.focus = (ctx.focus + 1).min(2),
+    Action::GrowPane | Action::ShrinkPane => {
+      let delta = if action == Action::GrowPane { 5 } else { -5 };
+      ctx.ratios = resize_pane(ctx.ratios, ctx.focus, delta);
+      session.pane_ratios = Some(ctx.ratios);
+    }
+    Action::NextMatch => search_next(ctx, true),
+    Action::PreviousMatch => search_next(ctx, false),
+    Action::MergeTool => {
+      run_merge_tool(ctx);
+      session.clear = true;
+    }
+    Action::DiffTool => {
+      run_diff_tool(ctx);
+      session.clear = true;
+    }
+    Action::MergeImports => merge_imports(ctx),
+    Action::AcceptLocalAll => process_all(Column::Left, ctx),
+    Action::AcceptIncomingAll => process_all(Column::Right, ctx),
+    Action::ToggleFold => toggle_fold(ctx),
+    Action::CollapseContext => {
+      ctx.collapse_context = !ctx.collapse_context;
+      ctx.expanded.clear();
+    }
+    Action::CenterView => align_view(ctx, ctx.column_height / 2),
+    Action::ViewTop => align_view(ctx, 0),
+    Action::ViewBottom => align_view(ctx, ctx.column_height.saturating_sub(1)),
+    Action::ResolveRemaining => {
+      ctx.popup = Some(Popup {
+        title: String::from("Resolve remaining"),
+        lines: vec![Spans::from(format!(
+          "Resolve {} unresolved conflict(s) with:",
+          unresolved_conflicts(ctx)
+        ))],
+        choices: vec![
+          ('o', "Ours", |ctx| resolve_remaining(Strategy::Ours, ctx)),
+          ('t', "Theirs", |ctx| {
+            resolve_remaining(Strategy::Theirs, ctx)
+          }),
+          ('u', "Union", |ctx| resolve_remaining(Strategy::Union, ctx)),
+          ('n', "Cancel", |_| ()),
+        ],
+      })
+    }
+    Action::Select => {
+      ctx.selection = match ctx.selection {
+        Some(_) => None,
+        None => Some(ctx.current_line),
+      }
+    }
+    Action::Yank => yank(ctx),
+    Action::YankPermalink => yank_permalink(ctx),
+    Action::Paste => paste(ctx),
+    Action::Write => match line_count_warning(ctx) {
+      Some(lines) => {
+        ctx.popup = Some(Popup {
+          title: String::from("Suspicious line count"),
+          lines: lines.into_iter().map(Spans::from).collect(),
+          choices: vec![('y', "Write anyway", write_file), ('n', "Cancel", |_| ())],
+        })
+      }
+      None => write_file(ctx),
+    },
+    Action::WriteAll => {
+      let mut skipped = vec![];
+
+      for ctx in session.files.iter_mut().filter(|ctx| ctx.modified) {
+        match line_count_warning(ctx) {
+          Some(_) => skipped.push(ctx.file_name.clone()),
+          None => write_file(ctx),
+        }
+      }
+
+      if !skipped.is_empty() {
+        session.files[session.current].message = Some(format!(
+          "Not written, line count needs a check: {}",
+          skipped.join(", ")
+        ));
+      }
+    }
+    Action::Summary => {
+      let lines = summary_lines(&session.files);
+
+      session.files[session.current].popup = Some(Popup {
+        title: String::from("Files by conflict density"),
+        lines,
+        choices: vec![('q', "Close", |_| ())],
+      })
+    }
+    Action::Diffstat => {
+      ctx.popup = Some(Popup {
+        title: format!("Diffstat of {}", ctx.file_name),
+        lines: diffstat_lines(ctx),
+        choices: vec![('D', "Close", |_| ())],
+      })
+    }
+    Action::PreviousFile => {
+      session.current = (session.current + session.files.len() - 1) % session.files.len();
+    }
+    Action::NextFile => session.current = (session.current + 1) % session.files.len(),
+    Action::OpenFile(i) => session.current = i.min(session.files.len() - 1),
+    Action::JumpTo(line) => {
+      move_to(line, ctx);
+      align_view(ctx, ctx.column_height / 2);
+    }
+    Action::Picker => session.picker = Some(session.current),
+    Action::Quit if session.files.len() == 1 => session.review = Some(0),
+    // the last file has the review screen, any other asks before its changes are left unwritten
+    Action::Quit
+      if session.files[session.current].modified && !session.files[session.current].close =>
+    {
+      let ctx = &mut session.files[session.current];
+      ctx.popup = Some(Popup {
+        title: String::from("Not written"),
+        lines: vec![
+          Spans::from(format!(
+            "{} has changes that are not written, close it anyway?",
+            ctx.file_name
+          )),
+          Spans::from("They are kept for the next time it is opened."),
+        ],
+        choices: vec![
+          ('c', "Close", |ctx| ctx.close = true),
+          ('n', "Cancel", |_| ()),
+        ],
+      })
+    }
+    Action::Quit => {
+      // close the current file, the last one goes through the review screen
+      let _ = keep_state(&session.files[session.current]);
+      let ctx = session.files.remove(session.current);
+      session
+        .touched
+        .push(format!("{}; closed", review_line(&ctx)));
+      session.current = session.current.min(session.files.len() - 1);
+    }
+    Action::QuitAll => session.review = Some(session.current),
+  };
+
+  if let (file, count, Some(c)) = before {
+    if (file, count) == (session.current, session.files.len()) {
+      offer_similar(c, &mut session.files[file]);
+    }
+  }
+
+  true
+}
+
+// index of the conflict under the cursor when it is not resolved yet
+fn unresolved_at(ctx: &Context) -> Option<usize> {
+  ctx
+    .conflicts
+    .iter()
+    .position(|conflict| ctx.current_line >= conflict.start && ctx.current_line < conflict.end)
+    .filter(|c| is_unresolved(ctx, &ctx.conflicts[*c]))
+}
+
+fn is_unresolved(ctx: &Context, conflict: &Conflict) -> bool {
+  (conflict.start..conflict.end).any(|i| ctx.result[i].change == Change::None)
+}
+
+// unresolved conflicts with the same local and incoming lines as conflict `c`
+fn similar_conflicts(ctx: &Context, c: usize) -> Vec<usize> {
+  let sides = |conflict: &Conflict| {
+    (
+      side_lines(&ctx.local_changes[conflict.start..conflict.end]),
+      side_lines(&ctx.incoming_changes[conflict.start..conflict.end]),
+    )
+  };
+  let source = sides(&ctx.conflicts[c]);
+
+  (0..ctx.conflicts.len())
+    .filter(|other| *other != c)
+    .filter(|other| is_unresolved(ctx, &ctx.conflicts[*other]))
+    .filter(|other| sides(&ctx.conflicts[*other]) == source)
+    .collect()
+}
+
+// conflict `c` was just resolved, ask whether the conflicts looking the same go the same way
+fn offer_similar(c: usize, ctx: &mut Context) {
+  if ctx.popup.is_some() || is_unresolved(ctx, &ctx.conflicts[c]) {
+    return;
+  }
+
+  let similar = similar_conflicts(ctx, c).len();
+  if similar == 0 {
+    return;
+  }
+
+  ctx.similar_to = Some(c);
+  ctx.popup = Some(Popup {
+    title: String::from("Similar conflicts"),
+    lines: vec![Spans::from(format!(
+      "Apply to {} similar conflict(s)?",
+      similar
+    ))],
+    choices: vec![
+      ('y', "Apply", apply_to_similar),
+      ('n', "No", |ctx| ctx.similar_to = None),
+    ],
+  });
+}
+
+// resolve every conflict with the same sides like the one in `similar_to`
+fn apply_to_similar(ctx: &mut Context) {
+  let c = match ctx.similar_to.take() {
+    Some(c) => c,
+    None => return,
+  };
+  let Conflict { start, end, .. } = ctx.conflicts[c];
+  let rows: Vec<(text::Text, bool)> = ctx.result[start..end]
+    .iter()
+    .map(|line| (line.value.clone(), line.change == Change::Deletion))
+    .collect();
+  let similar = similar_conflicts(ctx, c);
+
+  for other in &similar {
+    let Conflict { start, end, .. } = ctx.conflicts[*other];
+
+    // lines added to the resolved one do not exist in the others, those get the text instead
+    if end - start != rows.len() {
+      let lines = rows
+        .iter()
+        .filter(|(_, deleted)| !deleted)
+        .map(|(value, _)| value.to_string())
+        .collect();
+      replace_conflict(*other, lines, ctx);
+      continue;
+    }
+
+    for (line, (value, deleted)) in ctx.result[start..end].iter_mut().zip(&rows) {
+      *line = Line {
+        value: value.clone(),
+        change: if *deleted {
+          Change::Deletion
+        } else {
+          Change::Addition
+        },
+      };
+    }
+  }
+
+  ctx.modified = true;
+  ctx.message = Some(format!("Applied to {} similar conflict(s)", similar.len()));
+}
+
+// the action just taken resolved the last conflict of the file, write it and move on to
+// the next file that still has conflicts
+fn autowrite(session: &mut Session, file_name: &str, unresolved: usize) {
+  let current = session.current;
+  let ctx = &mut session.files[current];
+
+  if !session.autowrite
+    || ctx.file_name != file_name
+    || unresolved == 0
+    || unresolved_conflicts(ctx) > 0
+  {
+    return;
+  }
+
+  if line_count_warning(ctx).is_some() {
+    ctx.message = Some(String::from(
+      "All conflicts resolved, line count needs a check before writing",
+    ));
+    return;
+  }
+
+  write_file(ctx);
+
+  if ctx.modified {
+    return;
+  }
+
+  let staged = stage_written(session.git.as_ref(), session.autostage, ctx);
+  let message = format!("Wrote {}{}", destination(ctx), staged);
+
+  let count = session.files.len();
+  if let Some(next) = (1..count)
+    .map(|i| (current + i) % count)
+    .find(|&i| unresolved_conflicts(&session.files[i]) > 0)
+  {
+    session.current = next;
+  }
+
+  session.files[session.current].message = Some(message);
+}
+
+fn control_at(x: u16, y: u16, ctx: &Context) -> Option<Action> {
+  ctx
+    .control_rects
+    .iter()
+    .find(|(rect, _)| x >= rect.x && x < rect.right() && y >= rect.y && y < rect.bottom())
+    .map(|(_, action)| *action)
+}
+
+fn process_change(column: Column, ctx: &mut Context) {
+  resolve_line(&column, ctx.current_line, ctx);
+}
+
+// apply the change on every line of the conflict under the cursor
+fn process_conflict(column: Column, ctx: &mut Context) {
+  if let Some(conflict) = current_conflict(ctx) {
+    for i in conflict.start..conflict.end {
+      resolve_line(&column, i, ctx);
+    }
+  }
+}
+
+fn process_all(column: Column, ctx: &mut Context) {
+  for c in 0..ctx.conflicts.len() {
+    for i in ctx.conflicts[c].start..ctx.conflicts[c].end {
+      resolve_line(&column, i, ctx);
+    }
+  }
+}
+
+// resolve every conflict line still showing the placeholder, decisions already made are kept
+fn resolve_remaining(strategy: Strategy, ctx: &mut Context) {
+  for c in 0..ctx.conflicts.len() {
+    for i in ctx.conflicts[c].start..ctx.conflicts[c].end {
+      if ctx.result[i].change == Change::None {
+        resolve_line(&strategy_column(strategy, i, ctx), i, ctx);
+      }
+    }
+  }
+}
+
+// resolve conflicts whose sides differ only in whitespace, returns how many
+fn resolve_whitespace(preference: config::Whitespace, ctx: &mut Context) -> usize {
+  let mut resolved = 0;
+
+  for c in 0..ctx.conflicts.len() {
+    let range = ctx.conflicts[c].start..ctx.conflicts[c].end;
+    let local = side_lines(&ctx.local_changes[range.clone()]);
+    let incoming = side_lines(&ctx.incoming_changes[range.clone()]);
+
+    if local == incoming || !same_words(&local, &incoming) {
+      continue;
+    }
+
+    let column = match preference {
+      config::Whitespace::Ours => Column::Left,
+      config::Whitespace::Theirs => Column::Right,
+      // ours unless only theirs is indented like the surrounding lines
+      config::Whitespace::Context
+        if indent_char(&incoming) == context_indent(ctx, range.clone())
+          && indent_char(&local) != indent_char(&incoming) =>
+      {
+        Column::Right
+      }
+      config::Whitespace::Context => Column::Left,
+    };
+
+    for i in range {
+      resolve_line(&column, i, ctx);
+    }
+    resolved += 1;
+  }
+
+  resolved
+}
+
+// resolve conflicts whose region in `reference` is local, incoming or both in either order,
+// found by the unchanged lines around the conflict; returns how many
+fn resolve_from_reference(reference: &[String], ctx: &mut Context) -> usize {
+  let mut resolved = 0;
+
+  for c in 0..ctx.conflicts.len() {
+    if !is_unresolved(ctx, &ctx.conflicts[c]) {
+      continue;
+    }
+
+    let Conflict { start, end, .. } = ctx.conflicts[c];
+    let local: Vec<String> = side_lines(&ctx.local_changes[start..end])
+      .into_iter()
+      .map(String::from)
+      .collect();
+    let incoming: Vec<String> = side_lines(&ctx.incoming_changes[start..end])
+      .into_iter()
+      .map(String::from)
+      .collect();
+    let (before, after) = reference_context(ctx, start, end);
+
+    let candidates = [
+      local.clone(),
+      incoming.clone(),
+      [local.as_slice(), &incoming].concat(),
+      [incoming.as_slice(), &local].concat(),
+    ];
+    // candidates found exactly once, it is only unambiguous when they all spell the same lines
+    let found: Vec<usize> = (0..candidates.len())
+      .filter(|i| occurrences(reference, &[&before[..], &candidates[*i], &after].concat()) == 1)
+      .collect();
+
+    if found.is_empty() || found.iter().any(|i| candidates[*i] != candidates[found[0]]) {
+      continue;
+    }
+
+    match found[0] {
+      0 => (start..end).for_each(|i| resolve_line(&Column::Left, i, ctx)),
+      1 => (start..end).for_each(|i| resolve_line(&Column::Right, i, ctx)),
+      2 => {
+        (start..end).for_each(|i| resolve_line(&strategy_column(Strategy::Union, i, ctx), i, ctx))
+      }
+      _ => replace_conflict(c, candidates[3].clone(), ctx),
+    }
+    resolved += 1;
+  }
+
+  resolved
+}
+
+// up to three unchanged lines right before and after the conflict
+fn reference_context(ctx: &Context, start: usize, end: usize) -> (Vec<String>, Vec<String>) {
+  let unchanged = |i: &usize| {
+    ctx.local_changes[*i].change == Change::None
+      && ctx.incoming_changes[*i].change == Change::None
+      && ctx.result[*i].change == Change::None
+  };
+  let mut before: Vec<String> = (0..start)
+    .rev()
+    .take_while(unchanged)
+    .take(3)
+    .map(|i| ctx.result[i].value.to_string())
+    .collect();
+  before.reverse();
+  let after = (end..ctx.result.len())
+    .take_while(unchanged)
+    .take(3)
+    .map(|i| ctx.result[i].value.to_string())
+    .collect();
+
+  (before, after)
+}
+
+// places `needle` starts at in `lines`, nothing counts for an empty needle
+fn occurrences(lines: &[String], needle: &[String]) -> usize {
+  match needle.is_empty() {
+    true => 0,
+    false => lines
+      .windows(needle.len())
+      .filter(|window| *window == needle)
+      .count(),
+  }
+}
+
+// both sides of the conflict add the same lines, whitespace aside when it is ignored
+fn identical_sides(ctx: &Context, conflict: &Conflict) -> bool {
+  let local = side_lines(&ctx.local_changes[conflict.start..conflict.end]);
+  let incoming = side_lines(&ctx.incoming_changes[conflict.start..conflict.end]);
+
+  match ctx.ignore_whitespace {
+    true => same_words(&local, &incoming),
+    false => local == incoming,
+  }
+}
+
+// text of the lines one side of a conflict adds
+fn side_lines(lines: &[Line]) -> Vec<&str> {
+  lines
+    .iter()
+    .filter(|line| line.change == Change::Addition)
+    .map(|line| line.value.as_str())
+    .collect()
+}
+
+fn same_words(a: &[&str], b: &[&str]) -> bool {
+  a.len() == b.len()
+    && a
+      .iter()
+      .zip(b)
+      .all(|(a, b)| a.split_whitespace().eq(b.split_whitespace()))
+}
+
+// tab or space the first indented line starts with
+fn indent_char(lines: &[&str]) -> Option<char> {
+  lines
+    .iter()
+    .find_map(|line| line.chars().next().filter(|c| *c == ' ' || *c == '\t'))
+}
+
+// indentation used by the unconflicted lines just around a conflict
+fn context_indent(ctx: &Context, range: std::ops::Range<usize>) -> Option<char> {
+  let after = (range.end..ctx.result.len().min(range.end + 3)).collect::<Vec<_>>();
+  let before = (range.start.saturating_sub(3)..range.start)
+    .rev()
+    .collect::<Vec<_>>();
+  let lines: Vec<&str> = after
+    .into_iter()
+    .chain(before)
+    .filter(|i| {
+      ctx.result[*i].change == Change::None && ctx.local_changes[*i].change == Change::None
+    })
+    .map(|i| ctx.result[i].value.as_str())
+    .collect();
+
+  indent_char(&lines)
+}
+
+fn strategy_column(strategy: Strategy, i: usize, ctx: &Context) -> Column {
+  match strategy {
+    Strategy::Ours => Column::Left,
+    Strategy::Theirs => Column::Right,
+    Strategy::Union if ctx.local_changes[i].change == Change::Addition => Column::Left,
+    Strategy::Union => Column::Right,
+  }
+}
+
+// preview the next candidate resolution of the current line in place
+fn cycle_line(ctx: &mut Context) {
+  let i = ctx.current_line;
+
+  if ctx.local_changes[i].change == Change::None && ctx.incoming_changes[i].change == Change::None {
+    return;
+  }
+
+  let next = match ctx.cycle {
+    Some((line, index)) if line == i => (index + 1) % CYCLE.len(),
+    _ => 0,
+  };
+
+  let (strategy, name) = CYCLE[next];
+
+  match strategy {
+    Some(strategy) => resolve_line(&strategy_column(strategy, i, ctx), i, ctx),
+    None => unresolve_line(i, ctx),
+  };
+
+  ctx.cycle = Some((i, next));
+  ctx.message = Some(format!("Taking {}", name));
+}
+
+fn resolve_line(column: &Column, i: usize, ctx: &mut Context) {
+  let line: &Line = match column {
+    Column::Left => &ctx.local_changes[i],
+    Column::Right => &ctx.incoming_changes[i],
+    Column::Middle => return,
+  };
+
+  match line.change {
+    Change::Addition => {
+      ctx.result[i].value = line.value.clone();
+      ctx.result[i].change = Change::Addition;
+      ctx.modified = true;
+    }
+    Change::Deletion => {
+      ctx.result[i].change = Change::Deletion;
+      ctx.modified = true;
+    }
+    Change::None => (),
+  };
+}
+
+fn submit_prompt(prompt: Prompt, ctx: &mut Context) {
+  match prompt.kind {
+    '/' => {
+      // an empty search repeats the last one
+      if !prompt.input.is_empty() {
+        ctx.search = Some(prompt.input);
+      }
+      search_next(ctx, true);
+    }
+    ':' => goto_line(&prompt.input, ctx),
+    _ => (),
+  }
+}
+
+// line numbers start at 1 like in compiler errors
+fn goto_line(input: &str, ctx: &mut Context) {
+  match input.trim().parse::<usize>() {
+    Ok(line) => move_to(line.saturating_sub(1), ctx),
+    Err(_) => ctx.message = Some(format!("Not a line number: {}", input)),
+  }
+}
+
+fn search_overlays(
+  string: &str,
+  ctx: &Context,
+  style: Style,
+) -> Vec<(std::ops::Range<usize>, Style)> {
+  match &ctx.search {
+    Some(query) => matches(string, query)
+      .into_iter()
+      .map(|range| (range, style))
+      .collect(),
+    None => vec![],
+  }
+}
+
+// move to the next line (or previous one) with a match in any of the three buffers, wrapping around
+fn search_next(ctx: &mut Context, forward: bool) {
+  let query = match &ctx.search {
+    Some(query) => query.clone(),
+    None => return next_conflict(ctx, forward),
+  };
+
+  let len = ctx.result.len();
+  let found = (1..=len)
+    .map(|step| {
+      if forward {
+        (ctx.current_line + step) % len
+      } else {
+        (ctx.current_line + len - step % len) % len
+      }
+    })
+    .find(|i| {
+      [
+        &ctx.local_changes[*i],
+        &ctx.result[*i],
+        &ctx.incoming_changes[*i],
+      ]
+      .iter()
+      .any(|line| !matches(&line.value, &query).is_empty())
+    });
+
+  match found {
+    Some(i) => move_to(i, ctx),
+    None => ctx.message = Some(format!("Pattern not found: {}", query)),
+  }
+}
+
+// move to the start of the next (or previous) unresolved conflict, wrapping around
+fn next_conflict(ctx: &mut Context, forward: bool) {
+  let current = ctx.current_line;
+  let unresolved: Vec<usize> = ctx
+    .conflicts
+    .iter()
+    .filter(|conflict| is_unresolved(ctx, conflict))
+    .map(|conflict| conflict.start)
+    .collect();
+  let found = match forward {
+    true => unresolved
+      .iter()
+      .find(|start| **start > current)
+      .or(unresolved.first()),
+    false => unresolved
+      .iter()
+      .rev()
+      .find(|start| **start < current)
+      .or(unresolved.last()),
+  };
+
+  match found {
+    Some(start) => move_to(*start, ctx),
+    None => ctx.message = Some(String::from("No unresolved conflicts left")),
+  }
+}
+
+// hand the conflict under the cursor to the configured merge tool
+fn run_merge_tool(ctx: &mut Context) {
+  let command = match &ctx.merge_tool {
+    Some(command) => command.clone(),
+    None => {
+      ctx.message = Some(String::from("No merge_tool configured"));
+      return;
+    }
+  };
+
+  let c = match ctx
+    .conflicts
+    .iter()
+    .position(|conflict| (conflict.start..conflict.end).contains(&ctx.current_line))
+  {
+    Some(c) => c,
+    None => {
+      ctx.message = Some(String::from("Not in a conflict"));
+      return;
+    }
+  };
+
+  let conflict = &ctx.conflicts[c];
+  let side = |lines: &Vec<Line>| -> Vec<String> {
+    lines[conflict.start..conflict.end]
+      .iter()
+      .filter(|line| line.change == Change::Addition)
+      .map(|line| line.value.to_string())
+      .collect()
+  };
+
+  let local = side(&ctx.local_changes);
+  let incoming = side(&ctx.incoming_changes);
+  let base = conflict.base.clone().unwrap_or_default();
+
+  ctx.message = Some(
+    match tool::suspend(|| {
+      tool::merge(
+        &command,
+        &ctx.file_name,
+        ctx.marker_size,
+        &local,
+        &base,
+        &incoming,
+      )
+    }) {
+      Ok(lines) => {
+        let count = lines.len();
+        replace_conflict(c, lines, ctx);
+        format!("Merge tool resolved the conflict with {} line(s)", count)
+      }
+      Err(err) => format!("Merge tool failed: {}", err),
+    },
+  );
+}
+
+fn run_diff_tool(ctx: &mut Context) {
+  let command = match &ctx.diff_tool {
+    Some(command) => command.clone(),
+    None => {
+      ctx.message = Some(String::from("No diff_tool configured"));
+      return;
+    }
+  };
+
+  let conflict = match current_conflict(ctx) {
+    Some(conflict) => conflict,
+    None => {
+      ctx.message = Some(String::from("Not in a conflict"));
+      return;
+    }
+  };
+
+  let side = |lines: &Vec<Line>| -> Vec<String> {
+    side_lines(&lines[conflict.start..conflict.end])
+      .into_iter()
+      .map(String::from)
+      .collect()
+  };
+  let local = side(&ctx.local_changes);
+  let incoming = side(&ctx.incoming_changes);
+
+  if let Err(err) = tool::suspend(|| tool::diff(&command, &ctx.file_name, &local, &incoming)) {
+    ctx.message = Some(format!("Diff tool failed: {}", err));
+  }
+}
+
+fn merge_imports(ctx: &mut Context) {
+  let c = match ctx
+    .conflicts
+    .iter()
+    .position(|conflict| (conflict.start..conflict.end).contains(&ctx.current_line))
+  {
+    Some(c) => c,
+    None => {
+      ctx.message = Some(String::from("Not in a conflict"));
+      return;
+    }
+  };
+
+  ctx.message = Some(match merged_imports(ctx, &ctx.conflicts[c]) {
+    Some(lines) => {
+      let count = lines.len();
+      replace_conflict(c, lines, ctx);
+      format!("Merged {} import(s)", count)
+    }
+    None => String::from("The conflict has lines other than imports"),
+  });
+}
+
+// both sides only add import statements, all of them once in sorted order
+fn merged_imports(ctx: &Context, conflict: &Conflict) -> Option<Vec<String>> {
+  let local = side_lines(&ctx.local_changes[conflict.start..conflict.end]);
+  let incoming = side_lines(&ctx.incoming_changes[conflict.start..conflict.end]);
+  let mut lines: Vec<&str> = local
+    .into_iter()
+    .chain(incoming)
+    .filter(|line| !line.trim().is_empty())
+    .collect();
+
+  if lines.is_empty() || !lines.iter().all(|line| is_import(line)) {
+    return None;
+  }
+
+  lines.sort();
+  lines.dedup();
+  Some(lines.into_iter().map(String::from).collect())
+}
+
+// a whole import on one line, statements spanning lines are not sorted
+fn is_import(line: &str) -> bool {
+  let line = line.trim();
+  let prefixes = [
+    "use ",
+    "pub use ",
+    "pub(crate) use ",
+    "extern crate ",
+    "import ",
+    "from ",
+    "#include ",
+    "using ",
+    "require ",
+  ];
+
+  prefixes.iter().any(|prefix| line.starts_with(prefix)) && !line.ends_with(['{', '(', ','])
+}
+
+// drop everything the conflict resolved to so far and use `lines` instead
+fn replace_conflict(c: usize, lines: Vec<String>, ctx: &mut Context) {
+  let Conflict { start, end, .. } = ctx.conflicts[c];
+
+  for line in &mut ctx.result[start..end] {
+    line.change = Change::Deletion;
+  }
+
+  insert_lines(ctx, end, lines);
+  ctx.modified = true;
+}
+
+// put the conflict placeholder back so the line counts as unresolved again
+fn unresolve_line(i: usize, ctx: &mut Context) {
+  let is_conflict_line =
+    ctx.local_changes[i].change != Change::None || ctx.incoming_changes[i].change != Change::None;
+
+  if is_conflict_line && ctx.result[i].change != Change::None {
+    ctx.result[i] = Line {
+      value: text::Text::from("#"),
+      change: Change::None,
+    };
+    ctx.modified = true;
+  }
+}
+
+// conflict lines still showing the placeholder were not taken from either side
+fn unresolved_conflicts(ctx: &Context) -> usize {
+  ctx
+    .conflicts
+    .iter()
+    .filter(|conflict| is_unresolved(ctx, conflict))
+    .count()
+}
+
+// middle pane title with the position among conflicts and how many are left
+fn result_title(ctx: &Context) -> String {
+  let count = ctx.conflicts.len();
+
+  if count == 0 {
+    return String::from("Result");
+  }
+
+  let unresolved = unresolved_conflicts(ctx);
+  let position = ctx
+    .conflicts
+    .iter()
+    .position(|conflict| ctx.current_line >= conflict.start && ctx.current_line < conflict.end);
+
+  match position {
+    Some(i) if identical_sides(ctx, &ctx.conflicts[i]) => format!(
+      "Result — conflict {}/{} ({} unresolved), sides identical",
+      i + 1,
+      count,
+      unresolved
+    ),
+    Some(i)
+      if (ctx.conflicts[i].start..ctx.conflicts[i].end)
+        .any(|line| ctx.result[line].change == Change::None)
+        && merged_imports(ctx, &ctx.conflicts[i]).is_some() =>
+    {
+      format!(
+        "Result — conflict {}/{} ({} unresolved), imports, [I] to merge",
+        i + 1,
+        count,
+        unresolved
+      )
+    }
+    Some(i) => format!(
+      "Result — conflict {}/{} ({} unresolved)",
+      i + 1,
+      count,
+      unresolved
+    ),
+    None => format!("Result — {} conflict(s) ({} unresolved)", count, unresolved),
+  }
+}
+
+// lines of the resolved conflict or unchanged stretch containing `i` when it is shown as a
+// single summary row
+fn fold_at(ctx: &Context, i: usize) -> Option<std::ops::Range<usize>> {
+  fold_among(ctx, i, &context_regions(ctx))
+}
+
+// fold at line `i` with the context regions worked out once for all the lines on screen
+fn fold_among(
+  ctx: &Context,
+  i: usize,
+  regions: &[std::ops::Range<usize>],
+) -> Option<std::ops::Range<usize>> {
+  let conflict = conflict_index(ctx, i)
+    .map(|c| &ctx.conflicts[c])
+    .filter(|conflict| conflict.toggled != ctx.fold_resolved)
+    .filter(|conflict| (conflict.start..conflict.end).all(|i| ctx.result[i].change != Change::None))
+    .map(|conflict| conflict.start..conflict.end);
+
+  if conflict.is_some() || !ctx.collapse_context {
+    return conflict;
+  }
+
+  regions
+    .get(regions.partition_point(|region| region.end <= i))
+    .filter(|region| region.contains(&i) && !ctx.expanded.contains(&region.start))
+    .cloned()
+}
+
+// conflict containing line `i`, conflicts are kept in order and never overlap
+fn conflict_index(ctx: &Context, i: usize) -> Option<usize> {
+  let c = ctx.conflicts.partition_point(|conflict| conflict.end <= i);
+
+  ctx
+    .conflicts
+    .get(c)
+    .filter(|conflict| conflict.start <= i)
+    .map(|_| c)
+}
+
+// unchanged lines further than `context_lines` away from any conflict
+fn context_regions(ctx: &Context) -> Vec<std::ops::Range<usize>> {
+  if ctx.conflicts.is_empty() {
+    return vec![];
+  }
+
+  let n = ctx.context_lines;
+  let mut regions = vec![];
+  let mut start = 0;
+
+  for (c, conflict) in ctx.conflicts.iter().enumerate() {
+    let from = if c == 0 { start } else { start + n };
+    let to = conflict.start.saturating_sub(n);
+
+    // hiding a single line behind a separator saves nothing
+    if to > from + 1 {
+      regions.push(from..to);
+    }
+
+    start = conflict.end;
+  }
+
+  if ctx.result.len() > start + n + 1 {
+    regions.push(start + n..ctx.result.len());
+  }
+
+  regions
+}
+
+fn fold_summary(ctx: &Context, fold: std::ops::Range<usize>) -> String {
+  if !ctx
+    .conflicts
+    .iter()
+    .any(|conflict| conflict.start == fold.start)
+  {
+    return format!("… {} unchanged lines …", fold.len());
+  }
+
+  let kept = fold
+    .clone()
+    .filter(|&i| ctx.result[i].change != Change::Deletion)
+    .count();
+  let took = match resolution(ctx, fold) {
+    side @ ("local" | "incoming" | "both") => format!("took {}", side),
+    other => String::from(other),
+  };
+
+  format!("▸ {} line(s), {}", kept, took)
+}
+
+// which side the lines kept from a resolved conflict came from
+fn resolution(ctx: &Context, range: std::ops::Range<usize>) -> &'static str {
+  let kept: Vec<usize> = range
+    .filter(|&i| ctx.result[i].change != Change::Deletion)
+    .collect();
+  let local = kept
+    .iter()
+    .all(|&i| ctx.local_changes[i].change == Change::Addition);
+  let incoming = kept
+    .iter()
+    .all(|&i| ctx.incoming_changes[i].change == Change::Addition);
+  let edited = kept.iter().any(|&i| {
+    ctx.local_changes[i].change != Change::Addition
+      && ctx.incoming_changes[i].change != Change::Addition
+  });
+
+  match (local, incoming, edited) {
+    (_, _, true) => "edited",
+    (true, true, _) => "nothing",
+    (true, false, _) => "local",
+    (false, true, _) => "incoming",
+    (false, false, _) => "both",
+  }
+}
+
+fn toggle_fold(ctx: &mut Context) {
+  let i = ctx.current_line;
+  let region = match ctx.collapse_context {
+    true => context_regions(ctx)
+      .into_iter()
+      .find(|region| region.contains(&i)),
+    false => None,
+  };
+
+  if let Some(region) = region {
+    match ctx.expanded.iter().position(|start| *start == region.start) {
+      Some(position) => {
+        ctx.expanded.remove(position);
+      }
+      None => ctx.expanded.push(region.start),
+    }
+  } else {
+    match ctx
+      .conflicts
+      .iter_mut()
+      .find(|conflict| i >= conflict.start && i < conflict.end)
+    {
+      Some(conflict) => conflict.toggled = !conflict.toggled,
+      None => ctx.message = Some(String::from("Nothing to fold here")),
+    }
+  }
+
+  if let Some(fold) = fold_at(ctx, i) {
+    ctx.current_line = fold.start;
+  }
+}
+
+fn current_conflict(ctx: &Context) -> Option<&Conflict> {
+  ctx
+    .conflicts
+    .iter()
+    .find(|conflict| ctx.current_line >= conflict.start && ctx.current_line < conflict.end)
+}
+
+// lines between the selection anchor and the cursor, or just the cursor line
+fn selected_lines(ctx: &Context) -> std::ops::Range<usize> {
+  match ctx.selection {
+    Some(anchor) => anchor.min(ctx.current_line)..anchor.max(ctx.current_line) + 1,
+    None => ctx.current_line..ctx.current_line + 1,
+  }
+}
+
+fn yank(ctx: &mut Context) {
+  let lines: Vec<&str> = ctx.result[selected_lines(ctx)]
+    .iter()
+    .filter(|line| line.change != Change::Deletion)
+    .map(|line| line.value.as_str())
+    .collect();
+
+  let mut text = lines.join("\n");
+  text.push('\n');
+
+  ctx.message = Some(match clipboard::copy(&text) {
+    Ok(()) => format!("Copied {} line(s)", lines.len()),
+    Err(err) => format!("Could not copy: {}", err),
+  });
+  ctx.selection = None;
+}
+
+fn yank_permalink(ctx: &mut Context) {
+  let template = match &ctx.permalink {
+    Some(template) => template,
+    None => {
+      ctx.message = Some(String::from("No permalink configured"));
+      return;
+    }
+  };
+
+  let link = permalink(
+    template,
+    ctx.repository_path.as_deref().unwrap_or(&ctx.file_name),
+    ctx.current_line + 1,
+    ctx.head.as_deref().unwrap_or("HEAD"),
+  );
+
+  ctx.message = Some(match clipboard::copy(&link) {
+    Ok(()) => format!("Copied {}", link),
+    Err(err) => format!("Could not copy: {}", err),
+  });
+}
+
+fn permalink(template: &str, path: &str, line: usize, commit: &str) -> String {
+  template
+    .replace("{path}", path)
+    .replace("{line}", &line.to_string())
+    .replace("{commit}", commit)
+}
+
+fn paste(ctx: &mut Context) {
+  ctx.message = Some(match clipboard::paste() {
+    Ok(text) => {
+      let lines: Vec<String> = text.lines().map(String::from).collect();
+      let count = lines.len();
+      insert_lines(ctx, ctx.current_line, lines);
+      format!("Pasted {} line(s)", count)
+    }
+    Err(err) => format!("Could not paste: {}", err),
+  });
+}
+
+// insert lines into the result above `at`, the other buffers get blank lines to stay aligned;
+// lines right after a conflict become part of it
+fn insert_lines(ctx: &mut Context, at: usize, lines: Vec<String>) {
+  let count = lines.len();
+
+  for (i, value) in lines.into_iter().enumerate() {
+    ctx.local_changes.insert(
+      at + i,
+      Line {
+        value: text::Text::default(),
+        change: Change::None,
+      },
+    );
+    ctx.result.insert(
+      at + i,
+      Line {
+        value: value.into(),
+        change: Change::Addition,
+      },
+    );
+    ctx.incoming_changes.insert(
+      at + i,
+      Line {
+        value: text::Text::default(),
+        change: Change::None,
+      },
+    );
+  }
+
+  for conflict in ctx.conflicts.iter_mut() {
+    if conflict.start >= at {
+      conflict.start += count;
+      conflict.end += count;
+    } else if conflict.end >= at {
+      conflict.end += count;
+    }
+  }
+
+  if count > 0 {
+    ctx.modified = true;
+  }
+}
+
+// compare the result with taking either side everywhere, a big difference from both usually
+// means a conflict was left unresolved or accepted twice
+fn line_count_warning(ctx: &Context) -> Option<Vec<String>> {
+  let count = |lines: &Vec<Line>| {
+    lines
+      .iter()
+      .filter(|line| line.change != Change::Deletion)
+      .count()
+  };
+  let drastic = |a: usize, b: usize| a.abs_diff(b) > 5.max(b / 10);
+
+  let local = count(&ctx.local_changes);
+  let incoming = count(&ctx.incoming_changes);
+  let result = count(&ctx.result);
+
+  if !drastic(result, local) || !drastic(result, incoming) {
+    return None;
+  }
+
+  let mut lines = vec![format!(
+    "Result has {} lines, local has {} and incoming has {}.",
+    result, local, incoming
+  )];
+
+  for conflict in &ctx.conflicts {
+    let range = conflict.start..conflict.end;
+    let kept = |side: &Vec<Line>| {
+      range
+        .clone()
+        .any(|i| side[i].change == Change::Addition && ctx.result[i].change == Change::Addition)
+    };
+
+    if range.clone().any(|i| ctx.result[i].change == Change::None) {
+      lines.push(format!(
+        "Lines {}-{}: unresolved",
+        conflict.start + 1,
+        conflict.end
+      ));
+    } else if kept(&ctx.local_changes) && kept(&ctx.incoming_changes) {
+      lines.push(format!(
+        "Lines {}-{}: both sides kept",
+        conflict.start + 1,
+        conflict.end
+      ));
+    }
+  }
+
+  Some(lines)
+}
+
+// lines of the file as resolved so far, conflict lines without a decision are written back
+// between conflict markers so the file can be finished later
+fn result_lines(ctx: &Context) -> Vec<String> {
+  let marker = |c: char| c.to_string().repeat(ctx.marker_size);
+  let mut lines = vec![];
+  let mut i = 0;
+  // conflicts are sorted, the next one that can hold `i` moves along with it
+  let mut next = 0;
+
+  while i < ctx.result.len() {
+    while next < ctx.conflicts.len() && ctx.conflicts[next].end <= i {
+      next += 1;
+    }
+    let conflict = ctx
+      .conflicts
+      .get(next)
+      .filter(|conflict| conflict.start <= i);
+    let is_unresolved = |i: usize| {
+      ctx.result[i].change == Change::None
+        && (ctx.local_changes[i].change != Change::None
+          || ctx.incoming_changes[i].change != Change::None)
+    };
+
+    if let (Some(conflict), true) = (conflict, is_unresolved(i)) {
+      let start = i;
+      while i < conflict.end && is_unresolved(i) {
+        i += 1;
+      }
+
+      let side = |lines: &Vec<Line>| {
+        lines[start..i]
+          .iter()
+          .filter(|line| line.change == Change::Addition)
+          .map(|line| line.value.to_string())
+          .collect::<Vec<String>>()
+      };
+
+      lines.push(marker('<'));
+      lines.extend(side(&ctx.local_changes));
+
+      // base only makes sense when nothing of the conflict was decided
+      if let (Some(base), true) = (&conflict.base, start == conflict.start && i == conflict.end) {
+        lines.push(marker('|'));
+        lines.extend(base.iter().cloned());
+      }
+
+      lines.push(marker('='));
+      lines.extend(side(&ctx.incoming_changes));
+      lines.push(marker('>'));
+      continue;
+    }
+
+    if ctx.result[i].change != Change::Deletion {
+      lines.push(ctx.result[i].value.to_string());
+    }
+
+    i += 1;
+  }
+
+  lines
+}
+
+// the result with the line endings, byte order mark and final newline of the input
+fn file_content(ctx: &Context) -> String {
+  let mut content = String::new();
+
+  if ctx.bom {
+    content.push('\u{feff}');
+  }
+
+  let line_ending = if ctx.crlf { "\r\n" } else { "\n" };
+  content.push_str(&result_lines(ctx).join(line_ending));
+
+  if ctx.trailing_newline {
+    content.push_str(line_ending);
+  }
+
+  content
+}
+
+// write from the editor, a failure is shown over the file and it stays modified
+fn write_file(ctx: &mut Context) {
+  let (title, err) = match save(ctx) {
+    Ok(None) => return,
+    Ok(Some(warning)) => ("Warning", warning),
+    Err(err) => ("Error", err),
+  };
+
+  ctx.popup = Some(Popup {
+    title: String::from(title),
+    lines: vec![Spans::from(err.to_string())],
+    choices: vec![('q', "Close", |_| ())],
+  });
+}
+
+// `git add` a file just saved with every conflict resolved when staging is on, returns what
+// to add to the message about the write
+fn stage_written(git: &dyn git::GitBackend, autostage: bool, ctx: &mut Context) -> String {
+  if !std::mem::take(&mut ctx.written) || !autostage || unresolved_conflicts(ctx) > 0 {
+    return String::new();
+  }
+
+  match git.stage(destination(ctx)) {
+    Ok(()) => String::from(", staged"),
+    Err(err) => format!(", could not stage it: {}", err),
+  }
+}
+
+// the file is written once this returns Ok, a failure after that, like appending to the audit
+// log, comes back as a warning
+fn save(ctx: &mut Context) -> Result<Option<error::Error>, error::Error> {
+  let content = file_content(ctx);
+
+  // keep the conflicted file around like git mergetool does, once, before it is overwritten
+  if ctx.backup && !ctx.backed_up && ctx.output.is_none() {
+    let backup = format!("{}.orig", ctx.file_name);
+
+    std::fs::copy(&ctx.file_name, &backup).map_err(|err| error::Error::Backup(backup, err))?;
+    ctx.backed_up = true;
+  }
+
+  let bytes = encoding::encode(&content, ctx.encoding)
+    .map_err(|err| error::Error::Encode(String::from(destination(ctx)), err))?;
+
+  write_atomic(std::path::Path::new(destination(ctx)), &bytes)
+    .map_err(|err| error::Error::Write(String::from(destination(ctx)), err))?;
+  ctx.modified = false;
+  ctx.written = true;
+  state::remove(&ctx.file_name, &ctx.original.join("\n"));
+
+  let warning = ctx.audit_log.as_ref().and_then(|path| {
+    audit::append(path, &audit_entries(ctx))
+      .err()
+      .map(|err| error::Error::AuditLog(path.clone(), err))
+  });
+
+  Ok(warning)
+}
+
+// what the audit log records about each conflict of the file just written
+fn audit_entries(ctx: &Context) -> Vec<audit::Entry> {
+  let markers = &markers(ctx.original.iter().map(text::Text::as_str), ctx.marker_size);
+  let lines = |c: char| (1..=markers.len()).filter(move |i| markers[i - 1] == Some(c));
+  let ranges: Vec<(usize, usize)> = lines('<').zip(lines('>')).collect();
+
+  ctx
+    .conflicts
+    .iter()
+    .enumerate()
+    .map(|(c, conflict)| {
+      let resolution = match is_unresolved(ctx, conflict) {
+        true => "unresolved",
+        false => resolution(ctx, conflict.start..conflict.end),
+      };
+
+      audit::Entry {
+        file: String::from(destination(ctx)),
+        // a driver or structured merge leaves other conflicts than the ones read
+        lines: ranges
+          .get(c)
+          .filter(|_| ranges.len() == ctx.conflicts.len())
+          .copied(),
+        resolution,
+        edited: resolution == "edited",
+      }
+    })
+    .collect()
+}
+
+// resolutions and cursor of a file closed unwritten, so the next session can resume them
+fn keep_state(ctx: &Context) -> std::io::Result<()> {
+  let content = ctx.original.join("\n");
+
+  match ctx.modified {
+    true => state::save(&ctx.file_name, &content, &context_state(ctx)),
+    false => {
+      state::remove(&ctx.file_name, &content);
+      Ok(())
+    }
+  }
+}
+
+fn context_state(ctx: &Context) -> state::State {
+  let buffer = |lines: &[Line]| {
+    lines
+      .iter()
+      .map(|line| {
+        let change = match line.change {
+          Change::None => ' ',
+          Change::Addition => '+',
+          Change::Deletion => '-',
+        };
+        (change, line.value.to_string())
+      })
+      .collect()
+  };
+
+  state::State {
+    current_line: ctx.current_line,
+    conflicts: ctx
+      .conflicts
+      .iter()
+      .map(|conflict| (conflict.start, conflict.end))
+      .collect(),
+    buffers: [
+      buffer(&ctx.local_changes),
+      buffer(&ctx.result),
+      buffer(&ctx.incoming_changes),
+    ],
+  }
+}
+
+fn resume(ctx: &mut Context) {
+  let state = match ctx.resume.take() {
+    Some(state) => state,
+    None => return,
+  };
+  let len = state.buffers[1].len();
+  let fits = state.buffers.iter().all(|buffer| buffer.len() == len)
+    && state.conflicts.len() == ctx.conflicts.len()
+    && state
+      .conflicts
+      .iter()
+      .all(|(start, end)| start <= end && *end <= len);
+
+  if !fits {
+    ctx.message = Some(String::from("The saved progress does not match the file"));
+    return;
+  }
+
+  let [local, result, incoming] = state.buffers.map(|buffer| {
+    buffer
+      .into_iter()
+      .map(|(change, value)| Line {
+        value: value.into(),
+        change: match change {
+          '+' => Change::Addition,
+          '-' => Change::Deletion,
+          _ => Change::None,
+        },
+      })
+      .collect()
+  });
+
+  ctx.local_changes = local;
+  ctx.result = result;
+  ctx.incoming_changes = incoming;
+  for (conflict, (start, end)) in ctx.conflicts.iter_mut().zip(state.conflicts) {
+    conflict.start = start;
+    conflict.end = end;
+  }
+  ctx.modified = true;
+  move_to(state.current_line, ctx);
+  ctx.message = Some(format!(
+    "Resumed, {} unresolved conflict(s)",
+    unresolved_conflicts(ctx)
+  ));
+}
+
+fn start_over(ctx: &mut Context) {
+  ctx.resume = None;
+  state::remove(&ctx.file_name, &ctx.original.join("\n"));
+}
+
+// where the result is written, the input itself unless --output says otherwise
+fn destination(ctx: &Context) -> &str {
+  ctx.output.as_deref().unwrap_or(&ctx.file_name)
+}
+
+// a new file in `dir` to write `name` to first, a fresh name is tried until one does not exist
+// yet so nothing someone else put there is ever written through
+fn temp_file_next_to(
+  dir: &std::path::Path,
+  name: &str,
+) -> std::io::Result<(std::path::PathBuf, std::fs::File)> {
+  static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+  loop {
+    let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let temp = dir.join(format!(".{}.mersge-{}-{}", name, std::process::id(), count));
+
+    match std::fs::OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .open(&temp)
+    {
+      Ok(file) => return Ok((temp, file)),
+      Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+      Err(err) => return Err(err),
+    }
+  }
+}
+
+// write next to `path` first and rename over it, so a crash never leaves half a file behind
+fn write_atomic(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+  let dir = match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent,
+    _ => std::path::Path::new("."),
+  };
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  let (temp, mut file) = temp_file_next_to(dir, &name)?;
+  let written = file.write_all(bytes);
+  drop(file);
+
+  let result = written.and_then(|_| {
+    if let Ok(metadata) = std::fs::metadata(path) {
+      std::fs::set_permissions(&temp, metadata.permissions())?;
+
+      // only root can give the file away, keep going when that fails
+      #[cfg(unix)]
+      {
+        use std::os::unix::fs::MetadataExt;
+        let _ = std::os::unix::fs::chown(&temp, Some(metadata.uid()), Some(metadata.gid()));
+      }
+    }
+
+    std::fs::rename(&temp, path)
+  });
+
+  if result.is_err() {
+    let _ = std::fs::remove_file(&temp);
+  }
+
+  result
+}
+
+// a folded conflict is a single row, step over it as a whole
+fn move_down(ctx: &mut Context) {
+  let next = match fold_at(ctx, ctx.current_line) {
+    Some(fold) if fold.end < ctx.result.len() => fold.end,
+    Some(_) => ctx.current_line,
+    None => ctx.current_line + 1,
+  };
+
+  move_to(next, ctx);
+}
+
+fn move_up(ctx: &mut Context) {
+  let line = match fold_at(ctx, ctx.current_line) {
+    Some(fold) => fold.start,
+    None => ctx.current_line,
+  };
+  let previous = line.saturating_sub(1);
+
+  match fold_at(ctx, previous) {
+    Some(fold) => move_to(fold.start, ctx),
+    None => move_to(previous, ctx),
+  }
+}
+
+fn page_down(ctx: &mut Context) {
+  move_to(ctx.current_line + ctx.column_height.max(1), ctx);
+}
+
+fn page_up(ctx: &mut Context) {
+  move_to(
+    ctx.current_line.saturating_sub(ctx.column_height.max(1)),
+    ctx,
+  );
+}
+
+// put the cursor on `line` (clamped to the file) and scroll just enough to keep it visible
+fn move_to(line: usize, ctx: &mut Context) {
+  if ctx.result.is_empty() {
+    return;
+  }
+
+  ctx.current_line = line.min(ctx.result.len() - 1);
+
+  if ctx.current_line < ctx.line_offset {
+    ctx.line_offset = ctx.current_line;
+  } else if ctx.column_height > 0 && ctx.current_line >= ctx.line_offset + ctx.column_height {
+    ctx.line_offset = ctx.current_line + 1 - ctx.column_height;
+  }
+}
+
+// panes got a different height, when the terminal was resized or lines were added, the cursor
+// stays on screen and the panes stay filled
+fn fit_viewport(ctx: &mut Context, height: usize) {
+  ctx.column_height = height;
+  ctx.line_offset = ctx.line_offset.min(ctx.result.len().saturating_sub(height));
+  move_to(ctx.current_line, ctx);
+}
+
+// what a row of the minimap stands for
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mark {
+  Context,
+  Unresolved,
+  Resolved,
+}
+
+// one row per `height`th of the file, with whether its lines are on screen and the line a click
+// on it moves to, the start of its first conflict when it has one
+fn minimap(ctx: &Context, height: usize) -> Vec<(Mark, bool, usize)> {
+  let total = ctx.result.len();
+  let view = ctx.line_offset..ctx.line_offset + ctx.column_height.max(1);
+
+  (0..height.min(total))
+    .map(|row| {
+      let start = row * total / height.min(total);
+      let end = ((row + 1) * total / height.min(total)).max(start + 1);
+      let first = ctx
+        .conflicts
+        .partition_point(|conflict| conflict.end <= start);
+      let conflicts: Vec<&Conflict> = ctx.conflicts[first..]
+        .iter()
+        .take_while(|conflict| conflict.start < end)
+        .collect();
+      let mark = match conflicts.first() {
+        None => Mark::Context,
+        Some(_)
+          if conflicts
+            .iter()
+            .any(|conflict| is_unresolved(ctx, conflict)) =>
+        {
+          Mark::Unresolved
+        }
+        Some(_) => Mark::Resolved,
+      };
+      let line = conflicts
+        .first()
+        .map_or(start, |conflict| conflict.start.max(start));
+
+      (mark, start < view.end && end > view.start, line)
+    })
+    .collect()
+}
+
+// rows of the scrollbar thumb for `visible` lines from `offset` out of `total`, none when
+// everything fits; the thumb touches the ends only at the first and last lines
+fn scrollbar(
+  offset: usize,
+  visible: usize,
+  total: usize,
+  height: u16,
+) -> Option<std::ops::Range<u16>> {
+  if total <= visible || height == 0 {
+    return None;
+  }
+
+  let height = height as usize;
+  let size = (height * visible / total).clamp(1, height);
+  let start = match offset {
+    0 => 0,
+    offset if offset + visible >= total => height - size,
+    offset => (((height - size) * offset / (total - visible)).max(1)).min(height - size),
+  };
+
+  Some(start as u16..(start + size) as u16)
+}
+
+// the right border of a pane, below its title and above its bottom border
+fn track(pane: Rect) -> Rect {
+  Rect {
+    x: (pane.x + pane.width).saturating_sub(1),
+    y: pane.y + 1,
+    width: pane.width.min(1),
+    height: pane.height.saturating_sub(2),
+  }
+}
+
+// thumb drawn over a pane border, the border itself is the track
+struct Scrollbar(Option<std::ops::Range<u16>>);
+
+impl tui::widgets::Widget for Scrollbar {
+  fn render(self, area: Rect, buffer: &mut tui::buffer::Buffer) {
+    if area.width == 0 {
+      return;
+    }
+
+    for row in self.0.into_iter().flatten() {
+      buffer.get_mut(area.x, area.y + row).set_symbol("┃");
+    }
+  }
+}
+
+// scroll so `rows_above` lines show above the cursor, as far as the file allows
+fn align_view(ctx: &mut Context, rows_above: usize) {
+  let max_offset = ctx.result.len().saturating_sub(ctx.column_height);
+  ctx.line_offset = ctx.current_line.saturating_sub(rows_above).min(max_offset);
+}
+
+// scroll the viewport and drag the cursor along when it would leave the screen
+fn scroll_down(ctx: &mut Context, lines: usize) {
+  let max_offset = ctx.result.len().saturating_sub(ctx.column_height);
+  ctx.line_offset = (ctx.line_offset + lines).min(max_offset);
+
+  if ctx.current_line < ctx.line_offset {
+    ctx.current_line = ctx.line_offset;
+  }
+}
+
+fn scroll_up(ctx: &mut Context, lines: usize) {
+  ctx.line_offset = ctx.line_offset.saturating_sub(lines);
+
+  if ctx.column_height > 0 && ctx.current_line > ctx.line_offset + ctx.column_height - 1 {
+    ctx.current_line = ctx.line_offset + ctx.column_height - 1;
+  }
+}
+
+fn click(x: u16, y: u16, ctx: &mut Context) {
+  for (column, rect) in ctx.column_rects.iter().enumerate() {
+    // ignore clicks on the border and hidden panes
+    if rect.width < 2
+      || x <= rect.x
+      || x >= rect.right() - 1
+      || y <= rect.y
+      || y >= rect.bottom() - 1
+    {
+      continue;
+    }
+
+    let row = (y - rect.y - 1) as usize;
+
+    // rows skip folded and deleted lines, so map the row through what was rendered
+    let line = if column == 1 {
+      ctx.result_rows.get(row).copied()
+    } else {
+      ctx.side_rows.get(row).copied()
+    };
+
+    if let Some(line) = line {
+      if line < ctx.result.len() {
+        ctx.current_line = line;
+      }
+    }
+  }
+}
+
+// end of the run of deleted lines starting at `start` that came from the same side
+fn removed_run(ctx: &Context, start: usize) -> usize {
+  let side = &ctx.local_changes[start].change;
+  let mut end = start;
+
+  while end < ctx.result.len()
+    && ctx.result[end].change == Change::Deletion
+    && ctx.local_changes[end].change == *side
+  {
+    end += 1;
+  }
+
+  end
+}
+
+// draw the guide at `column` so overlong lines stand out, returns where it went
+fn guide(string: String, column: Option<usize>) -> (String, Option<std::ops::Range<usize>>) {
+  let column = match column {
+    Some(column) => column,
+    None => return (string, None),
+  };
+
+  // `column` counts terminal cells, find the character starting there
+  let mut cells = 0;
+  let index = string.chars().position(|c| {
+    let found = cells == column;
+    cells += c.width().unwrap_or(0);
+    found
+  });
+
+  let index = match index {
+    Some(index) => index,
+    None => return (string, None),
+  };
+
+  let mut guided = string;
+  let (offset, c) = guided.char_indices().nth(index).unwrap();
+
+  if c == ' ' {
+    guided.replace_range(offset..offset + 1, "│");
+  }
+
+  (guided, Some(index..index + 1))
+}
+
+// character ranges of `query` in `string`, case only matters when the query has uppercase
+fn matches(string: &str, query: &str) -> Vec<std::ops::Range<usize>> {
+  let ignore_case = !query.chars().any(char::is_uppercase);
+  let normalize = |c: char| {
+    if ignore_case {
+      c.to_lowercase().next().unwrap_or(c)
+    } else {
+      c
+    }
+  };
+
+  let haystack: Vec<char> = string.chars().map(normalize).collect();
+  let needle: Vec<char> = query.chars().map(normalize).collect();
+  let mut ranges = vec![];
+
+  if needle.is_empty() || needle.len() > haystack.len() {
+    return ranges;
+  }
+
+  let mut i = 0;
+  while i + needle.len() <= haystack.len() {
+    if haystack[i..i + needle.len()] == needle[..] {
+      ranges.push(i..i + needle.len());
+      i += needle.len();
+    } else {
+      i += 1;
+    }
+  }
+
+  ranges
+}
+
+// split a line into spans, each overlay patches its style over a range of characters
+fn styled(
+  string: String,
+  style: Style,
+  overlays: &[(std::ops::Range<usize>, Style)],
+) -> Spans<'static> {
+  // most rows have nothing on top, the string becomes the span as it is
+  if overlays.is_empty() {
+    return Spans::from(Span::styled(string, style));
+  }
+
+  // byte offset of every character and of the end, overlays count characters
+  let offsets: Vec<usize> = string
+    .char_indices()
+    .map(|(offset, _)| offset)
+    .chain(std::iter::once(string.len()))
+    .collect();
+  let chars = offsets.len() - 1;
+  let style_at = |i: usize| {
+    overlays
+      .iter()
+      .filter(|(range, _)| range.contains(&i))
+      .fold(style, |style, (_, overlay)| style.patch(*overlay))
+  };
+
+  let mut spans = vec![];
+  let mut start = 0;
+
+  while start < chars {
+    let span_style = style_at(start);
+    let mut end = start + 1;
+
+    while end < chars && style_at(end) == span_style {
+      end += 1;
+    }
+
+    spans.push(Span::styled(
+      String::from(&string[offsets[start]..offsets[end]]),
+      span_style,
+    ));
+    start = end;
+  }
+
+  Spans::from(spans)
+}
+
+// what the result pane shows for a line, a run of removed lines starting there as one marker
+fn result_value(ctx: &Context, i: usize) -> std::borrow::Cow<'_, str> {
+  if ctx.result[i].change != Change::Deletion {
+    return std::borrow::Cow::Borrowed(ctx.result[i].value.as_str());
+  }
+
+  let side = if ctx.local_changes[i].change == Change::Addition {
+    "local"
+  } else {
+    "incoming"
+  };
+  let count = removed_run(ctx, i) - i;
+  std::borrow::Cow::Owned(format!("⌫ {} line(s) removed (from {})", count, side))
+}
+
+// move row `row` out of the rows of a wrapped line, a row past the end is empty
+fn take_row(rows: &mut [String], row: usize) -> String {
+  rows.get_mut(row).map(std::mem::take).unwrap_or_default()
+}
+
+// split into rows of at most `width` terminal cells without breaking up a grapheme, an empty
+// line still takes a row
+fn wrap(string: &str, width: usize) -> Vec<String> {
+  let mut rows = vec![String::new()];
+  let mut used = 0;
+
+  if width == 0 {
+    return vec![String::from(string)];
+  }
+
+  for grapheme in string.graphemes(true) {
+    let grapheme_width = grapheme.width();
+
+    if used + grapheme_width > width && used > 0 {
+      rows.push(String::new());
+      used = 0;
+    }
+
+    rows.last_mut().unwrap().push_str(grapheme);
+    used += grapheme_width;
+  }
+
+  rows
+}
+
+// first line to show so that the lines from there to `current` fit in `height` rows when
+// lines take `rows(i)` rows each
+fn wrapped_offset(
+  offset: usize,
+  current: usize,
+  height: usize,
+  rows: impl Fn(usize) -> usize,
+) -> usize {
+  if current < offset {
+    return offset;
+  }
+
+  let mut start = current;
+  let mut used = rows(current);
+
+  while start > offset && used + rows(start - 1) <= height {
+    start -= 1;
+    used += rows(start);
+  }
+
+  start
+}
+
+// replace tabs with spaces up to the next tab stop, the buffers keep the tabs for writing
+fn expand_tabs(string: &str, width: usize) -> std::borrow::Cow<'_, str> {
+  if !string.contains('\t') {
+    return std::borrow::Cow::Borrowed(string);
+  }
+
+  let mut expanded = String::new();
+  let mut cells = 0;
+
+  for c in string.chars() {
+    if c == '\t' {
+      let spaces = width - cells % width.max(1);
+      expanded.push_str(&" ".repeat(spaces));
+      cells += spaces;
+    } else {
+      expanded.push(c);
+      cells += c.width().unwrap_or(0);
+    }
+  }
+
+  std::borrow::Cow::Owned(expanded)
+}
+
+// `+`, `-` or `│` like `git diff` on the first row of a line, rows it wraps onto get a blank
+fn gutter_mark(change: &Change, row: usize, style: Style) -> Span<'static> {
+  let (mark, color) = match (row, change) {
+    (1.., _) => (" ", Color::Reset),
+    (_, Change::None) => ("│", Color::DarkGray),
+    (_, Change::Addition) => ("+", Color::Green),
+    (_, Change::Deletion) => ("-", Color::Red),
+  };
+
+  Span::styled(mark, style.fg(color))
+}
+
+// fill up to `len` terminal cells, wide characters take two
+fn pad(mut string: String, len: usize) -> String {
+  let width = string.width();
+
+  if width < len {
+    string.extend(std::iter::repeat_n(' ', len - width));
+  }
+
+  string
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn parse_input_file() {
+    let mut ctx = crate::Context {
+      file_name: String::new(),
+      local_changes: vec![],
+      incoming_changes: vec![],
+      result: vec![],
+      current_line: 0,
+      line_offset: 0,
+      column_height: 0,
+      ..Default::default()
+    };
+
+    let file = String::from("before\n<<<<<<<\nabcd\nefgh\n=======\nijkl\n>>>>>>>\nafter");
+
+    crate::parse_input_file(&file, &mut ctx);
+
+    assert_eq!(ctx.local_changes.len(), 5);
+    assert_eq!(ctx.local_changes[0].value, "before");
+    assert_eq!(ctx.local_changes[0].change, crate::Change::None);
+    assert_eq!(ctx.local_changes[1].value, "abcd");
+    assert_eq!(ctx.local_changes[1].change, crate::Change::Addition);
+    assert_eq!(ctx.local_changes[2].value, "efgh");
+    assert_eq!(ctx.local_changes[2].change, crate::Change::Addition);
+    assert_eq!(ctx.local_changes[3].value, "-");
+    assert_eq!(ctx.local_changes[3].change, crate::Change::Deletion);
+    assert_eq!(ctx.local_changes[4].value, "after");
+    assert_eq!(ctx.local_changes[4].change, crate::Change::None);
+
+    assert_eq!(ctx.result.len(), 5);
+    assert_eq!(ctx.result[0].value, "before");
+    assert_eq!(ctx.result[0].change, crate::Change::None);
+    assert_eq!(ctx.result[1].value, "#");
+    assert_eq!(ctx.result[1].change, crate::Change::None);
+    assert_eq!(ctx.result[2].value, "#");
+    assert_eq!(ctx.result[2].change, crate::Change::None);
+    assert_eq!(ctx.result[3].value, "#");
+    assert_eq!(ctx.result[3].change, crate::Change::None);
+    assert_eq!(ctx.result[4].value, "after");
+    assert_eq!(ctx.result[4].change, crate::Change::None);
+
+    assert_eq!(ctx.incoming_changes.len(), 5);
+    assert_eq!(ctx.incoming_changes[0].value, "before");
+    assert_eq!(ctx.incoming_changes[0].change, crate::Change::None);
+    assert_eq!(ctx.incoming_changes[1].value, "-");
+    assert_eq!(ctx.incoming_changes[1].change, crate::Change::Deletion);
+    assert_eq!(ctx.incoming_changes[2].value, "-");
+    assert_eq!(ctx.incoming_changes[2].change, crate::Change::Deletion);
+    assert_eq!(ctx.incoming_changes[3].value, "ijkl");
+    assert_eq!(ctx.incoming_changes[3].change, crate::Change::Addition);
+    assert_eq!(ctx.incoming_changes[4].value, "after");
+    assert_eq!(ctx.incoming_changes[4].change, crate::Change::None);
+
+    assert_eq!(
+      ctx.conflicts,
+      vec![crate::Conflict {
+        start: 1,
+        end: 4,
+        base: None,
+        toggled: false
+      }]
+    );
+
+    // the original lines and the buffers share the text
+    let shared = |a: &crate::text::Text, b: &crate::text::Text| a.as_ptr() == b.as_ptr();
+    assert_eq!(ctx.original.len(), 8);
+    assert!(shared(&ctx.original[0], &ctx.result[0].value));
+    assert!(shared(&ctx.original[2], &ctx.local_changes[1].value));
+    assert!(shared(&ctx.original[5], &ctx.incoming_changes[3].value));
+  }
+
+  #[test]
+  fn parse_input_file_diff3() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+      "<<<<<<< ours\nL1\n||||||| base\nB1\nB2\n=======\nR1\n>>>>>>> theirs",
+      &mut ctx,
+    );
+
+    assert_eq!(ctx.result.len(), 2);
+    assert_eq!(ctx.local_changes[0].value, "L1");
+    assert_eq!(ctx.incoming_changes[1].value, "R1");
+    assert_eq!(
+      ctx.conflicts[0].base,
+      Some(vec![String::from("B1"), String::from("B2")])
+    );
+  }
+
+  #[test]
+  fn parse_stages() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_stages(
+      "Title\n=======\nb\n",
+      "Title\n=======\nL\n",
+      "Title\n=======\nR1\nR2\n",
+      &mut ctx,
+    );
+
+    let values = |lines: &Vec<crate::Line>| -> Vec<String> {
+      lines.iter().map(|line| line.value.to_string()).collect()
+    };
+    assert_eq!(
+      values(&ctx.local_changes),
+      ["Title", "=======", "L", "-", "-"]
+    );
+    assert_eq!(values(&ctx.result), ["Title", "=======", "#", "#", "#"]);
+    assert_eq!(
+      values(&ctx.incoming_changes),
+      ["Title", "=======", "-", "R1", "R2"]
+    );
+    assert_eq!(
+      ctx.conflicts,
+      vec![crate::Conflict {
+        start: 2,
+        end: 5,
+        base: Some(vec![String::from("b")]),
+        toggled: false,
+      }]
+    );
+    assert!(ctx.trailing_newline);
+    assert_eq!(ctx.local_label.as_deref(), Some("HEAD"));
+  }
+
+  #[test]
+  fn conflict_style_warning() {
+    let mut ctx = crate::Context::default();
+    assert_eq!(crate::conflict_style_warning("diff3", &ctx), None);
+
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>", &mut ctx);
+    assert_eq!(crate::conflict_style_warning("merge", &ctx), None);
+    assert!(crate::conflict_style_warning("zdiff3", &ctx).is_some());
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file("<<<<<<<\nL1\n|||||||\n=======\nR1\n>>>>>>>", &mut ctx);
+    assert_eq!(crate::conflict_style_warning("diff3", &ctx), None);
+    assert!(crate::conflict_style_warning("merge", &ctx).is_some());
+  }
+
+  #[test]
+  fn process_change() {
+    let mut ctx = crate::Context {
+      file_name: String::new(),
+      local_changes: vec![
+        crate::Line {
+          value: crate::text::Text::from("L1"),
+          change: crate::Change::Addition,
+        },
+        crate::Line {
+          value: crate::text::Text::from("L2"),
+          change: crate::Change::Addition,
+        },
+      ],
+      incoming_changes: vec![
+        crate::Line {
+          value: crate::text::Text::from("R1"),
+          change: crate::Change::Deletion,
+        },
+        crate::Line {
+          value: crate::text::Text::from("R2"),
+          change: crate::Change::Addition,
+        },
+      ],
+      result: vec![
+        crate::Line {
+          value: crate::text::Text::default(),
+          change: crate::Change::None,
+        },
+        crate::Line {
+          value: crate::text::Text::default(),
+          change: crate::Change::None,
+        },
+      ],
+      current_line: 0,
+      line_offset: 0,
+      column_height: 0,
+      ..Default::default()
+    };
+
+    crate::process_change(crate::Column::Right, &mut ctx);
+    assert_eq!(ctx.result[0].value, "");
+    assert_eq!(ctx.result[0].change, crate::Change::Deletion);
+
+    ctx.current_line = 1;
+
+    crate::process_change(crate::Column::Left, &mut ctx);
+    assert_eq!(ctx.result[1].value, "L2");
+    assert_eq!(ctx.result[1].change, crate::Change::Addition);
+  }
+
+  #[test]
+  fn process_conflict() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\nR2\n>>>>>>>\nafter", &mut ctx);
+
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+    assert_eq!(ctx.result[0].change, crate::Change::Deletion);
+    assert_eq!(ctx.result[1].value, "R1");
+    assert_eq!(ctx.result[2].value, "R2");
+
+    // outside of a conflict nothing happens
+    ctx.current_line = 3;
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert_eq!(ctx.result[3].change, crate::Change::None);
+    assert_eq!(ctx.result[1].value, "R1");
+  }
+
+  #[test]
+  fn unresolved_conflicts() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+      "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+    assert_eq!(crate::unresolved_conflicts(&ctx), 2);
+    assert!(!ctx.modified);
+
+    crate::process_change(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::unresolved_conflicts(&ctx), 2);
+    assert!(ctx.modified);
+
+    ctx.current_line = 1;
+    crate::process_change(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::unresolved_conflicts(&ctx), 1);
+  }
+
+  #[test]
+  fn selected_lines() {
+    let mut ctx = crate::Context {
+      current_line: 3,
+      ..Default::default()
+    };
+    assert_eq!(crate::selected_lines(&ctx), 3..4);
+
+    ctx.selection = Some(5);
+    assert_eq!(crate::selected_lines(&ctx), 3..6);
+
+    ctx.selection = Some(1);
+    assert_eq!(crate::selected_lines(&ctx), 1..4);
+  }
+
+  #[test]
+  fn apply_to_similar() {
+    use crossterm::event::KeyCode;
+
+    let conflict = |local: &str| format!("<<<<<<<\n{}\n=======\nR\n>>>>>>>\n", local);
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(
+      &[
+        conflict("L"),
+        conflict("other"),
+        conflict("L"),
+        conflict("L"),
+        String::from("after"),
+      ]
+      .concat(),
+      &mut ctx,
+    );
+    crate::move_to(ctx.conflicts[3].start, &mut ctx);
+    let mut session = session(vec![ctx]);
+    session.picker = None;
+
+    // take incoming, say no to the others for now and paste below it
+    crate::handle_event(crate::key_event(KeyCode::Char('R')), &mut session);
+    crate::handle_event(crate::key_event(KeyCode::Char('n')), &mut session);
+    crate::handle_event(crate::key_event(KeyCode::End), &mut session);
+    crate::clipboard::PASTED.with(|pasted| *pasted.borrow_mut() = Some(String::from("P")));
+    crate::handle_event(crate::key_event(KeyCode::Char('P')), &mut session);
+    crate::clipboard::PASTED.with(|pasted| *pasted.borrow_mut() = None);
+
+    let ctx = &mut session.files[0];
+    assert_eq!(ctx.message.as_deref(), Some("Pasted 1 line(s)"));
+    assert_eq!(crate::similar_conflicts(ctx, 3), vec![0, 2]);
+
+    crate::offer_similar(3, ctx);
+    assert!(ctx.popup.is_some());
+    crate::apply_to_similar(ctx);
+
+    assert_eq!(crate::unresolved_conflicts(ctx), 1);
+    assert_eq!(
+      crate::result_lines(ctx),
+      vec!["R", "P", "<<<<<<<", "other", "=======", "R", ">>>>>>>", "R", "P", "R", "P", "after"]
+    );
+  }
+
+  #[test]
+  fn resume() {
+    let file = "a\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>";
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+    crate::insert_lines(&mut ctx, 1, vec![String::from("P")]);
+    crate::move_to(3, &mut ctx);
+    let state = crate::context_state(&ctx);
+
+    let mut resumed = crate::Context::default();
+    crate::parse_input_file(file, &mut resumed);
+    resumed.resume = Some(state);
+    crate::resume(&mut resumed);
+
+    assert_eq!(crate::result_lines(&resumed), crate::result_lines(&ctx));
+    assert_eq!(resumed.conflicts, ctx.conflicts);
+    assert_eq!(resumed.current_line, 3);
+    assert!(resumed.modified);
+
+    let mut other = crate::Context::default();
+    crate::parse_input_file("<<<<<<<\nL\n=======\nR\n>>>>>>>", &mut other);
+    other.resume = Some(crate::context_state(&ctx));
+    crate::resume(&mut other);
+    assert!(!other.modified);
+  }
+
+  #[test]
+  fn insert_lines() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file("before\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>\nafter", &mut ctx);
+
+    crate::insert_lines(&mut ctx, 2, vec![String::from("P1"), String::from("P2")]);
+
+    assert_eq!(ctx.result.len(), 6);
+    assert_eq!(ctx.local_changes.len(), 6);
+    assert_eq!(ctx.incoming_changes.len(), 6);
+    assert_eq!(ctx.result[2].value, "P1");
+    assert_eq!(ctx.result[3].value, "P2");
+    assert_eq!(ctx.result[3].change, crate::Change::Addition);
+    assert_eq!(ctx.incoming_changes[4].value, "R1");
+    assert_eq!((ctx.conflicts[0].start, ctx.conflicts[0].end), (1, 5));
+    assert!(ctx.modified);
+
+    crate::insert_lines(&mut ctx, 0, vec![String::from("P0")]);
+    assert_eq!((ctx.conflicts[0].start, ctx.conflicts[0].end), (2, 6));
+
+    // right after the conflict the lines join it
+    crate::insert_lines(&mut ctx, 6, vec![String::from("P3")]);
+    assert_eq!((ctx.conflicts[0].start, ctx.conflicts[0].end), (2, 7));
+    assert_eq!(ctx.result[7].value, "after");
+  }
+
+  #[test]
+  fn line_count_warning() {
+    let mut ctx = crate::Context::default();
+    let local = "L\n".repeat(10);
+    let incoming = "R\n".repeat(10);
+
+    crate::parse_input_file(
+      &format!("<<<<<<<\n{}=======\n{}>>>>>>>\nafter", local, incoming),
+      &mut ctx,
+    );
+
+    // placeholders for both sides
+    let warning = crate::line_count_warning(&ctx).unwrap();
+    assert_eq!(
+      warning[0],
+      "Result has 21 lines, local has 11 and incoming has 11."
+    );
+    assert_eq!(warning[1], "Lines 1-20: unresolved");
+
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert!(crate::line_count_warning(&ctx).is_none());
+
+    for i in 10..20 {
+      ctx.current_line = i;
+      crate::process_change(crate::Column::Right, &mut ctx);
+    }
+    for i in 0..10 {
+      ctx.current_line = i;
+      crate::process_change(crate::Column::Left, &mut ctx);
+    }
+
+    let warning = crate::line_count_warning(&ctx).unwrap();
+    assert_eq!(warning[1], "Lines 1-20: both sides kept");
+  }
+
+  #[test]
+  fn process_all() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+      "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\nmiddle\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+
+    crate::process_all(crate::Column::Right, &mut ctx);
+
+    let values: Vec<&str> = ctx
+      .result
+      .iter()
+      .filter(|line| line.change != crate::Change::Deletion)
+      .map(|line| line.value.as_str())
+      .collect();
+    assert_eq!(values, vec!["R1", "middle", "R2"]);
+  }
+
+  #[test]
+  fn autowrite() {
+    let path = std::env::temp_dir().join(format!("mersge-autowrite-{}", std::process::id()));
+    let mut files = vec![];
+
+    for file_name in [path.to_str().unwrap(), "other"] {
+      let mut ctx = crate::Context {
+        file_name: String::from(file_name),
+        ..Default::default()
+      };
+      crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>", &mut ctx);
+      files.push(ctx);
+    }
+
+    let mut session = crate::Session {
+      files,
+      current: 0,
+      clear: false,
+      autowrite: true,
+      autostage: false,
+      git: crate::git::backend("subprocess").unwrap(),
+      review: None,
+      touched: vec![],
+      pane_ratios: None,
+      picker: None,
+      dirty: true,
+    };
+    let file_name = session.files[0].file_name.clone();
+
+    crate::process_conflict(crate::Column::Left, &mut session.files[0]);
+    crate::autowrite(&mut session, &file_name, 1);
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "L1");
+    assert!(!session.files[0].modified);
+    assert_eq!(session.current, 1);
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  fn session(files: Vec<crate::Context>) -> crate::Session {
+    crate::Session {
+      files,
+      current: 0,
+      clear: false,
+      autowrite: false,
+      autostage: false,
+      git: crate::git::backend("subprocess").unwrap(),
+      review: None,
+      touched: vec![],
+      pane_ratios: None,
+      picker: None,
+      dirty: true,
+    }
+  }
+
+  fn conflicted(file_name: &str) -> crate::Context {
+    let mut ctx = crate::Context {
+      file_name: String::from(file_name),
+      ratios: [30, 40, 30],
+      ..Default::default()
+    };
+    crate::parse_input_file("a\n<<<<<<< HEAD\nL\n=======\nR\n>>>>>>> topic\nb", &mut ctx);
+    crate::jump_to_first_conflict(&mut ctx);
+    ctx
+  }
+
+  // a file without conflicts of `n` lines, `line 0` to `line {n - 1}`
+  fn numbered(n: usize) -> crate::Context {
+    let mut ctx = crate::Context {
+      file_name: String::from("a.txt"),
+      ratios: [30, 40, 30],
+      ..Default::default()
+    };
+    let lines: Vec<String> = (0..n).map(|i| format!("line {}", i)).collect();
+    crate::parse_input_file(&lines.join("\n"), &mut ctx);
+    ctx
+  }
+
+  // what `render` leaves on a terminal of this size
+  fn screen(session: &mut crate::Session, width: u16, height: u16) -> tui::buffer::Buffer {
+    let backend = tui::backend::TestBackend::new(width, height);
+    let mut terminal = tui::Terminal::new(backend).unwrap();
+
+    crate::render(&mut terminal, session).unwrap();
+    terminal.backend().buffer().clone()
+  }
+
+  fn row(buffer: &tui::buffer::Buffer, y: u16) -> String {
+    (0..buffer.area.width)
+      .map(|x| buffer.get(x, y).symbol.as_str())
+      .collect()
+  }
+
+  #[test]
+  fn render_wrapped() {
+    let mut ctx = crate::Context {
+      file_name: String::from("a.txt"),
+      ratios: [45, 10, 45],
+      wrap: true,
+      ..Default::default()
+    };
+    let long = "x".repeat(30);
+    let file = format!(
+      "<<<<<<<\nL\n=======\nR\n>>>>>>>\n{}",
+      [long.as_str(); 4].join("\n")
+    );
+    crate::parse_input_file(&file, &mut ctx);
+    ctx.current_line = 5;
+    let mut session = session(vec![ctx]);
+
+    // the long lines wrap onto more rows in the narrow result pane, the side panes keep
+    // them level and the cursor stays on screen
+    let buffer = screen(&mut session, 120, 12);
+    let ctx = &session.files[0];
+    assert_eq!(ctx.result_rows, ctx.side_rows);
+    assert_eq!(ctx.result_rows.last(), Some(&5));
+    assert!(row(&buffer, 1).starts_with("│xxx"));
+    assert!(row(&buffer, 2).starts_with("│   "));
+  }
+
+  #[test]
+  fn render_panes() {
+    let mut session = session(vec![conflicted("a.txt")]);
+    let buffer = screen(&mut session, 120, 10);
+
+    assert!(row(&buffer, 0).starts_with("┌Local changes (HEAD)─"));
+    assert!(row(&buffer, 0).contains("┌Result — conflict 1/1 (1 unresolved)─"));
+    assert!(row(&buffer, 0).contains("┌Incoming changes (topic)─"));
+    assert!(row(&buffer, 2).starts_with("│L    "));
+    assert!(row(&buffer, 3).contains("││R    "));
+    assert!(row(&buffer, 8).contains("[L] Accept local"));
+
+    // the cursor sits on the first conflict line, in every pane
+    let current = tui::style::Color::Yellow;
+    assert_eq!(buffer.get(1, 2).bg, current);
+    assert_eq!(buffer.get(36, 2).symbol, "#");
+    assert_eq!(buffer.get(36, 2).bg, current);
+    assert_ne!(buffer.get(1, 1).bg, current);
+    assert_eq!(buffer.get(1, 3).fg, tui::style::Color::Red);
+
+    // narrow terminals get local and incoming only
+    let buffer = screen(&mut session, 90, 10);
+    assert!(row(&buffer, 0).contains("┐┌Incoming changes (topic)─"));
+    assert!(!row(&buffer, 0).contains("Result"));
+  }
+
+  #[test]
+  fn render_files() {
+    let mut session = session(vec![conflicted("a.txt"), conflicted("b.txt")]);
+    session.files[1].message = Some(String::from("Hello"));
+    session.current = 1;
+
+    let buffer = screen(&mut session, 120, 12);
+    assert!(row(&buffer, 0).starts_with(" a.txt (1) │ b.txt (1) │"));
+    assert_eq!(buffer.get(12, 0).bg, tui::style::Color::Yellow);
+    assert!(row(&buffer, 9).contains("b.txt, file 2/2, 0/2 conflicts resolved | Hello"));
+
+    session.picker = Some(0);
+    let buffer = screen(&mut session, 120, 12);
+    assert!(row(&buffer, 0).contains("Files — 0/2 resolved"));
+    assert!(row(&buffer, 1).contains("a.txt — 1/1 conflict(s) left"));
+  }
+
+  #[test]
+  fn loading() {
+    let file = String::from("a\n<<<<<<<\nL\n=======\nR\n>>>>>>>\nb");
+    let mut expected = crate::Context::default();
+    crate::parse_input_file(&file, &mut expected);
+
+    let ctx = crate::Context {
+      file_name: String::from("big.txt"),
+      ..Default::default()
+    };
+    let receiver = crate::parse_in_background(crate::input::Input::Decoded(file), ctx);
+    let backend = tui::backend::TestBackend::new(40, 5);
+    let mut terminal = tui::Terminal::new(backend).unwrap();
+    let ctx = crate::loading(&mut terminal, "big.txt", &receiver).unwrap();
+
+    assert_eq!(ctx.file_name, "big.txt");
+    assert_eq!(ctx.conflicts, expected.conflicts);
+    assert_eq!(ctx.original, expected.original);
+
+    crate::render_loading(&mut terminal, "big.txt", 42, 1).unwrap();
+    assert_eq!(
+      row(terminal.backend().buffer(), 2).trim(),
+      "⠙ Loading big.txt… 42%"
+    );
+  }
+
+  #[test]
+  fn resize() {
+    let mut session = session(vec![numbered(40)]);
+
+    screen(&mut session, 90, 30);
+    crate::move_to(20, &mut session.files[0]);
+    assert_eq!(session.files[0].line_offset, 0);
+
+    crate::handle_event(crossterm::event::Event::Resize(90, 12), &mut session);
+    assert!(session.clear);
+
+    let buffer = screen(&mut session, 90, 12);
+    let ctx = &session.files[0];
+    assert_eq!(ctx.column_height, 7);
+    assert_eq!(ctx.line_offset, 14);
+    assert!(row(&buffer, 7).contains("line 20"));
+
+    // growing back fills the panes instead of leaving the top lines out
+    screen(&mut session, 90, 60);
+    assert_eq!(session.files[0].line_offset, 0);
+  }
+
+  #[test]
+  fn modifiers() {
+    use crossterm::event;
+
+    let chord = |modifiers, c| {
+      event::Event::Key(event::KeyEvent {
+        code: event::KeyCode::Char(c),
+        modifiers,
+      })
+    };
+    let mut session = session(vec![conflicted("a.txt")]);
+
+    crate::handle_event(chord(event::KeyModifiers::SHIFT, 'R'), &mut session);
+    assert_eq!(crate::result_lines(&session.files[0]), vec!["a", "R", "b"]);
+
+    // Ctrl+Z takes the decision back like X
+    crate::handle_event(chord(event::KeyModifiers::CONTROL, 'z'), &mut session);
+    assert_eq!(crate::unresolved_conflicts(&session.files[0]), 1);
+
+    // a chord never runs the plain key, `s` would switch the panes
+    crate::handle_event(chord(event::KeyModifiers::ALT, 's'), &mut session);
+    assert!(!session.files[0].show_result);
+
+    session.picker = Some(0);
+    assert!(crate::handle_event(
+      chord(event::KeyModifiers::CONTROL, 'c'),
+      &mut session
+    ));
+    assert_eq!((session.picker, session.review), (None, Some(0)));
+    assert!(!crate::handle_event(
+      chord(event::KeyModifiers::CONTROL, 'c'),
+      &mut session
+    ));
+  }
+
+  #[test]
+  fn count() {
+    use crossterm::event::KeyCode;
+
+    let mut session = session(vec![numbered(40)]);
+    let mut press = |code| crate::handle_event(crate::key_event(code), &mut session);
+
+    press(KeyCode::Char('1'));
+    press(KeyCode::Char('5'));
+    press(KeyCode::Char('j'));
+    press(KeyCode::Char('0'));
+    press(KeyCode::Down);
+    press(KeyCode::Char('3'));
+    press(KeyCode::Up);
+    // a count in front of anything else runs it once and is gone
+    press(KeyCode::Char('2'));
+    press(KeyCode::Char('v'));
+    press(KeyCode::Down);
+
+    let ctx = &session.files[0];
+    assert_eq!(ctx.current_line, 14);
+    assert_eq!(ctx.selection, Some(13));
+    assert_eq!(ctx.count, None);
+
+    // a count is never more than the lines of the file
+    for _ in 0..30 {
+      crate::handle_event(crate::key_event(KeyCode::Char('9')), &mut session);
+    }
+    assert_eq!(session.files[0].count, Some(40));
+    crate::handle_event(crate::key_event(KeyCode::Char('j')), &mut session);
+    assert_eq!(session.files[0].current_line, 39);
+  }
+
+  #[test]
+  fn repeat() {
+    use crossterm::event::KeyCode;
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(
+      "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\na\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+    let mut session = session(vec![ctx]);
+    let mut press = |code| crate::handle_event(crate::key_event(code), &mut session);
+
+    press(KeyCode::Char('.'));
+    press(KeyCode::Char('R'));
+    // without a search `n` goes to the next unresolved conflict
+    press(KeyCode::Char('n'));
+    press(KeyCode::Char('.'));
+
+    let ctx = &session.files[0];
+    assert_eq!(crate::result_lines(ctx), vec!["R1", "a", "R2"]);
+    assert_eq!(
+      ctx.last_resolution,
+      Some(crate::Action::AcceptIncomingConflict)
+    );
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file("a", &mut ctx);
+    crate::next_conflict(&mut ctx, true);
+    assert_eq!(ctx.message.as_deref(), Some("No unresolved conflicts left"));
+  }
+
+  #[test]
+  fn align_view() {
+    let mut ctx = numbered(40);
+    ctx.column_height = 10;
+    crate::move_to(20, &mut ctx);
+
+    crate::align_view(&mut ctx, 5);
+    assert_eq!(ctx.line_offset, 15);
+    crate::align_view(&mut ctx, 0);
+    assert_eq!(ctx.line_offset, 20);
+    crate::align_view(&mut ctx, 9);
+    assert_eq!(ctx.line_offset, 11);
+
+    // the last lines cannot go to the top, the panes stay filled
+    crate::move_to(38, &mut ctx);
+    crate::align_view(&mut ctx, 0);
+    assert_eq!(ctx.line_offset, 30);
+    assert_eq!(ctx.current_line, 38);
+  }
+
+  #[test]
+  fn scrollbar() {
+    assert_eq!(crate::scrollbar(0, 10, 10, 8), None);
+    assert_eq!(crate::scrollbar(0, 10, 40, 8), Some(0..2));
+    assert_eq!(crate::scrollbar(1, 10, 40, 8), Some(1..3));
+    assert_eq!(crate::scrollbar(15, 10, 40, 8), Some(3..5));
+    assert_eq!(crate::scrollbar(29, 10, 40, 8), Some(5..7));
+    assert_eq!(crate::scrollbar(30, 10, 40, 8), Some(6..8));
+    assert_eq!(crate::scrollbar(500, 10, 1000, 8), Some(3..4));
+
+    let mut session = session(vec![numbered(40)]);
+
+    let buffer = screen(&mut session, 90, 15);
+    let right_border: Vec<&str> = (1..11).map(|y| buffer.get(89, y).symbol.as_str()).collect();
+    assert_eq!(
+      right_border,
+      vec!["┃", "┃", "│", "│", "│", "│", "│", "│", "│", "│"]
+    );
+  }
+
+  #[test]
+  fn minimap() {
+    use crate::Mark;
+
+    let mut ctx = crate::Context::default();
+    let mut lines: Vec<String> = (0..20).map(|i| format!("line {}", i)).collect();
+    lines.splice(
+      4..4,
+      ["<<<<<<<", "L1", "=======", "R1", ">>>>>>>"].map(String::from),
+    );
+    lines.splice(
+      16..16,
+      ["<<<<<<<", "L2", "=======", "R2", ">>>>>>>"].map(String::from),
+    );
+    crate::parse_input_file(&lines.join("\n"), &mut ctx);
+    ctx.column_height = 6;
+    ctx.current_line = 13;
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+
+    // 24 lines in 6 rows, conflicts at 4 and 13 of which the second is resolved
+    assert_eq!(
+      crate::minimap(&ctx, 6),
+      vec![
+        (Mark::Context, true, 0),
+        (Mark::Unresolved, true, 4),
+        (Mark::Context, false, 8),
+        (Mark::Resolved, false, 13),
+        (Mark::Context, false, 16),
+        (Mark::Context, false, 20),
+      ]
+    );
+
+    let mut session = session(vec![ctx]);
+    crate::perform(crate::Action::JumpTo(16), &mut session);
+    assert_eq!(session.files[0].current_line, 16);
+
+    // the click centers the line, rows of 4 or 5 lines now
+    let buffer = screen(&mut session, 120, 10);
+    assert_eq!(buffer.get(119, 1).symbol, "▐");
+    assert_eq!(buffer.get(119, 2).fg, tui::style::Color::Red);
+    assert_eq!(buffer.get(119, 4).symbol, "█");
+  }
+
+  #[test]
+  fn dim_resolved() {
+    use tui::style::Modifier;
+
+    let mut session = session(vec![conflicted("a.txt")]);
+    let buffer = screen(&mut session, 120, 10);
+    assert!(!buffer.get(1, 3).modifier.contains(Modifier::DIM));
+
+    crate::process_conflict(crate::Column::Left, &mut session.files[0]);
+    session.files[0].current_line = 0;
+    let buffer = screen(&mut session, 120, 10);
+
+    // local and incoming lines of the conflict in both side panes, the context above is not
+    assert!(buffer.get(1, 2).modifier.contains(Modifier::DIM));
+    assert!(buffer.get(1, 3).modifier.contains(Modifier::DIM));
+    assert!(buffer.get(100, 3).modifier.contains(Modifier::DIM));
+    assert!(!buffer.get(1, 1).modifier.contains(Modifier::DIM));
+  }
+
+  #[test]
+  fn current_conflict() {
+    use tui::style::Color;
+
+    let mut session = session(vec![conflicted("a.txt")]);
+    session.files[0].current_line = 1;
+    let buffer = screen(&mut session, 120, 10);
+
+    assert_eq!(buffer.get(1, 1).bg, Color::Reset);
+    assert_eq!(buffer.get(1, 2).bg, Color::Yellow);
+    assert_eq!(buffer.get(1, 3).bg, Color::Indexed(236));
+    assert_eq!(buffer.get(100, 3).bg, Color::Indexed(236));
+    assert_eq!(buffer.get(1, 4).bg, Color::Reset);
+
+    session.files[0].current_line = 0;
+    let buffer = screen(&mut session, 120, 10);
+    assert_eq!(buffer.get(1, 3).bg, Color::Reset);
+  }
+
+  #[test]
+  fn gutter() {
+    use tui::style::Color;
+
+    let mut session = session(vec![conflicted("a.txt")]);
+    session.files[0].current_line = 0;
+    crate::perform(crate::Action::ToggleGutter, &mut session);
+    let buffer = screen(&mut session, 120, 10);
+
+    assert!(row(&buffer, 1).starts_with("││a "));
+    assert_eq!(buffer.get(1, 2).symbol, "+");
+    assert_eq!(buffer.get(1, 2).fg, Color::Green);
+    // the text keeps its own color
+    assert_eq!(buffer.get(2, 2).symbol, "L");
+    assert_eq!(buffer.get(2, 2).fg, Color::Reset);
+    assert_eq!(buffer.get(1, 3).symbol, "-");
+  }
+
+  #[test]
+  fn tiny_terminal() {
+    let mut session = session(vec![conflicted("a.txt"), conflicted("b.txt")]);
+    session.picker = None;
+
+    // the tab row leaves the panes no room for their borders
+    for height in 1..6 {
+      screen(&mut session, 60, height);
+    }
+  }
+
+  #[test]
+  fn quit_unwritten() {
+    use crossterm::event::KeyCode;
+
+    let mut session = session(vec![conflicted("a.txt"), conflicted("b.txt")]);
+    session.picker = None;
+    crate::process_conflict(crate::Column::Left, &mut session.files[0]);
+
+    crate::perform(crate::Action::Quit, &mut session);
+    assert!(session.files[0].popup.is_some());
+    crate::handle_event(crate::key_event(KeyCode::Char('n')), &mut session);
+    assert_eq!(session.files.len(), 2);
+
+    // nothing to lose in the other file, it closes right away
+    session.current = 1;
+    crate::perform(crate::Action::Quit, &mut session);
+    assert_eq!(session.files.len(), 1);
+    assert_eq!(session.files[0].file_name, "a.txt");
+
+    // the review screen still lists it
+    assert_eq!(
+      session.touched,
+      vec!["b.txt → b.txt: 1 conflict(s); 1 unresolved; nothing to write; closed"]
+    );
+    session.review = Some(0);
+    let buffer = screen(&mut session, 100, 10);
+    assert!(row(&buffer, 1).starts_with("│a.txt → a.txt: "));
+    assert!(row(&buffer, 2).starts_with("│b.txt → b.txt: "));
+  }
+
+  #[test]
+  fn dirty() {
+    use crossterm::event;
+
+    let mut session = session(vec![conflicted("a.txt")]);
+    let mouse = |kind| {
+      event::Event::Mouse(event::MouseEvent {
+        kind,
+        column: 1,
+        row: 1,
+        modifiers: event::KeyModifiers::NONE,
+      })
+    };
+
+    session.dirty = false;
+    crate::handle_event(mouse(event::MouseEventKind::Moved), &mut session);
+    crate::handle_event(crate::key_event(event::KeyCode::F(5)), &mut session);
+    assert!(!session.dirty);
+
+    crate::handle_event(crate::key_event(event::KeyCode::Char('a')), &mut session);
+    assert!(session.dirty);
+
+    // the key after clears the message shown for `a`
+    session.dirty = false;
+    crate::handle_event(crate::key_event(event::KeyCode::F(5)), &mut session);
+    assert!(session.dirty);
+
+    session.dirty = false;
+    crate::handle_event(mouse(event::MouseEventKind::ScrollDown), &mut session);
+    assert!(session.dirty);
+  }
+
+  #[test]
+  fn run_script() {
+    let path = std::env::temp_dir().join(format!("mersge-script-{}", std::process::id()));
+    let mut ctx = crate::Context {
+      file_name: path.to_string_lossy().into_owned(),
+      ..Default::default()
+    };
+    crate::parse_input_file(
+      "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+    let mut session = crate::Session {
+      files: vec![ctx],
+      current: 0,
+      clear: false,
+      autowrite: false,
+      autostage: false,
+      git: crate::git::backend("subprocess").unwrap(),
+      review: None,
+      touched: vec![],
+      pane_ratios: None,
+      picker: None,
+      dirty: true,
+    };
+
+    let commands = crate::script::parse("conflict 2: theirs\nremaining: ours\nwrite").unwrap();
+    assert_eq!(crate::run_script(&commands, &mut session), Ok(()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "L1\nR2");
+
+    let commands = crate::script::parse("conflict 3: ours").unwrap();
+    assert!(crate::run_script(&commands, &mut session)
+      .unwrap_err()
+      .starts_with("script line 1: "));
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn stage_written() {
+    let git = crate::git::backend("subprocess").unwrap();
+    let mut ctx = crate::Context {
+      file_name: std::env::temp_dir()
+        .join("mersge-not-a-repository")
+        .to_string_lossy()
+        .into_owned(),
+      ..Default::default()
+    };
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>", &mut ctx);
+
+    ctx.written = true;
+    assert_eq!(crate::stage_written(git.as_ref(), true, &mut ctx), "");
+
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::stage_written(git.as_ref(), true, &mut ctx), "");
+
+    ctx.written = true;
+    assert!(crate::stage_written(git.as_ref(), true, &mut ctx).starts_with(", could not stage it"));
+    assert!(!ctx.written);
+  }
+
+  #[test]
+  fn result_title() {
+    let mut ctx = crate::Context::default();
+    assert_eq!(crate::result_title(&ctx), "Result");
+
+    crate::parse_input_file(
+      "a\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+    assert_eq!(
+      crate::result_title(&ctx),
+      "Result — 2 conflict(s) (2 unresolved)"
+    );
+
+    ctx.current_line = 3;
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert_eq!(
+      crate::result_title(&ctx),
+      "Result — conflict 2/2 (1 unresolved)"
+    );
+  }
+
+  #[test]
+  fn fold_at() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file("a\n<<<<<<<\nL1\nL2\n=======\nR1\n>>>>>>>\nb", &mut ctx);
+    ctx.current_line = 1;
+    crate::toggle_fold(&mut ctx);
+    assert_eq!(crate::fold_at(&ctx, 2), None);
+
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::fold_at(&ctx, 2), Some(1..4));
+    assert_eq!(crate::fold_summary(&ctx, 1..4), "▸ 2 line(s), took local");
+
+    ctx.current_line = 4;
+    crate::move_up(&mut ctx);
+    assert_eq!(ctx.current_line, 1);
+    crate::move_down(&mut ctx);
+    assert_eq!(ctx.current_line, 4);
+
+    ctx.current_line = 1;
+    crate::toggle_fold(&mut ctx);
+    assert_eq!(crate::fold_at(&ctx, 2), None);
+  }
+
+  #[test]
+  fn conflict_index() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+      "a\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>\nb\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+
+    let found: Vec<Option<usize>> = (0..ctx.result.len())
+      .map(|i| crate::conflict_index(&ctx, i))
+      .collect();
+    let expected: Vec<Option<usize>> = (0..ctx.result.len())
+      .map(|i| {
+        ctx
+          .conflicts
+          .iter()
+          .position(|conflict| (conflict.start..conflict.end).contains(&i))
+      })
+      .collect();
+
+    assert_eq!(found, expected);
+    assert_eq!(found[0], None);
+    assert_eq!(found[ctx.result.len() - 1], Some(1));
+  }
+
+  #[test]
+  fn context_regions() {
+    let mut ctx = crate::Context {
+      context_lines: 1,
+      ..Default::default()
+    };
+
+    crate::parse_input_file(
+      &format!(
+        "{}<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n{}<<<<<<<\nL2\n=======\nR2\n>>>>>>>\na\nb",
+        "a\n".repeat(5),
+        "a\n".repeat(4)
+      ),
+      &mut ctx,
+    );
+
+    // conflicts at 5..7 and 11..13
+    assert_eq!(crate::context_regions(&ctx), vec![0..4, 8..10]);
+
+    ctx.collapse_context = true;
+    assert_eq!(crate::fold_at(&ctx, 9), Some(8..10));
+    assert_eq!(crate::fold_summary(&ctx, 8..10), "… 2 unchanged lines …");
+
+    ctx.current_line = 9;
+    crate::toggle_fold(&mut ctx);
+    assert_eq!(crate::fold_at(&ctx, 9), None);
+  }
+
+  #[test]
+  fn tab_title() {
+    let mut ctx = crate::Context {
+      file_name: String::from("dir/a.txt"),
+      ..Default::default()
+    };
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>", &mut ctx);
+    assert_eq!(crate::tab_title(&ctx), "a.txt (1)");
+
+    crate::process_all(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::tab_title(&ctx), "a.txt ✓");
+  }
+
+  #[test]
+  fn tabs_from() {
+    let tabs: Vec<String> = ["aaaa", "bbbb", "cccc"].map(String::from).to_vec();
+    let first = |current: usize, width: usize| crate::tabs_from(&tabs, current, width)[0].0;
+
+    assert_eq!(first(0, 10), 0);
+    assert_eq!(first(2, 100), 0);
+    assert_eq!(first(2, 14), 1);
+    assert_eq!(first(2, 7), 2);
+  }
+
+  #[test]
+  fn progress() {
+    let file = || {
+      let mut ctx = crate::Context::default();
+      crate::parse_input_file(
+        "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+        &mut ctx,
+      );
+      ctx
+    };
+    let mut files = vec![file(), file(), file()];
+    crate::process_all(crate::Column::Left, &mut files[0]);
+
+    assert_eq!(
+      crate::progress(&files, 1),
+      "file 2/3, 2/6 conflicts resolved"
+    );
+  }
+
+  #[test]
+  fn picker_line() {
+    let mut ctx = crate::Context {
+      file_name: String::from("a.txt"),
+      ..Default::default()
+    };
+    crate::parse_input_file(
+      "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+    assert_eq!(crate::picker_line(&ctx), "  a.txt — 2/2 conflict(s) left");
+
+    crate::process_all(crate::Column::Left, &mut ctx);
+    assert_eq!(
+      crate::picker_line(&ctx),
+      "✓ a.txt — 2 conflict(s) resolved, not written"
+    );
+  }
+
+  #[test]
+  fn review_line() {
+    let mut ctx = crate::Context {
+      file_name: String::from("a.txt"),
+      ..Default::default()
+    };
+
+    crate::parse_input_file(
+      "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+
+    assert_eq!(
+      crate::review_line(&ctx),
+      "a.txt → a.txt: 2 conflict(s); 1 incoming; 1 unresolved; not written"
+    );
+  }
+
+  #[test]
+  fn pane_constraints() {
+    use tui::layout::Constraint::Percentage;
+
+    let mut ctx = crate::Context {
+      ratios: [30, 40, 30],
+      ..Default::default()
+    };
+    assert_eq!(
+      crate::pane_constraints(&ctx, 120),
+      [Percentage(30), Percentage(40), Percentage(30)]
+    );
+    assert_eq!(
+      crate::pane_constraints(&ctx, 80),
+      [Percentage(50), Percentage(0), Percentage(50)]
+    );
+
+    ctx.show_result = true;
+    assert_eq!(
+      crate::pane_constraints(&ctx, 80),
+      [Percentage(0), Percentage(100), Percentage(0)]
+    );
+
+    ctx.layout = crate::config::Layout::Three;
+    assert_eq!(
+      crate::pane_constraints(&ctx, 80),
+      [Percentage(30), Percentage(40), Percentage(30)]
+    );
+
+    ctx.zoom = true;
+    ctx.focus = 2;
+    assert_eq!(
+      crate::pane_constraints(&ctx, 80),
+      [Percentage(0), Percentage(0), Percentage(100)]
+    );
+  }
+
+  #[test]
+  fn files_under() {
+    let dir = std::env::temp_dir().join(format!("mersge-files-under-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub/.git")).unwrap();
+    std::fs::write(dir.join("a.txt"), "").unwrap();
+    std::fs::write(dir.join("sub/b.txt"), "").unwrap();
+    std::fs::write(dir.join("sub/.git/HEAD"), "").unwrap();
+    std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+    std::os::unix::fs::symlink(dir.join("a.txt"), dir.join("sub/c.txt")).unwrap();
+
+    // the link back up is not followed, the linked file is taken
+    let mut file_names = vec![];
+    crate::files_under(&dir, &mut file_names).unwrap();
+    let names: Vec<_> = file_names
+      .iter()
+      .map(|name| name.strip_prefix(dir.to_str().unwrap()).unwrap())
+      .collect();
+    assert_eq!(names, vec!["/a.txt", "/sub/b.txt", "/sub/c.txt"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn grow_pane() {
+    use crossterm::event::KeyCode;
+
+    let mut session = session(vec![conflicted("a.txt")]);
+    session.picker = None;
+    crate::handle_event(crate::key_event(KeyCode::Char('2')), &mut session);
+    crate::handle_event(crate::key_event(KeyCode::Char('>')), &mut session);
+
+    // kept for the config until the editor closes, not written on every step
+    let ratios = crate::resize_pane(crate::resize_pane([30, 40, 30], 0, 5), 0, 5);
+    assert_eq!(session.files[0].ratios, ratios);
+    assert_eq!(session.pane_ratios, Some(ratios));
+  }
+
+  #[test]
+  fn resize_pane() {
+    assert_eq!(crate::resize_pane([30, 40, 30], 1, 10), [25, 50, 25]);
+    assert_eq!(crate::resize_pane([30, 40, 30], 0, -5), [25, 40, 35]);
+    assert_eq!(crate::resize_pane([10, 80, 10], 1, 5), [10, 80, 10]);
+    assert_eq!(crate::resize_pane([10, 80, 10], 0, -5), [10, 80, 10]);
+  }
+
+  #[test]
+  fn wrap() {
+    assert_eq!(crate::wrap("", 3), vec![""]);
+    assert_eq!(crate::wrap("abcdefg", 3), vec!["abc", "def", "g"]);
+    assert_eq!(crate::wrap("čšř", 2), vec!["čš", "ř"]);
+    assert_eq!(crate::wrap("日本語", 5), vec!["日本", "語"]);
+    assert_eq!(
+      crate::wrap("e\u{301}e\u{301}", 1),
+      vec!["e\u{301}", "e\u{301}"]
+    );
+  }
+
+  #[test]
+  fn wrapped_offset() {
+    let rows = |i: usize| if i == 5 { 3 } else { 1 };
+
+    // lines 2..=6 take 7 rows
+    assert_eq!(crate::wrapped_offset(2, 6, 10, rows), 2);
+    assert_eq!(crate::wrapped_offset(2, 6, 5, rows), 4);
+    assert_eq!(crate::wrapped_offset(2, 1, 5, rows), 2);
+  }
+
+  #[test]
+  fn resolve_whitespace() {
+    let file = "fn a() {\n<<<<<<<\n    b();  \n=======\n\tb();\n>>>>>>>\n\tc();\n<<<<<<<\nd\n=======\ne\n>>>>>>>\n}\n";
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+
+    assert_eq!(
+      crate::resolve_whitespace(crate::config::Whitespace::Context, &mut ctx),
+      1
+    );
+    assert_eq!(crate::unresolved_conflicts(&ctx), 1);
+    assert_eq!(ctx.result[2].value, "\tb();");
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+    crate::resolve_whitespace(crate::config::Whitespace::Ours, &mut ctx);
+    assert_eq!(ctx.result[1].value, "    b();  ");
+  }
+
+  #[test]
+  fn context_json() {
+    let mut ctx = crate::Context {
+      file_name: String::from("a.txt"),
+      ..Default::default()
+    };
+    crate::parse_input_file("a\n<<<<<<< HEAD\nL\n=======\n>>>>>>> topic\n", &mut ctx);
+
+    let json = crate::json::print(&crate::context_json(&ctx), "").concat();
+    assert!(json.contains("\"local_label\": \"HEAD\",\"incoming_label\": \"topic\""));
+    assert!(json.contains(
+      "\"conflicts\": [{\"start\": 1,\"end\": 2,\"base\": null,\"resolution\": \"unresolved\"}]"
+    ));
+    assert!(json.contains(
+      "\"result\": [{\"change\": \"none\",\"value\": \"a\"},{\"change\": \"none\",\"value\": \"#\"}]"
+    ));
+    assert!(crate::json::parse(&json).is_ok());
+  }
+
+  #[test]
+  fn resolution_patch() {
+    let mut ctx = crate::Context {
+      file_name: String::from("a.txt"),
+      ..Default::default()
+    };
+    crate::parse_input_file(
+      "a\n<<<<<<<\nL\n|||||||\nB\n=======\nR\n>>>>>>>\nb",
+      &mut ctx,
+    );
+    crate::process_all(crate::Column::Right, &mut ctx);
+
+    assert_eq!(
+      crate::resolution_patch(&ctx, crate::PatchBase::Base).unwrap(),
+      vec![
+        "--- a/a.txt",
+        "+++ b/a.txt",
+        "@@ -1,3 +1,3 @@",
+        " a",
+        "-B",
+        "+R",
+        " b"
+      ]
+    );
+    assert_eq!(
+      crate::resolution_patch(&ctx, crate::PatchBase::Input).unwrap()[2],
+      "@@ -1,9 +1,3 @@"
+    );
+
+    ctx.original = vec![crate::text::Text::from("a")];
+    assert!(crate::resolution_patch(&ctx, crate::PatchBase::Base).is_err());
+  }
+
+  #[test]
+  fn audit_entries() {
+    let mut ctx = crate::Context {
+      file_name: String::from("a.txt"),
+      ..Default::default()
+    };
+    crate::parse_input_file(
+      "a\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+    crate::move_to(ctx.conflicts[0].start, &mut ctx);
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+
+    let entries = crate::audit_entries(&ctx);
+    assert_eq!(entries[0].lines, Some((2, 6)));
+    assert_eq!(entries[0].resolution, "incoming");
+    assert_eq!(entries[1].lines, Some((7, 11)));
+    assert_eq!(entries[1].resolution, "unresolved");
+    assert!(!entries[1].edited);
+  }
+
+  #[test]
+  fn resolve_from_reference() {
+    let conflict =
+      |local: &str, incoming: &str| format!("<<<<<<<\n{}\n=======\n{}\n>>>>>>>\n", local, incoming);
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(
+      &[
+        "a\n",
+        &conflict("L1", "R1"),
+        "b\n",
+        &conflict("L2", "R2"),
+        "c\n",
+        &conflict("L3", "R3"),
+        "d\n",
+        &conflict("L4", "R4"),
+        "e\n",
+      ]
+      .concat(),
+      &mut ctx,
+    );
+    let reference: Vec<String> = "a\nR1\nb\nL2\nR2\nc\nR3\nL3\nd\nX\ne"
+      .lines()
+      .map(String::from)
+      .collect();
+
+    assert_eq!(crate::resolve_from_reference(&reference, &mut ctx), 3);
+    assert_eq!(
+      crate::result_lines(&ctx),
+      vec![
+        "a", "R1", "b", "L2", "R2", "c", "R3", "L3", "d", "<<<<<<<", "L4", "=======", "R4",
+        ">>>>>>>", "e"
+      ]
+    );
+  }
+
+  #[test]
+  fn occurrences() {
+    let lines: Vec<String> = ["a", "b", "a", "b"].map(String::from).to_vec();
+
+    assert_eq!(crate::occurrences(&lines, &lines[..2]), 2);
+    assert_eq!(crate::occurrences(&lines, &lines[1..3]), 1);
+    assert_eq!(crate::occurrences(&lines, &[]), 0);
+  }
+
+  #[test]
+  fn identical_sides() {
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file("<<<<<<<\na  b\n=======\na b\n>>>>>>>\n", &mut ctx);
+
+    assert!(!crate::identical_sides(&ctx, &ctx.conflicts[0]));
+    ctx.ignore_whitespace = true;
+    assert!(crate::identical_sides(&ctx, &ctx.conflicts[0]));
+    assert!(crate::result_title(&ctx).ends_with("sides identical"));
+  }
+
+  #[test]
+  fn merge_json() {
+    let file = "{\r\n  \"a\": 1,\r\n<<<<<<< HEAD\r\n  \"b\": 2\r\n=======\r\n  \"b\": 3,\r\n  \"c\": 4\r\n>>>>>>> other\r\n}\r\n";
+
+    assert_eq!(
+      crate::merge_json(file, 7),
+      Ok((
+        String::from("{\r\n  \"a\": 1,\r\n<<<<<<< HEAD\r\n  \"b\": 2,\r\n=======\r\n  \"b\": 3,\r\n>>>>>>> other\r\n  \"c\": 4\r\n}\r\n"),
+        1
+      ))
+    );
+    assert!(crate::merge_json("<<<<<<<\n{\n=======\n[]\n>>>>>>>\n", 7).is_err());
+    assert!(crate::structured_merge("a.txt", file, 7).is_none());
+    assert!(crate::structured_merge("a.json", "{}", 7).is_none());
+  }
+
+  #[test]
+  fn conflict_list() {
+    let conflicts = crate::conflict_list(
+      "a\n<<<<<<< HEAD\nL\n||||||| base\nB\n=======\nR1\nR2\n>>>>>>> b\n",
+      7,
+    );
+
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!((conflicts[0].start, conflicts[0].end), (2, 9));
+    assert_eq!(
+      conflicts[0].labels,
+      [
+        Some(String::from("HEAD")),
+        Some(String::from("base")),
+        Some(String::from("b"))
+      ]
+    );
+    assert_eq!(conflicts[0].local, vec!["L"]);
+    assert_eq!(conflicts[0].base, Some(vec![String::from("B")]));
+    assert_eq!(conflicts[0].incoming, vec!["R1", "R2"]);
+    assert_eq!(
+      crate::json::print(&conflicts[0].to_json(), "").join(""),
+      "{\"start\": 2,\"end\": 9,\"local_label\": \"HEAD\",\"base_label\": \"base\",\"incoming_label\": \"b\",\"local\": [\"L\"],\"base\": [\"B\"],\"incoming\": [\"R1\",\"R2\"]}"
+    );
+  }
+
+  #[test]
+  fn scan() {
+    let dir = std::env::temp_dir().join(format!("mersge-scan-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/a.txt"), "<<<<<<<\nL\n=======\nR\n>>>>>>>\n").unwrap();
+    std::fs::write(dir.join("b.txt"), "b\n").unwrap();
+
+    let git = crate::git::backend("subprocess").unwrap();
+    let files = crate::scan(git.as_ref(), dir.to_str().unwrap()).unwrap();
+    assert_eq!(files, vec![dir.join("sub/a.txt").to_string_lossy()]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn conflict_lines() {
+    assert_eq!(
+      crate::conflict_lines(
+        "a\n<<<<<<< HEAD\nL\n=======\nR\n>>>>>>> b\nc\n<<<<<<<\n=======\n>>>>>>>\n"
+      ),
+      vec![2, 8]
+    );
+    assert!(crate::conflict_lines("Title\n=======\n").is_empty());
+  }
+
+  #[test]
+  fn driver_merge() {
+    let file = "a\n<<<<<<<\nL\n=======\nR\n>>>>>>>\n";
+
+    assert_eq!(
+      crate::driver_merge("cat \"$REMOTE\" > \"$MERGED\"", "a.txt", file, 7),
+      Some(Ok((String::from("a\nR\n"), 0)))
+    );
+    assert_eq!(
+      crate::driver_merge(
+        "printf '<<<<<<<\\nL\\n=======\\nR\\n>>>>>>>\\n' > \"$MERGED\"; exit 1",
+        "a.txt",
+        file,
+        7
+      ),
+      Some(Ok((String::from("<<<<<<<\nL\n=======\nR\n>>>>>>>\n"), 1)))
+    );
+    assert!(crate::driver_merge("exit 1", "a.txt", file, 7)
+      .unwrap()
+      .is_err());
+    assert_eq!(crate::driver_merge("exit 1", "a.txt", "a\n", 7), None);
+  }
+
+  #[test]
+  fn conflict_sides() {
+    let (local, base, incoming) =
+      crate::conflict_sides("a\n<<<<<<<\nL\n|||||||\nB\n=======\nR\n>>>>>>>\nb\n", 7);
+
+    assert_eq!(local, "a\nL\nb");
+    assert_eq!(base.as_deref(), Some("a\nB\nb"));
+    assert_eq!(incoming, "a\nR\nb");
+  }
+
+  #[test]
+  fn resolve_remaining() {
+    let file = "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>";
+    let values = |ctx: &crate::Context| -> Vec<String> {
+      ctx
+        .result
+        .iter()
+        .filter(|line| line.change != crate::Change::Deletion)
+        .map(|line| line.value.to_string())
+        .collect()
+    };
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+    crate::resolve_remaining(crate::Strategy::Ours, &mut ctx);
+    assert_eq!(values(&ctx), vec!["R1", "L2"]);
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+    crate::resolve_remaining(crate::Strategy::Theirs, &mut ctx);
+    assert_eq!(values(&ctx), vec!["R1", "R2"]);
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+    crate::resolve_remaining(crate::Strategy::Union, &mut ctx);
+    assert_eq!(values(&ctx), vec!["L1", "R1", "L2", "R2"]);
+    assert_eq!(crate::unresolved_conflicts(&ctx), 0);
+  }
+
+  #[test]
+  fn unresolve_line() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>\nafter", &mut ctx);
+
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::unresolved_conflicts(&ctx), 0);
+
+    crate::unresolve_line(1, &mut ctx);
+    assert_eq!(ctx.result[1].value, "#");
+    assert_eq!(ctx.result[1].change, crate::Change::None);
+    assert_eq!(crate::unresolved_conflicts(&ctx), 1);
+
+    // context lines are left alone
+    crate::unresolve_line(2, &mut ctx);
+    assert_eq!(ctx.result[2].value, "after");
+  }
+
+  #[test]
+  fn cycle_line() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>\nafter", &mut ctx);
+
+    crate::cycle_line(&mut ctx);
+    assert_eq!(ctx.result[0].value, "L1");
+    assert_eq!(ctx.result[0].change, crate::Change::Addition);
+
+    crate::cycle_line(&mut ctx);
+    assert_eq!(ctx.result[0].change, crate::Change::Deletion);
+
+    crate::cycle_line(&mut ctx);
+    assert_eq!(ctx.result[0].change, crate::Change::Addition);
+    assert_eq!(ctx.message.as_deref(), Some("Taking both"));
+
+    crate::cycle_line(&mut ctx);
+    assert_eq!(ctx.result[0].change, crate::Change::None);
+
+    crate::cycle_line(&mut ctx);
+    assert_eq!(ctx.result[0].value, "L1");
+
+    // starts over on another line
+    ctx.current_line = 1;
+    crate::cycle_line(&mut ctx);
+    assert_eq!(ctx.result[1].change, crate::Change::Deletion);
+    assert_eq!(ctx.message.as_deref(), Some("Taking local"));
+
+    // context lines have nothing to cycle through
+    ctx.current_line = 2;
+    crate::cycle_line(&mut ctx);
+    assert_eq!(ctx.result[2].value, "after");
+  }
+
+  #[test]
+  fn replace_conflict() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+      "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n<<<<<<<\nL2\n=======\nR2\n>>>>>>>",
+      &mut ctx,
+    );
+
+    crate::replace_conflict(0, vec![String::from("M1"), String::from("M2")], &mut ctx);
+
+    let values: Vec<&str> = ctx
+      .result
+      .iter()
+      .filter(|line| line.change != crate::Change::Deletion)
+      .map(|line| line.value.as_str())
+      .collect();
+    assert_eq!(values, vec!["M1", "M2", "#", "#"]);
+    assert_eq!((ctx.conflicts[0].start, ctx.conflicts[0].end), (0, 4));
+    assert_eq!((ctx.conflicts[1].start, ctx.conflicts[1].end), (4, 6));
+    assert_eq!(crate::unresolved_conflicts(&ctx), 1);
+  }
+
+  #[test]
+  fn merged_imports() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+        "<<<<<<<\nuse std::io;\nuse std::fs;\n=======\nuse std::env;\nuse std::io;\n>>>>>>>\n<<<<<<<\nuse a::{\n=======\nuse b;\n>>>>>>>",
+      &mut ctx,
+    );
+
+    assert_eq!(
+      crate::merged_imports(&ctx, &ctx.conflicts[0]),
+      Some(vec![
+        String::from("use std::env;"),
+        String::from("use std::fs;"),
+        String::from("use std::io;")
+      ])
+    );
+    assert_eq!(crate::merged_imports(&ctx, &ctx.conflicts[1]), None);
+  }
+
+  #[test]
+  fn marker_size() {
+    assert_eq!(crate::marker_size("a\n<<<<<<<<<< HEAD\nb"), 10);
+    assert_eq!(crate::marker_size("a\n<<< not a marker\nb"), 7);
+
+    assert!(crate::is_marker("<<<<<<<", '<', 7));
+    assert!(crate::is_marker("<<<<<<< HEAD", '<', 7));
+    assert!(!crate::is_marker("<<<<<<<< HEAD", '<', 7));
+    assert!(!crate::is_marker("=======text", '=', 7));
+  }
+
+  #[test]
+  fn parse_input_file_labels() {
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(
+      "<<<<<<< HEAD\nL1\n=======\nR1\n>>>>>>> feature/foo\n",
+      &mut ctx,
+    );
+
+    assert_eq!(
+      crate::side_title("Local changes", &ctx.local_label),
+      "Local changes (HEAD)"
+    );
+    assert_eq!(ctx.incoming_label.as_deref(), Some("feature/foo"));
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file("<<<<<<<\nL1\n=======\n>>>>>>>\n", &mut ctx);
+    assert_eq!(ctx.local_label, None);
+  }
+
+  #[test]
+  fn conflict_style() {
+    let file = "<<<<<<< working copy: 1a2b3c - ann: fix\nL1\n=======\nR1\n>>>>>>> merge rev:    4d5e6f - bob: feature\n";
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+
+    assert_eq!(ctx.style, crate::ConflictStyle::Mercurial);
+    assert_eq!(ctx.local_label.as_deref(), Some("working copy: 1a2b3c"));
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(
+      "<<<<<<< .mine\nL1\n||||||| .r1\nB\n=======\nR1\n>>>>>>> .r2\n",
+      &mut ctx,
+    );
+
+    assert_eq!(ctx.style, crate::ConflictStyle::Subversion);
+    assert_eq!(ctx.incoming_label.as_deref(), Some("r2"));
+    assert_eq!(ctx.conflicts[0].base, Some(vec![String::from("B")]));
+
+    assert_eq!(
+      crate::conflict_style(Some("HEAD"), Some("feature/foo")),
+      crate::ConflictStyle::Git
+    );
+  }
+
+  #[test]
+  fn parse_input_file_nested() {
+    let file = "<<<<<<<\nL1\n<<<<<<<\nA\n=======\nB\n>>>>>>>\n=======\nR1\n>>>>>>>\n";
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(file, &mut ctx);
+
+    let local: Vec<&str> = ctx
+      .local_changes
+      .iter()
+      .map(|line| line.value.as_str())
+      .collect();
+
+    assert_eq!(
+      local,
+      vec!["L1", "<<<<<<<", "A", "=======", "B", ">>>>>>>", "-"]
+    );
+    assert_eq!(ctx.conflicts.len(), 1);
+    assert_eq!(ctx.result.len(), ctx.incoming_changes.len());
+    assert!(crate::marker_problems(file, 7).is_empty());
+    assert_eq!(crate::nested_conflicts(file, 7), 1);
+  }
+
+  #[test]
+  fn parse_input_file_malformed() {
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file("=======\na\n<<<<<<<\nL1\n", &mut ctx);
+
+    assert_eq!(ctx.result[0].value, "=======");
+    assert_eq!(ctx.conflicts.len(), 1);
+    assert_eq!((ctx.conflicts[0].start, ctx.conflicts[0].end), (2, 3));
+  }
+
+  #[test]
+  fn marker_problems() {
+    assert!(crate::marker_problems("<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n", 7).is_empty());
+    assert_eq!(
+      crate::marker_problems("=======\n<<<<<<<\nL1\n>>>>>>>\n=======\n", 7),
+      vec![
+        "line 1: `=======` outside a conflict",
+        "line 4: unexpected `>>>>>>>` in the conflict from line 2",
+        "line 2: conflict is never closed",
+      ]
+    );
+  }
+
+  #[test]
+  fn parse_input_file_marker_size() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+      "<<<<<<<<< ours\n=======\n=========\n>>>>>>>>> theirs",
+      &mut ctx,
+    );
+
+    assert_eq!(ctx.marker_size, 9);
+    assert_eq!(ctx.local_changes[0].value, "=======");
+    assert_eq!(ctx.incoming_changes[0].change, crate::Change::Deletion);
+  }
+
+  #[test]
+  fn jump_to_first_conflict() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file(
+      &format!("{}<<<<<<<\nL1\n=======\nR1\n>>>>>>>", "a\n".repeat(10)),
+      &mut ctx,
+    );
+    crate::jump_to_first_conflict(&mut ctx);
+
+    assert_eq!((ctx.current_line, ctx.line_offset), (10, 7));
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file("a\nb", &mut ctx);
+    crate::jump_to_first_conflict(&mut ctx);
+
+    assert_eq!((ctx.current_line, ctx.line_offset), (0, 0));
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn write_atomic() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("mersge-atomic-{}", std::process::id()));
+    let path = dir.join("script.sh");
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&path, "old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o754)).unwrap();
+
+    crate::write_atomic(&path, b"new").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+    assert_eq!(
+      std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+      0o754
+    );
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    // names already taken, here by links someone else left, are skipped and not written through
+    let victim = dir.join("victim");
+    std::fs::write(&victim, "kept").unwrap();
+    for count in 0..200 {
+      let name = format!(".script.sh.mersge-{}-{}", std::process::id(), count);
+      std::os::unix::fs::symlink(&victim, dir.join(name)).unwrap();
+    }
+
+    crate::write_atomic(&path, b"newer").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "newer");
+    assert_eq!(std::fs::read_to_string(&victim).unwrap(), "kept");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn file_content() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file("a\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>", &mut ctx);
+    ctx.current_line = 1;
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::file_content(&ctx), "a\nL1");
+
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file("a\nb\n", &mut ctx);
+    assert_eq!(crate::file_content(&ctx), "a\nb\n");
+  }
+
+  #[test]
+  fn write_file() {
+    let path = std::env::temp_dir().join(format!("mersge-write-{}", std::process::id()));
+    let mut ctx = crate::Context {
+      file_name: String::from(path.to_str().unwrap()),
+      ..Default::default()
+    };
+
+    crate::parse_input_file(
+      "a\r\n<<<<<<<\r\nL1\r\n=======\r\nR1\r\n>>>>>>>\r\n",
+      &mut ctx,
+    );
+    ctx.current_line = 1;
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+    crate::write_file(&mut ctx);
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\r\nR1\r\n");
+
+    let mut ctx = crate::Context {
+      file_name: String::from(path.to_str().unwrap()),
+      ..Default::default()
+    };
+
+    crate::parse_input_file("\u{feff}a\nb\n", &mut ctx);
+    assert_eq!(ctx.result[0].value, "a");
+
+    crate::write_file(&mut ctx);
+    assert_eq!(std::fs::read(&path).unwrap(), b"\xef\xbb\xbfa\nb\n");
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn write_file_error() {
+    let mut ctx = crate::Context {
+      file_name: String::from("missing-directory/a.txt"),
+      modified: true,
+      ..Default::default()
+    };
+
+    crate::parse_input_file("a\n", &mut ctx);
+    crate::write_file(&mut ctx);
+
+    assert!(ctx.modified);
+    assert_eq!(ctx.popup.as_ref().unwrap().title, "Error");
+    assert!(crate::save(&mut ctx).is_err());
+  }
+
+  #[test]
+  fn audit_log_error() {
+    let path = std::env::temp_dir().join(format!("mersge-audit-error-{}", std::process::id()));
+    let mut ctx = crate::Context {
+      file_name: String::from(path.to_str().unwrap()),
+      audit_log: Some(String::from("missing-directory/audit.log")),
+      modified: true,
+      ..Default::default()
+    };
 
-        // left column
-        let span_style = match ctx.local_changes[i].change {
-          Change::None => style,
-          Change::Addition => style.patch(add_style),
-          Change::Deletion => style.patch(remove_style),
-        };
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n", &mut ctx);
+    crate::process_conflict(crate::Column::Right, &mut ctx);
 
-        let span_content = pad(
-          ctx.local_changes[i].value.clone(),
-          columns[0].width as usize,
-        );
+    // the file is written all the same, only the log is missing
+    assert!(matches!(
+      crate::save(&mut ctx),
+      Ok(Some(crate::error::Error::AuditLog(..)))
+    ));
+    assert!(!ctx.modified);
+    assert!(ctx.written);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "R1\n");
 
-        local_changes.push(Spans::from(Span::styled(
-          String::from(span_content),
-          span_style,
-        )));
+    crate::write_file(&mut ctx);
+    assert_eq!(ctx.popup.as_ref().unwrap().title, "Warning");
 
-        // right column
-        let span_style = match ctx.incoming_changes[i].change {
-          Change::None => style,
-          Change::Addition => style.patch(add_style),
-          Change::Deletion => style.patch(remove_style),
-        };
+    std::fs::remove_file(&path).unwrap();
+  }
 
-        let span_content = pad(
-          ctx.incoming_changes[i].value.clone(),
-          columns[2].width as usize,
-        );
+  #[test]
+  fn output() {
+    let path = std::env::temp_dir().join(format!("mersge-output-{}", std::process::id()));
+    let mut ctx = crate::Context {
+      file_name: String::from("input-is-not-written"),
+      output: Some(String::from(path.to_str().unwrap())),
+      ..Default::default()
+    };
 
-        incoming_changes.push(Spans::from(Span::styled(
-          String::from(span_content),
-          span_style,
-        )));
-      }
+    crate::parse_input_file("<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n", &mut ctx);
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+    crate::write_file(&mut ctx);
 
-      // middle column can have deleted lines, so handle them differently
-      let mut i = line_from;
-      let mut line_to = line_to;
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "R1\n");
+    assert!(!std::path::Path::new("input-is-not-written").exists());
 
-      loop {
-        let mut style = Style::default();
+    std::fs::remove_file(&path).unwrap();
+  }
 
-        if i == ctx.current_line {
-          style = style.patch(current_line_style);
-        }
+  #[test]
+  fn backup() {
+    let path = std::env::temp_dir().join(format!("mersge-backup-{}", std::process::id()));
+    let backup = format!("{}.orig", path.display());
+    let file = "<<<<<<<\nL1\n=======\nR1\n>>>>>>>\n";
+    let mut ctx = crate::Context {
+      file_name: String::from(path.to_str().unwrap()),
+      backup: true,
+      ..Default::default()
+    };
 
-        let span_style = match ctx.result[i].change {
-          Change::None => style,
-          Change::Addition => style.patch(add_style),
-          Change::Deletion => style.patch(remove_style),
-        };
+    std::fs::write(&path, file).unwrap();
+    crate::parse_input_file(file, &mut ctx);
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    crate::write_file(&mut ctx);
+    crate::write_file(&mut ctx);
 
-        let span_content = pad(ctx.result[i].value.clone(), columns[1].width as usize);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "L1\n");
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), file);
 
-        if ctx.result[i].change == Change::Deletion {
-          line_to += 1;
-        } else {
-          result.push(Spans::from(Span::styled(
-            String::from(span_content),
-            span_style,
-          )));
-        }
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&backup).unwrap();
+  }
 
-        i += 1;
+  #[test]
+  fn is_crlf() {
+    assert!(crate::is_crlf("a\r\nb\r\nc\n"));
+    assert!(!crate::is_crlf("a\nb\r\nc\n"));
+    assert!(!crate::is_crlf("a"));
+  }
 
-        if i >= ctx.result.len() || i == line_to {
-          break;
-        }
-      }
+  #[test]
+  fn conflict_density() {
+    let mut ctx = crate::Context::default();
 
-      let row_top = Block::default();
-      let row_bottom = Block::default().borders(Borders::ALL);
-
-      let block_left = Block::default()
-        .title("Local changes")
-        .borders(Borders::ALL);
-
-      let block_middle = Block::default().title("Result").borders(Borders::ALL);
-      let block_right = Block::default()
-        .title("Incoming changes")
-        .borders(Borders::ALL);
-
-      let text_left = Paragraph::new(local_changes).block(block_left);
-      let text_middle = Paragraph::new(result).block(block_middle);
-      let text_right = Paragraph::new(incoming_changes).block(block_right);
-
-      let controls = Paragraph::new(vec![Spans::from(vec![
-        Span::styled("[Up] ", control_style),
-        Span::from("Move up "),
-        Span::styled("[Down] ", control_style),
-        Span::from("Move down "),
-        Span::styled("[L] ", control_style),
-        Span::from("Accept local "),
-        Span::styled("[R] ", control_style),
-        Span::from("Accept incoming "),
-        Span::styled("[W] ", control_style),
-        Span::from("Write "),
-        Span::styled("[Q] ", control_style),
-        Span::from("Quit "),
-      ])])
-      .block(row_bottom);
-
-      frame.render_widget(row_top, rows[0]);
-      frame.render_widget(controls, rows[1]);
-
-      frame.render_widget(text_left, columns[0]);
-      frame.render_widget(text_middle, columns[1]);
-      frame.render_widget(text_right, columns[2]);
-    })
-    .unwrap();
-}
+    crate::parse_input_file("a\nb\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>\nc", &mut ctx);
+    assert_eq!(crate::conflict_density(&ctx), (5, 8));
+  }
 
-fn handle_events(ctx: &mut Context) -> bool {
-  let mut is_running = true;
+  #[test]
+  fn summary_lines() {
+    let mut files = vec![];
 
-  match event::read().unwrap() {
-    event::Event::Key(event) => {
-      match event.code {
-        event::KeyCode::Char('q') => is_running = false,
-        event::KeyCode::Char('l') => process_change(Column::Left, ctx),
-        event::KeyCode::Char('r') => process_change(Column::Right, ctx),
-        event::KeyCode::Char('w') => write_file(ctx),
-        event::KeyCode::Down => move_down(ctx),
-        event::KeyCode::Up => move_up(ctx),
-        _ => (),
+    for (file_name, content) in [
+      ("calm", "a\nb\nc\n<<<<<<<\nL1\n=======\nR1\n>>>>>>>"),
+      ("hot", "<<<<<<<\nL1\n=======\nR1\n>>>>>>>"),
+    ] {
+      let mut ctx = crate::Context {
+        file_name: String::from(file_name),
+        ..Default::default()
       };
+      crate::parse_input_file(content, &mut ctx);
+      files.push(ctx);
     }
 
-    event::Event::Mouse(_) => {}
+    let text = |spans: &tui::text::Spans| -> String {
+      spans.0.iter().map(|span| span.content.as_ref()).collect()
+    };
+    let lines = crate::summary_lines(&files);
 
-    event::Event::Resize(_, _) => {}
-  };
+    assert_eq!(text(&lines[0]), "100% hot (5/5 lines, 1 unresolved)");
+    assert_eq!(text(&lines[1]), " 62% calm (5/8 lines, 1 unresolved)");
+  }
 
-  return is_running;
-}
+  #[test]
+  fn diffstat() {
+    let mut ctx = crate::Context::default();
+    crate::parse_input_file(
+      "a\n<<<<<<<\nL1\nL2\n=======\nR1\n>>>>>>>\n<<<<<<<\nX\n=======\nY\n>>>>>>>",
+      &mut ctx,
+    );
+    ctx.current_line = 1;
+    crate::process_conflict(crate::Column::Left, &mut ctx);
 
-fn process_change(column: Column, ctx: &mut Context) {
-  let line: &Line = match column {
-    Column::Left => Some(&ctx.local_changes[ctx.current_line]),
-    Column::Right => Some(&ctx.incoming_changes[ctx.current_line]),
-    _ => None,
+    assert_eq!(
+      crate::diffstat(&ctx),
+      crate::Diffstat {
+        local: 2,
+        incoming: 0,
+        edited: 0,
+        deleted: 1,
+        unresolved: 2,
+      }
+    );
+
+    let text: Vec<String> = crate::diffstat_lines(&ctx)
+      .iter()
+      .map(|spans| spans.0.iter().map(|span| span.content.as_ref()).collect())
+      .collect();
+    assert_eq!(text[0], "1/2 conflicts resolved");
+    assert_eq!(text[2], "Local           2 lines");
+    assert_eq!(text[5], "Deleted         1 line");
   }
-  .unwrap();
 
-  match line.change {
-    Change::Addition => {
-      ctx.result[ctx.current_line].value = line.value.clone();
-      ctx.result[ctx.current_line].change = Change::Addition;
-    }
-    Change::Deletion => {
-      ctx.result[ctx.current_line].change = Change::Deletion;
-    }
-    Change::None => (),
-  };
-}
+  #[test]
+  fn sections() {
+    use crate::Section::*;
 
-fn write_file(ctx: &Context) {
-  let mut content = String::new();
+    let lines = "a\n<<<<<<<\nL\n|||||||\nB\n=======\nR\n>>>>>>>\n=======\nb".lines();
 
-  for i in 0..ctx.result.len() {
-    if ctx.result[i].change != Change::Deletion {
-      content.push_str(ctx.result[i].value.clone().as_str());
-      content.push('\n');
-    }
+    assert_eq!(
+      crate::sections(lines, 7),
+      vec![Outside, Marker, Local, Marker, Base, Marker, Incoming, Marker, Outside, Outside]
+    );
   }
 
-  std::fs::write(&ctx.file_name, content).unwrap();
-}
+  #[test]
+  fn conflict_regions() {
+    let lines = "a\n<<<<<<<\nb\n=======\nc\n>>>>>>>\nd\n>>>>>>>".lines();
 
-fn move_down(ctx: &mut Context) {
-  if ctx.current_line < ctx.result.len() - 1 {
-    ctx.current_line += 1;
+    assert_eq!(
+      crate::conflict_regions(lines, 7),
+      vec![false, true, true, true, true, true, false, false]
+    );
   }
 
-  if ctx.current_line > ctx.line_offset + ctx.column_height - 1 {
-    ctx.line_offset += 1;
-  }
-}
+  #[test]
+  fn result_lines() {
+    let mut ctx = crate::Context::default();
 
-fn move_up(ctx: &mut Context) {
-  if ctx.current_line > 0 {
-    ctx.current_line -= 1;
-  }
+    crate::parse_input_file(
+        "a\n<<<<<<<<\nL1\nL2\n||||||||\nB1\n========\nR1\n>>>>>>>>\n<<<<<<<<\nL3\n========\nR3\n>>>>>>>>",
+      &mut ctx,
+    );
 
-  if ctx.current_line < ctx.line_offset {
-    ctx.line_offset -= 1;
-  }
-}
+    assert_eq!(
+      crate::result_lines(&ctx),
+      vec![
+        "a", "<<<<<<<<", "L1", "L2", "||||||||", "B1", "========", "R1", ">>>>>>>>", "<<<<<<<<",
+        "L3", "========", "R3", ">>>>>>>>"
+      ]
+    );
 
-fn pad(mut string: String, len: usize) -> String {
-  loop {
-    if string.len() >= len {
-      break;
-    }
-    string.push(' ');
-  }
+    // partially resolved conflicts keep only the undecided lines between markers
+    ctx.current_line = 1;
+    crate::process_change(crate::Column::Left, &mut ctx);
+    ctx.current_line = 4;
+    crate::process_conflict(crate::Column::Right, &mut ctx);
 
-  string
-}
+    assert_eq!(
+      crate::result_lines(&ctx),
+      vec!["a", "L1", "<<<<<<<<", "L2", "========", "R1", ">>>>>>>>", "R3"]
+    );
+  }
 
-#[cfg(test)]
-mod tests {
   #[test]
-  fn parse_input_file() {
-    let mut ctx = crate::Context {
-      file_name: String::new(),
-      local_changes: vec![],
-      incoming_changes: vec![],
-      result: vec![],
-      current_line: 0,
-      line_offset: 0,
-      column_height: 0,
-    };
+  fn search_next() {
+    let mut ctx = crate::Context::default();
 
-    let file = String::from("before\n<<<<<<<\nabcd\nefgh\n=======\nijkl\n>>>>>>>\nafter");
+    crate::parse_input_file(
+      "foo\n<<<<<<<\nbar\n=======\nfoo bar\n>>>>>>>\nbaz",
+      &mut ctx,
+    );
 
-    crate::parse_input_file(file, &mut ctx);
+    ctx.search = Some(String::from("bar"));
 
-    assert_eq!(ctx.local_changes.len(), 5);
-    assert_eq!(ctx.local_changes[0].value, "before");
-    assert_eq!(ctx.local_changes[0].change, crate::Change::None);
-    assert_eq!(ctx.local_changes[1].value, "abcd");
-    assert_eq!(ctx.local_changes[1].change, crate::Change::Addition);
-    assert_eq!(ctx.local_changes[2].value, "efgh");
-    assert_eq!(ctx.local_changes[2].change, crate::Change::Addition);
-    assert_eq!(ctx.local_changes[3].value, "-");
-    assert_eq!(ctx.local_changes[3].change, crate::Change::Deletion);
-    assert_eq!(ctx.local_changes[4].value, "after");
-    assert_eq!(ctx.local_changes[4].change, crate::Change::None);
+    crate::search_next(&mut ctx, true);
+    assert_eq!(ctx.current_line, 1);
 
-    assert_eq!(ctx.result.len(), 5);
-    assert_eq!(ctx.result[0].value, "before");
-    assert_eq!(ctx.result[0].change, crate::Change::None);
-    assert_eq!(ctx.result[1].value, "#");
-    assert_eq!(ctx.result[1].change, crate::Change::None);
-    assert_eq!(ctx.result[2].value, "#");
-    assert_eq!(ctx.result[2].change, crate::Change::None);
-    assert_eq!(ctx.result[3].value, "#");
-    assert_eq!(ctx.result[3].change, crate::Change::None);
-    assert_eq!(ctx.result[4].value, "after");
-    assert_eq!(ctx.result[4].change, crate::Change::None);
+    crate::search_next(&mut ctx, true);
+    assert_eq!(ctx.current_line, 2);
 
-    assert_eq!(ctx.incoming_changes.len(), 5);
-    assert_eq!(ctx.incoming_changes[0].value, "before");
-    assert_eq!(ctx.incoming_changes[0].change, crate::Change::None);
-    assert_eq!(ctx.incoming_changes[1].value, "-");
-    assert_eq!(ctx.incoming_changes[1].change, crate::Change::Deletion);
-    assert_eq!(ctx.incoming_changes[2].value, "-");
-    assert_eq!(ctx.incoming_changes[2].change, crate::Change::Deletion);
-    assert_eq!(ctx.incoming_changes[3].value, "ijkl");
-    assert_eq!(ctx.incoming_changes[3].change, crate::Change::Addition);
-    assert_eq!(ctx.incoming_changes[4].value, "after");
-    assert_eq!(ctx.incoming_changes[4].change, crate::Change::None);
+    crate::search_next(&mut ctx, true);
+    assert_eq!(ctx.current_line, 1);
+
+    crate::search_next(&mut ctx, false);
+    assert_eq!(ctx.current_line, 2);
+
+    ctx.search = Some(String::from("qux"));
+    crate::search_next(&mut ctx, true);
+    assert_eq!(ctx.current_line, 2);
+    assert_eq!(ctx.message.as_deref(), Some("Pattern not found: qux"));
   }
 
   #[test]
-  fn process_change() {
+  fn goto_line() {
     let mut ctx = crate::Context {
-      file_name: String::new(),
-      local_changes: vec![
-        crate::Line {
-          value: String::from("L1"),
-          change: crate::Change::Addition,
-        },
-        crate::Line {
-          value: String::from("L2"),
-          change: crate::Change::Addition,
-        },
-      ],
-      incoming_changes: vec![
-        crate::Line {
-          value: String::from("R1"),
-          change: crate::Change::Deletion,
-        },
-        crate::Line {
-          value: String::from("R2"),
-          change: crate::Change::Addition,
-        },
-      ],
-      result: vec![
-        crate::Line {
-          value: String::new(),
+      result: (0..10)
+        .map(|_| crate::Line {
+          value: crate::text::Text::default(),
           change: crate::Change::None,
-        },
-        crate::Line {
-          value: String::new(),
-          change: crate::Change::None,
-        },
-      ],
-      current_line: 0,
-      line_offset: 0,
-      column_height: 0,
+        })
+        .collect(),
+      column_height: 4,
+      ..Default::default()
     };
 
-    crate::process_change(crate::Column::Right, &mut ctx);
-    assert_eq!(ctx.result[0].value, "");
-    assert_eq!(ctx.result[0].change, crate::Change::Deletion);
+    crate::goto_line("7", &mut ctx);
+    assert_eq!((ctx.current_line, ctx.line_offset), (6, 3));
 
-    ctx.current_line = 1;
+    crate::goto_line("1", &mut ctx);
+    assert_eq!(ctx.current_line, 0);
 
-    crate::process_change(crate::Column::Left, &mut ctx);
-    assert_eq!(ctx.result[1].value, "L2");
-    assert_eq!(ctx.result[1].change, crate::Change::Addition);
+    crate::goto_line("99", &mut ctx);
+    assert_eq!(ctx.current_line, 9);
+
+    crate::goto_line("x", &mut ctx);
+    assert_eq!(ctx.current_line, 9);
+    assert!(ctx.message.is_some());
+  }
+
+  #[test]
+  fn permalink() {
+    assert_eq!(
+      crate::permalink(
+        "https://example.com/blob/{commit}/{path}#L{line}",
+        "src/main.rs",
+        42,
+        "abc123"
+      ),
+      "https://example.com/blob/abc123/src/main.rs#L42"
+    );
   }
 
   #[test]
@@ -512,17 +7608,18 @@ mod tests {
       incoming_changes: vec![],
       result: vec![
         crate::Line {
-          value: String::new(),
+          value: crate::text::Text::default(),
           change: crate::Change::None,
         },
         crate::Line {
-          value: String::new(),
+          value: crate::text::Text::default(),
           change: crate::Change::None,
         },
       ],
       current_line: 0,
       line_offset: 0,
       column_height: 10,
+      ..Default::default()
     };
 
     crate::move_down(&mut ctx);
@@ -540,17 +7637,18 @@ mod tests {
       incoming_changes: vec![],
       result: vec![
         crate::Line {
-          value: String::new(),
+          value: crate::text::Text::default(),
           change: crate::Change::None,
         },
         crate::Line {
-          value: String::new(),
+          value: crate::text::Text::default(),
           change: crate::Change::None,
         },
       ],
       current_line: 1,
       line_offset: 0,
       column_height: 0,
+      ..Default::default()
     };
 
     crate::move_up(&mut ctx);
@@ -568,21 +7666,22 @@ mod tests {
       incoming_changes: vec![],
       result: vec![
         crate::Line {
-          value: String::new(),
+          value: crate::text::Text::default(),
           change: crate::Change::None,
         },
         crate::Line {
-          value: String::new(),
+          value: crate::text::Text::default(),
           change: crate::Change::None,
         },
         crate::Line {
-          value: String::new(),
+          value: crate::text::Text::default(),
           change: crate::Change::None,
         },
       ],
       current_line: 0,
       line_offset: 0,
       column_height: 2,
+      ..Default::default()
     };
 
     crate::move_down(&mut ctx);
@@ -611,6 +7710,220 @@ mod tests {
     assert_eq!(ctx.line_offset, 0);
   }
 
+  #[test]
+  fn page() {
+    let mut ctx = crate::Context {
+      result: (0..10)
+        .map(|_| crate::Line {
+          value: crate::text::Text::default(),
+          change: crate::Change::None,
+        })
+        .collect(),
+      column_height: 4,
+      ..Default::default()
+    };
+
+    crate::page_down(&mut ctx);
+    assert_eq!((ctx.current_line, ctx.line_offset), (4, 1));
+
+    crate::page_down(&mut ctx);
+    crate::page_down(&mut ctx);
+    assert_eq!((ctx.current_line, ctx.line_offset), (9, 6));
+
+    crate::page_up(&mut ctx);
+    assert_eq!((ctx.current_line, ctx.line_offset), (5, 5));
+
+    crate::move_to(0, &mut ctx);
+    assert_eq!((ctx.current_line, ctx.line_offset), (0, 0));
+
+    crate::move_to(100, &mut ctx);
+    assert_eq!((ctx.current_line, ctx.line_offset), (9, 6));
+  }
+
+  #[test]
+  fn scroll_wheel() {
+    let mut ctx = crate::Context {
+      result: (0..10)
+        .map(|_| crate::Line {
+          value: crate::text::Text::default(),
+          change: crate::Change::None,
+        })
+        .collect(),
+      column_height: 4,
+      ..Default::default()
+    };
+
+    crate::scroll_down(&mut ctx, 3);
+    assert_eq!(ctx.line_offset, 3);
+    assert_eq!(ctx.current_line, 3);
+
+    crate::scroll_down(&mut ctx, 3);
+    assert_eq!(ctx.line_offset, 6);
+    assert_eq!(ctx.current_line, 6);
+
+    ctx.current_line = 9;
+
+    crate::scroll_up(&mut ctx, 3);
+    assert_eq!(ctx.line_offset, 3);
+    assert_eq!(ctx.current_line, 6);
+
+    crate::scroll_up(&mut ctx, 3);
+    crate::scroll_up(&mut ctx, 3);
+    assert_eq!(ctx.line_offset, 0);
+    assert_eq!(ctx.current_line, 3);
+  }
+
+  #[test]
+  fn click() {
+    let mut ctx = crate::Context {
+      result: vec![
+        crate::Line {
+          value: crate::text::Text::default(),
+          change: crate::Change::None,
+        },
+        crate::Line {
+          value: crate::text::Text::default(),
+          change: crate::Change::Deletion,
+        },
+        crate::Line {
+          value: crate::text::Text::default(),
+          change: crate::Change::None,
+        },
+      ],
+      line_offset: 1,
+      column_rects: [
+        tui::layout::Rect::new(0, 0, 10, 5),
+        tui::layout::Rect::new(10, 0, 10, 5),
+        tui::layout::Rect::new(20, 0, 10, 5),
+      ],
+      result_rows: vec![0, 2],
+      side_rows: vec![1, 2],
+      ..Default::default()
+    };
+
+    crate::click(5, 1, &mut ctx);
+    assert_eq!(ctx.current_line, 1);
+
+    crate::click(15, 2, &mut ctx);
+    assert_eq!(ctx.current_line, 2);
+
+    // border
+    crate::click(10, 1, &mut ctx);
+    assert_eq!(ctx.current_line, 2);
+
+    // past the end of the file
+    crate::click(25, 3, &mut ctx);
+    assert_eq!(ctx.current_line, 2);
+  }
+
+  #[test]
+  fn removed_run() {
+    let mut ctx = crate::Context::default();
+
+    crate::parse_input_file("<<<<<<<\nL1\nL2\n=======\nR1\n>>>>>>>\nafter", &mut ctx);
+
+    crate::process_conflict(crate::Column::Left, &mut ctx);
+    assert_eq!(crate::removed_run(&ctx, 2), 3);
+
+    crate::process_conflict(crate::Column::Right, &mut ctx);
+    assert_eq!(crate::removed_run(&ctx, 0), 2);
+    assert_eq!(crate::removed_run(&ctx, 1), 2);
+  }
+
+  #[test]
+  fn guide() {
+    assert_eq!(
+      crate::guide(String::from("abc  "), None),
+      (String::from("abc  "), None)
+    );
+    assert_eq!(
+      crate::guide(String::from("abc  "), Some(10)),
+      (String::from("abc  "), None)
+    );
+    assert_eq!(
+      crate::guide(String::from("abc  "), Some(1)),
+      (String::from("abc  "), Some(1..2))
+    );
+    assert_eq!(
+      crate::guide(String::from("日本  "), Some(4)),
+      (String::from("日本│ "), Some(2..3))
+    );
+    assert_eq!(
+      crate::guide(String::from("abc  "), Some(3)),
+      (String::from("abc│ "), Some(3..4))
+    );
+  }
+
+  #[test]
+  fn matches() {
+    assert_eq!(crate::matches("Foo foo", "foo"), vec![0..3, 4..7]);
+    assert_eq!(crate::matches("Foo foo", "Foo"), vec![0..3]);
+    assert_eq!(crate::matches("aaaa", "aa"), vec![0..2, 2..4]);
+    assert_eq!(crate::matches("ä ä", "Ä"), vec![]);
+    assert_eq!(crate::matches("abc", ""), vec![]);
+  }
+
+  #[test]
+  fn styled() {
+    let style = tui::style::Style::default();
+    let overlay = style.fg(tui::style::Color::Red);
+
+    let spans = crate::styled(
+      String::from("abcdef"),
+      style,
+      &[(1..3, overlay), (2..4, overlay)],
+    );
+    let contents: Vec<&str> = spans.0.iter().map(|span| span.content.as_ref()).collect();
+    assert_eq!(contents, vec!["a", "bcd", "ef"]);
+    assert_eq!(spans.0[1].style, overlay);
+
+    let spans = crate::styled(
+      String::from("čšř │"),
+      style,
+      &[(1..2, overlay), (4..5, overlay)],
+    );
+    let contents: Vec<&str> = spans.0.iter().map(|span| span.content.as_ref()).collect();
+    assert_eq!(contents, vec!["č", "š", "ř ", "│"]);
+
+    let spans = crate::styled(String::from("abc"), overlay, &[]);
+    assert_eq!(spans.0.len(), 1);
+    assert_eq!(spans.0[0].style, overlay);
+  }
+
+  #[test]
+  fn take_row() {
+    let mut rows = vec![String::from("ab"), String::from("c")];
+
+    assert_eq!(crate::take_row(&mut rows, 1), "c");
+    assert_eq!(crate::take_row(&mut rows, 1), "");
+    assert_eq!(crate::take_row(&mut rows, 5), "");
+    assert_eq!(rows[0], "ab");
+  }
+
+  #[test]
+  fn control_at() {
+    let ctx = crate::Context {
+      control_rects: vec![
+        (tui::layout::Rect::new(1, 5, 4, 1), crate::Action::MoveUp),
+        (tui::layout::Rect::new(5, 5, 6, 1), crate::Action::Quit),
+      ],
+      ..Default::default()
+    };
+
+    assert_eq!(crate::control_at(1, 5, &ctx), Some(crate::Action::MoveUp));
+    assert_eq!(crate::control_at(5, 5, &ctx), Some(crate::Action::Quit));
+    assert_eq!(crate::control_at(11, 5, &ctx), None);
+    assert_eq!(crate::control_at(1, 4, &ctx), None);
+  }
+
+  #[test]
+  fn expand_tabs() {
+    assert_eq!(crate::expand_tabs("a\tb", 4), "a   b");
+    assert_eq!(crate::expand_tabs("\t\tx", 2), "    x");
+    assert_eq!(crate::expand_tabs("abcd\tx", 4), "abcd    x");
+    assert_eq!(crate::expand_tabs("日\tx", 4), "日  x");
+  }
+
   #[test]
   fn pad() {
     let mut s1 = String::from("ABC");
@@ -620,5 +7933,8 @@ mod tests {
 
     s1 = crate::pad(s1, 3);
     assert_eq!(s1.len(), 5);
+
+    assert_eq!(crate::pad(String::from("日本"), 6), "日本  ");
+    assert_eq!(crate::pad(String::from("é"), 3), "é  ");
   }
 }
@@ -0,0 +1,498 @@
+// YAML and TOML documents as keyed entries, each with the lines it was written with (the
+// comments and blank lines above it included) and the entries nested under it; values are
+// compared as text, only mappings and tables are merged key by key
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+  pub key: String,
+  pub lines: Vec<String>,
+  pub children: Vec<Entry>,
+}
+
+// result of merging one entry, conflicts hold what each side has (None when it deleted it)
+#[derive(Debug, PartialEq)]
+pub enum Merged {
+  Entry(Entry),
+  Parent(Vec<String>, Vec<Merged>),
+  Conflict(Option<Entry>, Option<Entry>),
+}
+
+// comments and blank lines after the last entry
+const TRAILER: &str = "";
+
+fn is_blank_or_comment(line: &str) -> bool {
+  let line = line.trim();
+  line.is_empty() || line.starts_with('#')
+}
+
+fn indentation(line: &str) -> usize {
+  line.len() - line.trim_start_matches(' ').len()
+}
+
+fn trailer(entries: &mut Vec<Entry>, pending: Vec<String>) {
+  if !pending.is_empty() {
+    entries.push(Entry {
+      key: String::from(TRAILER),
+      lines: pending,
+      children: vec![],
+    });
+  }
+}
+
+// keys seen before get `#2`, `#3`, ... so repeated `[[table]]` headers stay apart
+fn number_duplicates(mut entries: Vec<Entry>) -> Vec<Entry> {
+  let mut seen: Vec<String> = vec![];
+
+  for entry in &mut entries {
+    let count = seen.iter().filter(|key| **key == entry.key).count();
+    seen.push(entry.key.clone());
+
+    if count > 0 {
+      entry.key = format!("{}#{}", entry.key, count + 1);
+    }
+  }
+
+  entries
+}
+
+// block mappings only, flow style, anchors in keys and more than one document are refused
+pub fn parse_yaml(text: &str) -> Result<Vec<Entry>, String> {
+  let lines: Vec<&str> = text.lines().collect();
+  let mut i = 0;
+  let mut pending = vec![];
+
+  if lines.first().map(|line| line.trim_end()) == Some("---") {
+    pending.push(String::from(lines[0]));
+    i = 1;
+  }
+
+  let mut entries = yaml_block(&lines, &mut i, 0, &mut pending)?;
+
+  if i < lines.len() {
+    return Err(format!("line {}: unexpected indentation", i + 1));
+  }
+
+  trailer(&mut entries, pending);
+  Ok(entries)
+}
+
+fn yaml_block(
+  lines: &[&str],
+  i: &mut usize,
+  indent: usize,
+  pending: &mut Vec<String>,
+) -> Result<Vec<Entry>, String> {
+  let mut entries = vec![];
+  let next_content = |from: usize| (from..lines.len()).find(|j| !is_blank_or_comment(lines[*j]));
+
+  while *i < lines.len() {
+    let line = lines[*i];
+
+    if is_blank_or_comment(line) {
+      pending.push(String::from(line));
+      *i += 1;
+      continue;
+    }
+
+    let line_indent = indentation(line);
+    if line_indent < indent {
+      break;
+    }
+    if line_indent > indent {
+      return Err(format!("line {}: unexpected indentation", *i + 1));
+    }
+
+    let (key, value) =
+      yaml_key(line.trim_start()).ok_or_else(|| format!("line {}: expected `key:`", *i + 1))?;
+    let mut entry = Entry {
+      key,
+      lines: std::mem::take(pending),
+      children: vec![],
+    };
+    entry.lines.push(String::from(line));
+    *i += 1;
+
+    let nested = next_content(*i).filter(|j| {
+      value.is_empty() && indentation(lines[*j]) > line_indent && !is_list_item(lines[*j])
+    });
+
+    match nested {
+      Some(j) => entry.children = yaml_block(lines, i, indentation(lines[j]), pending)?,
+      // a list, a block scalar or a string continued on the following lines
+      None => {
+        while let Some(j) = next_content(*i) {
+          let more = indentation(lines[j]) > line_indent
+            || (indentation(lines[j]) == line_indent && is_list_item(lines[j]));
+          if !more {
+            break;
+          }
+
+          entry
+            .lines
+            .extend(lines[*i..=j].iter().map(|line| String::from(*line)));
+          *i = j + 1;
+        }
+      }
+    }
+
+    entries.push(entry);
+  }
+
+  Ok(number_duplicates(entries))
+}
+
+fn is_list_item(line: &str) -> bool {
+  let line = line.trim_start();
+  line == "-" || line.starts_with("- ")
+}
+
+// key of a `key: value` line and the value after it, comments dropped
+fn yaml_key(text: &str) -> Option<(String, &str)> {
+  if is_list_item(text) || text.starts_with(['?', '{', '[', '&', '*', '!', '|', '>']) {
+    return None;
+  }
+
+  let end = match text.chars().next()? {
+    quote @ ('"' | '\'') => text[1..].find(quote)? + 2,
+    _ => text
+      .find(": ")
+      .or_else(|| text.strip_suffix(':').map(str::len))?,
+  };
+
+  let rest = text[end..].strip_prefix(':')?;
+  if !(rest.is_empty() || rest.starts_with(' ')) {
+    return None;
+  }
+
+  let value = rest.trim();
+  let value = if value.starts_with('#') { "" } else { value };
+
+  Some((String::from(&text[..end]), value))
+}
+
+// key = value pairs before the first table and one entry per table with its pairs as children
+pub fn parse_toml(text: &str) -> Result<Vec<Entry>, String> {
+  let lines: Vec<&str> = text.lines().collect();
+  let mut i = 0;
+  let mut pending = vec![];
+  let mut entries = vec![];
+  let mut table: Option<Entry> = None;
+
+  while i < lines.len() {
+    let line = lines[i];
+    let trimmed = line.trim();
+
+    if is_blank_or_comment(line) {
+      pending.push(String::from(line));
+      i += 1;
+      continue;
+    }
+
+    if trimmed.starts_with('[') {
+      let end = trimmed
+        .rfind(']')
+        .ok_or_else(|| format!("line {}: unclosed table header", i + 1))?;
+
+      if let Some(mut table) = table.take() {
+        table.children = number_duplicates(table.children);
+        entries.push(table);
+      }
+
+      let mut lines = std::mem::take(&mut pending);
+      lines.push(String::from(line));
+      table = Some(Entry {
+        key: String::from(&trimmed[..=end]),
+        lines,
+        children: vec![],
+      });
+      i += 1;
+      continue;
+    }
+
+    let (key, value) = trimmed
+      .split_once('=')
+      .ok_or_else(|| format!("line {}: expected `key = value`", i + 1))?;
+    let mut entry = Entry {
+      key: String::from(key.trim()),
+      lines: std::mem::take(&mut pending),
+      children: vec![],
+    };
+    let mut value = String::from(value);
+    entry.lines.push(String::from(line));
+    i += 1;
+
+    // arrays and multi-line strings go on until they are closed
+    while toml_open(&value) {
+      let line = lines
+        .get(i)
+        .ok_or_else(|| format!("value of `{}` is never closed", entry.key))?;
+      value.push('\n');
+      value.push_str(line);
+      entry.lines.push(String::from(*line));
+      i += 1;
+    }
+
+    match &mut table {
+      Some(table) => table.children.push(entry),
+      None => entries.push(entry),
+    }
+  }
+
+  if let Some(mut table) = table {
+    table.children = number_duplicates(table.children);
+    entries.push(table);
+  }
+
+  let mut entries = number_duplicates(entries);
+  trailer(&mut entries, pending);
+  Ok(entries)
+}
+
+// an array or inline table not closed yet, or a multi-line string still going on
+fn toml_open(value: &str) -> bool {
+  let mut depth = 0;
+  let mut string: Option<&str> = None;
+  let mut i = 0;
+
+  while i < value.len() {
+    let rest = &value[i..];
+
+    match string {
+      Some(quote) if rest.starts_with(quote) => {
+        string = None;
+        i += quote.len();
+        continue;
+      }
+      Some("\"" | "'") if rest.starts_with('\n') => string = None,
+      Some(quote) if quote.starts_with('"') && rest.starts_with('\\') => {
+        i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+        continue;
+      }
+      Some(_) => (),
+      None => {
+        if let Some(quote) = ["\"\"\"", "'''", "\"", "'"]
+          .into_iter()
+          .find(|quote| rest.starts_with(quote))
+        {
+          string = Some(quote);
+          i += quote.len();
+          continue;
+        }
+
+        match rest.chars().next() {
+          Some('#') => {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+          }
+          Some('[' | '{') => depth += 1,
+          Some(']' | '}') => depth -= 1,
+          _ => (),
+        }
+      }
+    }
+
+    i += rest.chars().next().map_or(1, char::len_utf8);
+  }
+
+  depth > 0 || matches!(string, Some("\"\"\"" | "'''"))
+}
+
+// three-way merge of the entries of one level, keys only one side has are taken as added by it
+pub fn merge(base: &[Entry], ours: &[Entry], theirs: &[Entry]) -> Vec<Merged> {
+  keys(ours, theirs)
+    .into_iter()
+    .filter_map(|key| merge_entry(find(base, key), find(ours, key), find(theirs, key)))
+    .collect()
+}
+
+fn merge_entry(
+  base: Option<&Entry>,
+  ours: Option<&Entry>,
+  theirs: Option<&Entry>,
+) -> Option<Merged> {
+  if ours == theirs || theirs == base {
+    return ours.cloned().map(Merged::Entry);
+  }
+
+  if ours == base {
+    return theirs.cloned().map(Merged::Entry);
+  }
+
+  let base_lines = base.map(|base| &base.lines);
+
+  match (ours, theirs) {
+    (Some(ours), Some(theirs))
+      if !ours.children.is_empty()
+        && !theirs.children.is_empty()
+        && (ours.lines == theirs.lines
+          || base_lines == Some(&ours.lines)
+          || base_lines == Some(&theirs.lines)) =>
+    {
+      let lines = match base_lines == Some(&ours.lines) {
+        true => theirs.lines.clone(),
+        false => ours.lines.clone(),
+      };
+      let base = base.map_or(&[][..], |base| &base.children);
+
+      Some(Merged::Parent(
+        lines,
+        merge(base, &ours.children, &theirs.children),
+      ))
+    }
+    _ => Some(Merged::Conflict(ours.cloned(), theirs.cloned())),
+  }
+}
+
+fn find<'a>(entries: &'a [Entry], key: &str) -> Option<&'a Entry> {
+  entries.iter().find(|entry| entry.key == key)
+}
+
+// keys in our order, their new keys go after the key they follow on their side
+fn keys<'a>(ours: &'a [Entry], theirs: &'a [Entry]) -> Vec<&'a str> {
+  let mut keys: Vec<&str> = ours.iter().map(|entry| entry.key.as_str()).collect();
+
+  for (i, entry) in theirs.iter().enumerate() {
+    if keys.contains(&entry.key.as_str()) {
+      continue;
+    }
+
+    let position = theirs[..i]
+      .iter()
+      .rev()
+      .find_map(|previous| keys.iter().position(|key| *key == previous.key))
+      .map_or(0, |position| position + 1);
+    keys.insert(position, &entry.key);
+  }
+
+  keys
+}
+
+// merged document with a conflict block for every entry the sides disagree on, `markers` are
+// the `<<<<<<<`, `=======` and `>>>>>>>` lines to use; returns the lines and the conflict count
+pub fn print_merged(merged: &[Merged], markers: [&str; 3]) -> (Vec<String>, usize) {
+  let mut lines = vec![];
+  let mut conflicts = 0;
+
+  for merged in merged {
+    print(merged, markers, &mut lines, &mut conflicts);
+  }
+
+  (lines, conflicts)
+}
+
+fn print(merged: &Merged, markers: [&str; 3], lines: &mut Vec<String>, conflicts: &mut usize) {
+  match merged {
+    Merged::Entry(entry) => print_entry(entry, lines),
+    Merged::Parent(own, children) => {
+      lines.extend(own.iter().cloned());
+      for child in children {
+        print(child, markers, lines, conflicts);
+      }
+    }
+    Merged::Conflict(ours, theirs) => {
+      *conflicts += 1;
+      lines.push(String::from(markers[0]));
+      for (side, marker) in [(ours, markers[1]), (theirs, markers[2])] {
+        if let Some(entry) = side {
+          print_entry(entry, lines);
+        }
+        lines.push(String::from(marker));
+      }
+    }
+  }
+}
+
+fn print_entry(entry: &Entry, lines: &mut Vec<String>) {
+  lines.extend(entry.lines.iter().cloned());
+  for child in &entry.children {
+    print_entry(child, lines);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn parse_yaml() {
+    let entries =
+      crate::outline::parse_yaml("a: 1\n# b\nb:\n  c: 2\n  d:\n    - x\n    - y\ne: |\n  text\n")
+        .unwrap();
+
+    assert_eq!(
+      entries
+        .iter()
+        .map(|entry| entry.key.as_str())
+        .collect::<Vec<_>>(),
+      vec!["a", "b", "e"]
+    );
+    assert_eq!(entries[1].lines, vec!["# b", "b:"]);
+    assert_eq!(
+      entries[1].children[1].lines,
+      vec!["  d:", "    - x", "    - y"]
+    );
+    assert_eq!(entries[2].lines, vec!["e: |", "  text"]);
+
+    assert!(crate::outline::parse_yaml("- a\n- b\n").is_err());
+    assert!(crate::outline::parse_yaml("a:\n  b: 1\n c: 2\n").is_err());
+  }
+
+  #[test]
+  fn yaml_key() {
+    assert_eq!(
+      crate::outline::yaml_key("key: value # note"),
+      Some((String::from("key"), "value # note"))
+    );
+    assert_eq!(
+      crate::outline::yaml_key("\"a: b\": # note"),
+      Some((String::from("\"a: b\""), ""))
+    );
+    assert_eq!(crate::outline::yaml_key("http://x"), None);
+  }
+
+  #[test]
+  fn parse_toml() {
+    let entries = crate::outline::parse_toml(
+      "name = \"x\"\n\n[dependencies]\na = [\n  \"1\",\n]\n\n[[bin]]\n[[bin]]\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+      entries
+        .iter()
+        .map(|entry| entry.key.as_str())
+        .collect::<Vec<_>>(),
+      vec!["name", "[dependencies]", "[[bin]]", "[[bin]]#2"]
+    );
+    assert_eq!(entries[1].lines, vec!["", "[dependencies]"]);
+    assert_eq!(entries[1].children[0].lines, vec!["a = [", "  \"1\",", "]"]);
+
+    assert!(crate::outline::parse_toml("a = [\n").is_err());
+    assert!(crate::outline::parse_toml("a\n").is_err());
+  }
+
+  #[test]
+  fn toml_open() {
+    assert!(crate::outline::toml_open(" [1, # ]\n"));
+    assert!(crate::outline::toml_open(" \"\"\"text"));
+    assert!(!crate::outline::toml_open(" \"[\" # ["));
+  }
+
+  #[test]
+  fn merge() {
+    let parse = |text| crate::outline::parse_toml(text).unwrap();
+    let base = parse("a = 1\n\n[t]\nb = 1\nc = 1\n");
+    let ours = parse("a = 1\n\n[t]\nb = 2\nc = 1\n");
+    let theirs = parse("a = 1\nd = 4\n\n[t]\nb = 1\nc = 3\n");
+
+    let merged = crate::outline::merge(&base, &ours, &theirs);
+    let (lines, conflicts) = crate::outline::print_merged(&merged, ["<", "=", ">"]);
+
+    assert_eq!(conflicts, 0);
+    assert_eq!(lines, vec!["a = 1", "d = 4", "", "[t]", "b = 2", "c = 3"]);
+
+    let theirs = parse("a = 1\n\n[t]\nb = 3\nc = 1\n");
+    let merged = crate::outline::merge(&base, &ours, &theirs);
+    let (lines, conflicts) = crate::outline::print_merged(&merged, ["<", "=", ">"]);
+
+    assert_eq!(conflicts, 1);
+    assert_eq!(lines[3..], ["<", "b = 2", "=", "b = 3", ">", "c = 1"]);
+  }
+}
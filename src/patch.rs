@@ -0,0 +1,182 @@
+// lines of context around each change, like `diff -u`
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+  Keep,
+  Delete,
+  Insert,
+}
+
+// unified diff turning `old` into `new`, empty when they are the same
+pub fn unified(old: &[String], new: &[String], old_name: &str, new_name: &str) -> Vec<String> {
+  let edits = edits(old, new);
+  let mut lines = vec![];
+  // position in old and new before each edit
+  let mut positions = Vec::with_capacity(edits.len());
+  let (mut o, mut n) = (0, 0);
+
+  for edit in &edits {
+    positions.push((o, n));
+    match edit {
+      Edit::Keep => (o, n) = (o + 1, n + 1),
+      Edit::Delete => o += 1,
+      Edit::Insert => n += 1,
+    }
+  }
+
+  let changes: Vec<usize> = (0..edits.len())
+    .filter(|i| edits[*i] != Edit::Keep)
+    .collect();
+  let mut i = 0;
+
+  while i < changes.len() {
+    // changes closer than twice the context share a hunk
+    let mut last = i;
+    while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT {
+      last += 1;
+    }
+
+    let start = changes[i].saturating_sub(CONTEXT);
+    let end = (changes[last] + CONTEXT + 1).min(edits.len());
+    let count = |kind: Edit| {
+      edits[start..end]
+        .iter()
+        .filter(|edit| **edit == Edit::Keep || **edit == kind)
+        .count()
+    };
+    let range = |position: usize, count: usize| match count {
+      0 => format!("{},0", position),
+      1 => format!("{}", position + 1),
+      count => format!("{},{}", position + 1, count),
+    };
+
+    if lines.is_empty() {
+      lines.push(format!("--- {}", old_name));
+      lines.push(format!("+++ {}", new_name));
+    }
+    lines.push(format!(
+      "@@ -{} +{} @@",
+      range(positions[start].0, count(Edit::Delete)),
+      range(positions[start].1, count(Edit::Insert))
+    ));
+
+    for (edit, (o, n)) in edits[start..end].iter().zip(&positions[start..end]) {
+      lines.push(match edit {
+        Edit::Keep => format!(" {}", old[*o]),
+        Edit::Delete => format!("-{}", old[*o]),
+        Edit::Insert => format!("+{}", new[*n]),
+      });
+    }
+
+    i = last + 1;
+  }
+
+  lines
+}
+
+// shortest edit script by Myers' algorithm, `trace` keeps the furthest reaching x of every
+// diagonal after each step so the path can be walked back
+fn edits(old: &[String], new: &[String]) -> Vec<Edit> {
+  let (n, m) = (old.len() as isize, new.len() as isize);
+  let offset = n + m + 1;
+  let mut v = vec![0isize; 2 * offset as usize + 1];
+  let mut trace: Vec<Vec<isize>> = vec![];
+  let at = |k: isize| (offset + k) as usize;
+
+  'search: for d in 0..=(n + m) {
+    trace.push(v[at(-d - 1)..=at(d + 1)].to_vec());
+
+    for k in (-d..=d).step_by(2) {
+      let mut x = match k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+        true => v[at(k + 1)],
+        false => v[at(k - 1)] + 1,
+      };
+      let mut y = x - k;
+
+      while x < n && y < m && old[x as usize] == new[y as usize] {
+        x += 1;
+        y += 1;
+      }
+      v[at(k)] = x;
+
+      if x >= n && y >= m {
+        break 'search;
+      }
+    }
+  }
+
+  let mut edits = vec![];
+  let (mut x, mut y) = (n, m);
+
+  for (d, v) in trace.iter().enumerate().rev() {
+    let d = d as isize;
+    let get = |k: isize| v[(k + d + 1) as usize];
+    let k = x - y;
+    let previous = match k == -d || (k != d && get(k - 1) < get(k + 1)) {
+      true => k + 1,
+      false => k - 1,
+    };
+    let previous_x = get(previous);
+    let previous_y = previous_x - previous;
+
+    while x > previous_x && y > previous_y {
+      edits.push(Edit::Keep);
+      x -= 1;
+      y -= 1;
+    }
+
+    if d > 0 {
+      edits.push(match x == previous_x {
+        true => Edit::Insert,
+        false => Edit::Delete,
+      });
+    }
+
+    (x, y) = (previous_x, previous_y);
+  }
+
+  edits.reverse();
+  edits
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::patch::Edit;
+
+  fn lines(text: &str) -> Vec<String> {
+    text.lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn edits() {
+    assert_eq!(
+      crate::patch::edits(&lines("a\nb\nc"), &lines("a\nx\nc\nd")),
+      vec![
+        Edit::Keep,
+        Edit::Delete,
+        Edit::Insert,
+        Edit::Keep,
+        Edit::Insert
+      ]
+    );
+    assert_eq!(crate::patch::edits(&[], &[]), vec![]);
+    assert_eq!(crate::patch::edits(&lines("a"), &[]), vec![Edit::Delete]);
+  }
+
+  #[test]
+  fn unified() {
+    let old =
+      lines("1\n2\n3\n4\n<<<<<<<\nL\n=======\nR\n>>>>>>>\n5\n6\n7\n8\n9\n10\n11\n12\n13\nx");
+    let new = lines("1\n2\n3\n4\nR\n5\n6\n7\n8\n9\n10\n11\n12\n13\ny");
+
+    assert_eq!(
+      crate::patch::unified(&old, &new, "a/f", "b/f"),
+      lines(
+        "--- a/f\n+++ b/f\n@@ -2,11 +2,7 @@\n 2\n 3\n 4\n-<<<<<<<\n-L\n-=======\n R\n->>>>>>>\n 5\n 6\n \
+         7\n@@ -16,4 +12,4 @@\n 11\n 12\n 13\n-x\n+y"
+      )
+    );
+    assert!(crate::patch::unified(&old, &old, "a/f", "b/f").is_empty());
+  }
+}
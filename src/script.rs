@@ -0,0 +1,152 @@
+// side a script command picks, `union` keeps both with local first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+  Ours,
+  Theirs,
+  Union,
+}
+
+// one line of a script, numbers are 1-based like everywhere the user sees them
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+  File(usize),
+  Conflict(usize, Side),
+  Line(usize, Side),
+  Unresolve(usize),
+  All(Side),
+  Remaining(Side),
+  Key(Key),
+  Write,
+  WriteAll,
+}
+
+// key press for popups and anything without a command of its own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+  Char(char),
+  Enter,
+  Esc,
+  Tab,
+  Up,
+  Down,
+  Backspace,
+}
+
+// commands with the line they are on, blank lines and `#` comments are skipped
+pub fn parse(text: &str) -> Result<Vec<(usize, Command)>, String> {
+  let mut commands = vec![];
+
+  for (i, line) in text.lines().enumerate() {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let command = command(line).map_err(|err| format!("line {}: {}", i + 1, err))?;
+    commands.push((i + 1, command));
+  }
+
+  Ok(commands)
+}
+
+fn command(line: &str) -> Result<Command, String> {
+  // a key can be `:` itself
+  if let Some(name) = line.strip_prefix("key ") {
+    return Ok(Command::Key(key(name.trim())?));
+  }
+
+  let (target, what) = match line.split_once(':') {
+    Some((target, what)) => (target.trim(), Some(what.trim())),
+    None => (line, None),
+  };
+  let (name, argument) = match target.split_once(' ') {
+    Some((name, argument)) => (name, argument.trim()),
+    None => (target, ""),
+  };
+  let number = || match argument.parse::<usize>() {
+    Ok(number) if number > 0 => Ok(number),
+    _ => Err(format!(
+      "`{}` needs a number from 1, got `{}`",
+      name, argument
+    )),
+  };
+  // conflicts and lines are decided by one side, only the rest can be a union
+  let one_side = |side: Side| match side {
+    Side::Union => Err(format!("`{}` takes ours or theirs", name)),
+    side => Ok(side),
+  };
+
+  match (name, what) {
+    ("file", None) => Ok(Command::File(number()?)),
+    ("conflict", Some(what)) => Ok(Command::Conflict(number()?, one_side(side(what)?)?)),
+    ("line", Some("unresolve")) => Ok(Command::Unresolve(number()?)),
+    ("line", Some(what)) => Ok(Command::Line(number()?, one_side(side(what)?)?)),
+    ("all", Some(what)) if argument.is_empty() => Ok(Command::All(one_side(side(what)?)?)),
+    ("remaining", Some(what)) if argument.is_empty() => Ok(Command::Remaining(side(what)?)),
+    ("write", None) if argument.is_empty() => Ok(Command::Write),
+    ("write", None) if argument == "all" => Ok(Command::WriteAll),
+    _ => Err(format!("unknown command `{}`", line)),
+  }
+}
+
+fn side(what: &str) -> Result<Side, String> {
+  match what {
+    "ours" | "local" => Ok(Side::Ours),
+    "theirs" | "incoming" => Ok(Side::Theirs),
+    "union" | "both" => Ok(Side::Union),
+    _ => Err(format!("`{}` is not ours, theirs or union", what)),
+  }
+}
+
+fn key(name: &str) -> Result<Key, String> {
+  let mut chars = name.chars();
+
+  match (chars.next(), chars.next(), name.to_lowercase().as_str()) {
+    (Some(c), None, _) => Ok(Key::Char(c)),
+    (_, _, "enter") => Ok(Key::Enter),
+    (_, _, "esc") => Ok(Key::Esc),
+    (_, _, "tab") => Ok(Key::Tab),
+    (_, _, "up") => Ok(Key::Up),
+    (_, _, "down") => Ok(Key::Down),
+    (_, _, "backspace") => Ok(Key::Backspace),
+    _ => Err(format!("unknown key `{}`", name)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::script::{Command, Key, Side};
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      crate::script::parse(
+        "# take theirs here\nconflict 3: theirs\n\nline 120: ours\nline 4: unresolve\n\
+         remaining: union\nkey :\nkey Enter\nfile 2\nwrite\nwrite all"
+      ),
+      Ok(vec![
+        (2, Command::Conflict(3, Side::Theirs)),
+        (4, Command::Line(120, Side::Ours)),
+        (5, Command::Unresolve(4)),
+        (6, Command::Remaining(Side::Union)),
+        (7, Command::Key(Key::Char(':'))),
+        (8, Command::Key(Key::Enter)),
+        (9, Command::File(2)),
+        (10, Command::Write),
+        (11, Command::WriteAll),
+      ])
+    );
+
+    assert_eq!(
+      crate::script::parse("write\nconflict 0: ours"),
+      Err(String::from(
+        "line 2: `conflict` needs a number from 1, got `0`"
+      ))
+    );
+    assert!(crate::script::parse("line 3: union").is_err());
+    assert!(crate::script::parse("all: maybe").is_err());
+    assert!(crate::script::parse("key F13").is_err());
+    assert!(crate::script::parse("resolve everything").is_err());
+  }
+}
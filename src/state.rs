@@ -0,0 +1,149 @@
+// progress on a file left unwritten, kept until the same conflicted file is opened again
+#[derive(Debug, Default, PartialEq)]
+pub struct State {
+  pub current_line: usize,
+  // start and end of each conflict, lines picked from a popup or pasted move them
+  pub conflicts: Vec<(usize, usize)>,
+  // local, result and incoming buffers, a line is its change (' ', '+' or '-') and its text
+  pub buffers: [Vec<(char, String)>; 3],
+}
+
+const BUFFERS: [&str; 3] = ["local", "result", "incoming"];
+
+impl State {
+  pub fn to_text(&self) -> String {
+    let mut text = format!("line {}\n", self.current_line);
+
+    for (start, end) in &self.conflicts {
+      text.push_str(&format!("conflict {} {}\n", start, end));
+    }
+
+    for (name, lines) in BUFFERS.iter().zip(&self.buffers) {
+      text.push_str(&format!("{} {}\n", name, lines.len()));
+      for (change, value) in lines {
+        text.push(*change);
+        text.push_str(value);
+        text.push('\n');
+      }
+    }
+
+    text
+  }
+
+  pub fn parse(text: &str) -> Result<State, String> {
+    let mut state = State::default();
+    let mut lines = text.lines();
+    let number = |value: &str| {
+      value
+        .parse::<usize>()
+        .map_err(|_| format!("`{}` is not a number", value))
+    };
+
+    while let Some(line) = lines.next() {
+      let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+      match (key, BUFFERS.iter().position(|name| *name == key)) {
+        ("line", _) => state.current_line = number(value)?,
+        ("conflict", _) => {
+          let (start, end) = value
+            .split_once(' ')
+            .ok_or_else(|| format!("`{}` is not a range", value))?;
+          state.conflicts.push((number(start)?, number(end)?));
+        }
+        (_, Some(buffer)) => {
+          for _ in 0..number(value)? {
+            let line = lines.next().ok_or_else(|| format!("{} ends early", key))?;
+            let mut chars = line.chars();
+            let change = chars.next().unwrap_or(' ');
+            state.buffers[buffer].push((change, String::from(chars.as_str())));
+          }
+        }
+        _ => return Err(format!("unknown line `{}`", line)),
+      }
+    }
+
+    Ok(state)
+  }
+}
+
+// state of the file with this content, read back only if nothing changed in between
+pub fn load(file_name: &str, content: &str) -> Option<State> {
+  let text = std::fs::read_to_string(path(file_name, content)?).ok()?;
+  State::parse(&text).ok()
+}
+
+pub fn save(file_name: &str, content: &str, state: &State) -> std::io::Result<()> {
+  let path = path(file_name, content)
+    .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no state directory"))?;
+
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+
+  std::fs::write(path, state.to_text())
+}
+
+pub fn remove(file_name: &str, content: &str) {
+  if let Some(path) = path(file_name, content) {
+    let _ = std::fs::remove_file(path);
+  }
+}
+
+// named after the absolute path and the content, editing the file outside leaves the state behind
+fn path(file_name: &str, content: &str) -> Option<std::path::PathBuf> {
+  let dir = match std::env::var_os("XDG_STATE_HOME") {
+    Some(dir) => std::path::Path::new(&dir).join("mersge"),
+    None => std::path::Path::new(&std::env::var_os("HOME")?).join(".local/state/mersge"),
+  };
+  let absolute = std::fs::canonicalize(file_name).unwrap_or_else(|_| file_name.into());
+
+  Some(dir.join(format!(
+    "{:016x}",
+    hash(&format!("{}\0{}", absolute.display(), content))
+  )))
+}
+
+// FNV-1a, stable across builds unlike the standard hasher
+fn hash(text: &str) -> u64 {
+  text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn parse() {
+    let state = crate::state::State {
+      current_line: 2,
+      conflicts: vec![(1, 3)],
+      buffers: [
+        vec![
+          (' ', String::from("a")),
+          ('+', String::from("L")),
+          (' ', String::new()),
+        ],
+        vec![
+          (' ', String::from("a")),
+          ('-', String::from("#")),
+          ('+', String::from("P")),
+        ],
+        vec![
+          (' ', String::from("a")),
+          ('+', String::from("R ")),
+          (' ', String::new()),
+        ],
+      ],
+    };
+
+    assert_eq!(crate::state::State::parse(&state.to_text()), Ok(state));
+    assert!(crate::state::State::parse("local 2\n+L").is_err());
+    assert!(crate::state::State::parse("cursor 1").is_err());
+  }
+
+  #[test]
+  fn hash() {
+    assert_eq!(crate::state::hash(""), 0xcbf29ce484222325);
+    assert_ne!(crate::state::hash("a"), crate::state::hash("b"));
+  }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+// text of a line in the buffers, an unchanged line is shown by all three of them and they
+// share one copy of it instead of holding three
+#[derive(Clone, Default, PartialEq)]
+pub struct Text(Arc<str>);
+
+impl Text {
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl std::ops::Deref for Text {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    &self.0
+  }
+}
+
+impl From<&str> for Text {
+  fn from(value: &str) -> Text {
+    Text(Arc::from(value))
+  }
+}
+
+impl From<String> for Text {
+  fn from(value: String) -> Text {
+    Text(Arc::from(value))
+  }
+}
+
+impl PartialEq<&str> for Text {
+  fn eq(&self, other: &&str) -> bool {
+    &*self.0 == *other
+  }
+}
+
+// lets a slice of them be joined like strings
+impl std::borrow::Borrow<str> for Text {
+  fn borrow(&self) -> &str {
+    &self.0
+  }
+}
+
+impl std::fmt::Debug for Text {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&*self.0, f)
+  }
+}
+
+impl std::fmt::Display for Text {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::text::Text;
+
+  #[test]
+  fn shared() {
+    let text = Text::from("a line");
+    let copy = text.clone();
+
+    assert_eq!(copy, "a line");
+    assert_eq!(copy.as_str().as_ptr(), text.as_str().as_ptr());
+    assert_eq!(format!("{:?} {}", text, text), "\"a line\" a line");
+  }
+}
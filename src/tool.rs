@@ -0,0 +1,283 @@
+use crossterm::{event, terminal};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// give the terminal back to an external program while `f` runs
+pub fn suspend<T>(f: impl FnOnce() -> T) -> T {
+  let mut stdout = std::io::stdout();
+
+  let _ = terminal::disable_raw_mode();
+  let _ = crossterm::execute!(
+    stdout,
+    terminal::LeaveAlternateScreen,
+    event::DisableMouseCapture
+  );
+
+  let value = f();
+
+  let _ = crossterm::execute!(
+    stdout,
+    terminal::EnterAlternateScreen,
+    event::EnableMouseCapture
+  );
+  let _ = terminal::enable_raw_mode();
+
+  value
+}
+
+// a directory only the user can enter, a fresh name is tried until one does not exist yet so
+// nothing another user put in the temp dir is ever written through
+fn private_dir() -> Result<PathBuf, String> {
+  static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+  loop {
+    let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("mersge-{}-{}", std::process::id(), count));
+    let mut builder = std::fs::DirBuilder::new();
+
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+    match builder.create(&path) {
+      Ok(()) => return Ok(path),
+      Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+      Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    }
+  }
+}
+
+// `a.rs` gives `a_LOCAL.rs` like `git mergetool`, so tools still highlight the language
+fn temp_file(dir: &Path, file_name: &str, name: &str, lines: &[String]) -> Result<PathBuf, String> {
+  let file_name = Path::new(file_name);
+  let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+  let path = match file_name.extension() {
+    Some(extension) => dir.join(format!("{}_{}.{}", stem, name, extension.to_string_lossy())),
+    None => dir.join(format!("{}_{}", stem, name)),
+  };
+  let mut content = lines.join("\n");
+
+  if !lines.is_empty() {
+    content.push('\n');
+  }
+
+  std::fs::OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .open(&path)
+    .and_then(|mut file| file.write_all(content.as_bytes()))
+    .map_err(|err| format!("{}: {}", path.display(), err))?;
+
+  Ok(path)
+}
+
+// run a merge tool command the way `git mergetool` does, the shell sees the files as
+// $LOCAL, $BASE, $REMOTE and $MERGED, returns the merged lines; $MERGED starts out with the
+// conflict in markers of `marker_size`
+pub fn merge(
+  command: &str,
+  file_name: &str,
+  marker_size: usize,
+  local: &[String],
+  base: &[String],
+  remote: &[String],
+) -> Result<Vec<String>, String> {
+  let marker = |c: char| c.to_string().repeat(marker_size);
+  let mut merged = vec![format!("{} local", marker('<'))];
+  merged.extend_from_slice(local);
+  merged.push(marker('='));
+  merged.extend_from_slice(remote);
+  merged.push(format!("{} incoming", marker('>')));
+
+  match run(command, file_name, local, base, remote, &merged)? {
+    (status, lines) if status.success() => Ok(lines),
+    (status, _) => Err(format!("merge tool exited with {}", status)),
+  }
+}
+
+// run a merge driver on whole files like git does, $MERGED starts out as the local side and the
+// driver leaves the result in it; a driver that fails may leave conflict markers there, returns
+// the merged lines and whether the driver succeeded
+pub fn driver(
+  command: &str,
+  file_name: &str,
+  local: &[String],
+  base: &[String],
+  remote: &[String],
+) -> Result<(Vec<String>, bool), String> {
+  let (status, lines) = run(command, file_name, local, base, remote, local)?;
+  Ok((lines, status.success()))
+}
+
+// show the two sides of a conflict with a diff command, the shell sees them as $LOCAL and
+// $REMOTE; diff tools exit with 1 when the files differ so only a missing command fails
+pub fn diff(
+  command: &str,
+  file_name: &str,
+  local: &[String],
+  remote: &[String],
+) -> Result<(), String> {
+  match run(command, file_name, local, &[], remote, &[])? {
+    (status, _) if status.code() == Some(127) => Err(format!("`{}` not found", command)),
+    _ => Ok(()),
+  }
+}
+
+fn run(
+  command: &str,
+  file_name: &str,
+  local: &[String],
+  base: &[String],
+  remote: &[String],
+  merged: &[String],
+) -> Result<(std::process::ExitStatus, Vec<String>), String> {
+  let dir = private_dir()?;
+  let result = run_in(&dir, command, file_name, local, base, remote, merged);
+
+  let _ = std::fs::remove_dir_all(&dir);
+  result
+}
+
+fn run_in(
+  dir: &Path,
+  command: &str,
+  file_name: &str,
+  local: &[String],
+  base: &[String],
+  remote: &[String],
+  merged: &[String],
+) -> Result<(std::process::ExitStatus, Vec<String>), String> {
+  let paths = [
+    temp_file(dir, file_name, "LOCAL", local)?,
+    temp_file(dir, file_name, "BASE", base)?,
+    temp_file(dir, file_name, "REMOTE", remote)?,
+    temp_file(dir, file_name, "MERGED", merged)?,
+  ];
+
+  let status = Command::new("sh")
+    .arg("-c")
+    .arg(command)
+    .env("LOCAL", &paths[0])
+    .env("BASE", &paths[1])
+    .env("REMOTE", &paths[2])
+    .env("MERGED", &paths[3])
+    .status();
+
+  match status {
+    Ok(status) => std::fs::read_to_string(&paths[3])
+      .map(|content| (status, content.lines().map(String::from).collect()))
+      .map_err(|err| err.to_string()),
+    Err(err) => Err(err.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn private_dir() {
+    let (a, b) = (
+      crate::tool::private_dir().unwrap(),
+      crate::tool::private_dir().unwrap(),
+    );
+    assert_ne!(a, b);
+
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      let mode = std::fs::metadata(&a).unwrap().permissions().mode();
+      assert_eq!(mode & 0o777, 0o700);
+    }
+
+    // a name taken before is not written through
+    std::fs::write(a.join("a_LOCAL.txt"), "").unwrap();
+    assert!(crate::tool::temp_file(&a, "a.txt", "LOCAL", &[]).is_err());
+
+    std::fs::remove_dir_all(&a).unwrap();
+    std::fs::remove_dir_all(&b).unwrap();
+  }
+
+  #[test]
+  fn merge() {
+    let lines = |values: &[&str]| {
+      values
+        .iter()
+        .map(|value| String::from(*value))
+        .collect::<Vec<_>>()
+    };
+
+    let merged = crate::tool::merge(
+      "cat \"$LOCAL\" \"$REMOTE\" > \"$MERGED\"",
+      "a.txt",
+      7,
+      &lines(&["L1"]),
+      &[],
+      &lines(&["R1", "R2"]),
+    )
+    .unwrap();
+    assert_eq!(merged, vec!["L1", "R1", "R2"]);
+
+    assert_eq!(
+      crate::tool::merge(
+        "grep -c '^<<<<<<<<<< ' \"$MERGED\" > \"$MERGED\".n; mv \"$MERGED\".n \"$MERGED\"",
+        "a.txt",
+        10,
+        &[],
+        &[],
+        &[]
+      )
+      .unwrap(),
+      vec!["1"]
+    );
+    assert_eq!(
+      crate::tool::merge(
+        "basename \"$LOCAL\" > \"$MERGED\"",
+        "src/a.rs",
+        7,
+        &[],
+        &[],
+        &[]
+      )
+      .unwrap(),
+      vec!["a_LOCAL.rs"]
+    );
+    assert!(crate::tool::merge("exit 1", "a.txt", 7, &[], &[], &[]).is_err());
+  }
+
+  #[test]
+  fn driver() {
+    let lines = |values: &[&str]| {
+      values
+        .iter()
+        .map(|value| String::from(*value))
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      crate::tool::driver(
+        "cat \"$REMOTE\" >> \"$MERGED\"",
+        "a.txt",
+        &lines(&["L1"]),
+        &[],
+        &lines(&["R1"])
+      )
+      .unwrap(),
+      (lines(&["L1", "R1"]), true)
+    );
+    assert_eq!(
+      crate::tool::driver("exit 1", "a.txt", &lines(&["L1"]), &[], &[]).unwrap(),
+      (lines(&["L1"]), false)
+    );
+  }
+
+  #[test]
+  fn diff() {
+    assert!(crate::tool::diff(
+      "cmp -s \"$LOCAL\" \"$REMOTE\"",
+      "a.txt",
+      &[String::from("L")],
+      &[]
+    )
+    .is_ok());
+    assert!(crate::tool::diff("exit 127", "a.txt", &[], &[]).is_err());
+  }
+}